@@ -0,0 +1,226 @@
+use crate::camera::Camera;
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+// Slots in the accretion event ring buffer; the oldest events are overwritten first
+pub const MAX_ACCRETION_EVENTS: u32 = 256;
+// Simulation frames a flash takes to fade out
+const FLASH_FRAMES: u32 = 20;
+// Half-size of a flash sprite in world units
+const FLASH_SIZE: f32 = 3.0;
+
+// Matches `AccretionEvent` in update.wgsl and accretion.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct AccretionEvent {
+    position: [f32; 3],
+    // Simulation frame of the event plus one; 0 marks an unused slot
+    stamp: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct FlashUniform {
+    view_proj: [[f32; 4]; 4],
+    camera_right: [f32; 4],
+    camera_up: [f32; 4],
+    frame: u32,
+    flash_frames: u32,
+    size: f32,
+    _padding: u32,
+}
+
+// Recent accretion events, written by the compute shader when a particle is
+// absorbed and drawn as fading sprites where matter crossed the horizon
+pub struct AccretionFlashes {
+    events_buffer: wgpu::Buffer,
+    cursor_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    enabled: bool,
+}
+
+impl AccretionFlashes {
+    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat, enabled: bool) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Accretion Flash Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/accretion.wgsl").into()),
+        });
+
+        let events_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Accretion Events Buffer"),
+            contents: bytemuck::cast_slice(
+                &[AccretionEvent::zeroed(); MAX_ACCRETION_EVENTS as usize],
+            ),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let cursor_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Accretion Cursor Buffer"),
+            contents: bytemuck::cast_slice(&[0u32]),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Accretion Flash Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[FlashUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Accretion Flash Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Accretion Flash Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: events_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Accretion Flash Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Accretion Flash Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            // Hidden by particles in front, without occluding anything itself
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            events_buffer,
+            cursor_buffer,
+            uniform_buffer,
+            pipeline,
+            bind_group,
+            enabled,
+        }
+    }
+
+    // Buffers the compute shader appends events to
+    pub fn events_buffer(&self) -> &wgpu::Buffer {
+        &self.events_buffer
+    }
+
+    pub fn cursor_buffer(&self) -> &wgpu::Buffer {
+        &self.cursor_buffer
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera, frame: u32) {
+        if !self.enabled {
+            return;
+        }
+
+        let (right, up) = camera.view_basis();
+        let uniform = FlashUniform {
+            view_proj: camera.build_view_projection_matrix().into(),
+            camera_right: [right.x, right.y, right.z, 0.0],
+            camera_up: [up.x, up.y, up.z, 0.0],
+            frame,
+            flash_frames: FLASH_FRAMES,
+            size: FLASH_SIZE,
+            _padding: 0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // One sprite per event slot; expired and unused slots are collapsed in the vertex shader
+    pub fn render_pass<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if !self.enabled {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..MAX_ACCRETION_EVENTS);
+    }
+}
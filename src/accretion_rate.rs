@@ -0,0 +1,47 @@
+// Particles absorbed per simulated second, from successive readbacks of the
+// absorbed count in the periodic GPU reduction, averaged over a trailing window.
+
+use crate::reduction::PositionStats;
+
+// Simulated seconds the rate is averaged over
+const WINDOW: f64 = 5.0;
+
+#[derive(Default)]
+pub struct AccretionRate {
+    // (time, absorbed count) of the reductions within the window
+    samples: Vec<(f64, u32)>,
+}
+
+impl AccretionRate {
+    // Start over from the next reduction
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    // Feed the latest reduction; repeated results are ignored
+    pub fn observe(&mut self, stats: &PositionStats) {
+        match self.samples.last() {
+            Some(&(time, _)) if stats.time == time => return,
+            // A restart clears the absorbed count and the clock; measuring
+            // across it would read as a negative or spurious rate
+            Some(&(time, absorbed)) if stats.time < time || stats.absorbed < absorbed => {
+                self.reset()
+            }
+            _ => {}
+        }
+        self.samples.push((stats.time, stats.absorbed));
+        let start = stats.time - WINDOW;
+        // Keep the last sample before the window so it spans all of it
+        let first_inside = self.samples.partition_point(|&(t, _)| t < start);
+        self.samples.drain(..first_inside.saturating_sub(1));
+    }
+
+    // Absorbed particles per simulated second, or 0 until two reductions
+    // since the last restart have been seen
+    pub fn rate(&self) -> f32 {
+        match (self.samples.first(), self.samples.last()) {
+            (Some(&(t0, n0)), Some(&(t1, n1))) if t1 > t0 => ((n1 - n0) as f64 / (t1 - t0)) as f32,
+            _ => 0.0,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Rough classification of the GPU and the start-up defaults picked for it, so a
+// laptop's integrated graphics doesn't start on the full particle buffer with
+// every post effect. Only a confident integrated or software match lowers
+// anything; discrete and unrecognised GPUs keep what was configured.
+
+use crate::simulation::NUM_PARTICLES;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuClass {
+    Discrete,
+    Integrated,
+    // CPU rasterizers such as SwiftShader or llvmpipe
+    Software,
+    // Nothing identifying; browsers often hide the adapter details
+    Unknown,
+}
+
+impl GpuClass {
+    pub fn name(self) -> &'static str {
+        match self {
+            GpuClass::Discrete => "discrete",
+            GpuClass::Integrated => "integrated",
+            GpuClass::Software => "software",
+            GpuClass::Unknown => "unknown",
+        }
+    }
+}
+
+// Name fragments, matched against whole words of the lowercased description.
+// Discrete is tried first so Intel Arc isn't taken for Intel integrated graphics.
+const SOFTWARE_WORDS: &[&str] = &["swiftshader", "llvmpipe", "lavapipe", "software", "basic"];
+const DISCRETE_WORDS: &[&str] = &[
+    "nvidia", "geforce", "quadro", "rtx", "gtx", "arc", "hpg", "rx", "firepro",
+];
+const INTEGRATED_WORDS: &[&str] = &[
+    "intel", "uhd", "iris", "apple", "mali", "adreno", "qualcomm", "powervr", "img", "arm",
+];
+
+// PCI vendor IDs whose GPUs are all integrated
+const INTEGRATED_VENDORS: &[u32] = &[0x8086, 0x106b, 0x13b5, 0x5143, 0x1010];
+const NVIDIA_VENDOR: u32 = 0x10de;
+
+// `description` is whatever names the adapter: wgpu's adapter name, or the
+// browser's vendor and architecture strings where wgpu reports none
+pub fn classify(
+    device_type: wgpu::DeviceType,
+    vendor: u32,
+    description: &str,
+    fallback_adapter: bool,
+) -> GpuClass {
+    match device_type {
+        wgpu::DeviceType::DiscreteGpu => return GpuClass::Discrete,
+        wgpu::DeviceType::IntegratedGpu => return GpuClass::Integrated,
+        wgpu::DeviceType::Cpu => return GpuClass::Software,
+        wgpu::DeviceType::VirtualGpu | wgpu::DeviceType::Other => {}
+    }
+    if fallback_adapter {
+        return GpuClass::Software;
+    }
+
+    let description = description.to_lowercase();
+    let words: Vec<&str> = description
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let any = |list: &[&str]| words.iter().any(|word| list.contains(word));
+    if any(SOFTWARE_WORDS) {
+        GpuClass::Software
+    } else if any(DISCRETE_WORDS) || vendor == NVIDIA_VENDOR {
+        GpuClass::Discrete
+    } else if any(INTEGRATED_WORDS) || INTEGRATED_VENDORS.contains(&vendor) {
+        GpuClass::Integrated
+    } else {
+        // AMD makes both, and its names don't reliably say which
+        GpuClass::Unknown
+    }
+}
+
+// What `AppState::auto_tune` applies for a class
+#[derive(Clone, Copy, Debug)]
+pub struct TunedDefaults {
+    // Particle count to drop to; `None` leaves the count alone
+    pub particles: Option<u32>,
+    // False turns off TAA, depth of field and the density glow
+    pub post_effects: bool,
+}
+
+pub fn defaults_for(class: GpuClass) -> TunedDefaults {
+    match class {
+        GpuClass::Discrete | GpuClass::Unknown => TunedDefaults {
+            particles: None,
+            post_effects: true,
+        },
+        GpuClass::Integrated => TunedDefaults {
+            particles: Some(NUM_PARTICLES / 4),
+            post_effects: false,
+        },
+        GpuClass::Software => TunedDefaults {
+            particles: Some(NUM_PARTICLES / 16),
+            post_effects: false,
+        },
+    }
+}
@@ -1,32 +1,247 @@
-use cgmath::{perspective, Deg, EuclideanSpace, Matrix4, Point3, Vector3};
+use crate::config::{CameraConfig, CameraMode};
+use cgmath::{
+    perspective, Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Quaternion, Rad, Rotation3,
+    Vector3,
+};
+
+// Natural log of the zoom ratio per unit of zoom delta
+const ZOOM_RATE: f32 = 0.01;
+// Auto-rotation until configured: rad/s, and seconds of idleness before it starts
+pub const DEFAULT_AUTO_ROTATE_SPEED: f32 = 0.05;
+pub const DEFAULT_AUTO_ROTATE_IDLE: f32 = 30.0;
+// Seconds over which auto-rotation eases up to full speed once idle
+const AUTO_ROTATE_RAMP: f32 = 2.0;
+// Clip planes of the perspective projection
+pub const NEAR: f32 = 0.1;
+pub const FAR: f32 = 5000.0;
+
+// A turntable in progress: the pose it started from and how far through it is
+#[derive(Copy, Clone)]
+struct Turntable {
+    rotation_y: f32,
+    orientation: Quaternion<f32>,
+    revolutions: u32,
+    frames: u32,
+    frame: u32,
+}
 
 pub struct Camera {
+    // Point the eye orbits and looks at, moved by panning
     pub position: Vector3<f32>,
     pub scale: f32,
     pub aspect_ratio: f32,
     pub rotation_x: f32,
     pub rotation_y: f32,
+    mode: CameraMode,
+    // Camera-to-world rotation in arcball mode; the orbit angles are only
+    // used in orbit mode
+    orientation: Quaternion<f32>,
+    // State restored by `reset`
+    initial: CameraConfig,
+    // Turn around the vertical axis at `auto_rotate_speed` rad/s after
+    // `auto_rotate_idle` seconds without input
+    auto_rotate: bool,
+    auto_rotate_speed: f32,
+    auto_rotate_idle: f32,
+    // Reduced motion holds the auto-rotation without forgetting its setting
+    auto_rotate_suppressed: bool,
+    turntable: Option<Turntable>,
+    // Subpixel offset of the projection in clip space, for temporal
+    // anti-aliasing
+    jitter: [f32; 2],
 }
 
 impl Camera {
-    pub fn new() -> Self {
-        Self {
-            position: Vector3::new(0.0, 0.0, 800.0),
-            scale: 3.0,
+    pub fn new(config: CameraConfig) -> Self {
+        let mut camera = Self {
+            position: Vector3::new(0.0, 0.0, 0.0),
+            scale: 1.0,
             aspect_ratio: 1.0,
             rotation_x: 0.0,
-            rotation_y: std::f32::consts::FRAC_PI_2,
-        }
+            rotation_y: 0.0,
+            mode: config.mode,
+            orientation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            initial: config,
+            auto_rotate: false,
+            auto_rotate_speed: DEFAULT_AUTO_ROTATE_SPEED,
+            auto_rotate_idle: DEFAULT_AUTO_ROTATE_IDLE,
+            auto_rotate_suppressed: false,
+            turntable: None,
+            jitter: [0.0, 0.0],
+        };
+        camera.reset();
+        camera
     }
 
     pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
         self.aspect_ratio = aspect_ratio;
     }
 
+    pub fn set_jitter(&mut self, jitter: [f32; 2]) {
+        self.jitter = jitter;
+    }
+
+    pub fn set_auto_rotate(&mut self, enabled: bool, speed: f32, idle_seconds: f32) {
+        self.auto_rotate = enabled;
+        if speed.is_finite() {
+            self.auto_rotate_speed = speed;
+        }
+        if idle_seconds.is_finite() {
+            self.auto_rotate_idle = idle_seconds.max(0.0);
+        }
+    }
+
+    pub fn set_auto_rotate_suppressed(&mut self, suppressed: bool) {
+        self.auto_rotate_suppressed = suppressed;
+    }
+
+    // Turn exactly `revolutions` times around the vertical axis over the next
+    // `frames` calls to `advance_turntable`, at a constant angle per frame.
+    // The frame after the last would be the first again, so the frames loop
+    // seamlessly.
+    pub fn start_turntable(&mut self, revolutions: u32, frames: u32) {
+        self.turntable = Some(Turntable {
+            rotation_y: self.rotation_y,
+            orientation: self.orientation,
+            revolutions,
+            frames: frames.max(1),
+            frame: 0,
+        });
+    }
+
+    pub fn stop_turntable(&mut self) {
+        self.turntable = None;
+    }
+
+    pub fn turntable_active(&self) -> bool {
+        self.turntable.is_some()
+    }
+
+    // Pose the camera for the next turntable frame. Each angle comes from the
+    // frame index rather than accumulating, so there is no drift; once every
+    // frame has been shown the start pose is restored and this returns true.
+    pub fn advance_turntable(&mut self) -> bool {
+        let Some(turntable) = &mut self.turntable else {
+            return false;
+        };
+        let finished = turntable.frame >= turntable.frames;
+        let turns = turntable.revolutions as u64 * turntable.frame as u64 % turntable.frames as u64;
+        let angle = (std::f64::consts::TAU * turns as f64 / turntable.frames as f64) as f32;
+        turntable.frame += 1;
+
+        let turntable = *turntable;
+        match self.mode {
+            CameraMode::Orbit => self.rotation_y = turntable.rotation_y + angle,
+            CameraMode::Arcball => {
+                self.orientation =
+                    (Quaternion::from_angle_y(Rad(angle)) * turntable.orientation).normalize();
+            }
+        }
+        if finished {
+            self.turntable = None;
+        }
+        finished
+    }
+
+    // Advance time-driven motion by `dt` seconds, `idle` seconds after the last
+    // input. Input resets `idle`, which stops the rotation on the same frame.
+    pub fn update(&mut self, dt: f32, idle: f32) {
+        // A turntable sets the angle itself
+        if !self.auto_rotate
+            || self.auto_rotate_suppressed
+            || self.turntable.is_some()
+            || idle < self.auto_rotate_idle
+        {
+            return;
+        }
+        // Ease in so the view doesn't lurch into motion
+        let ramp = ((idle - self.auto_rotate_idle) / AUTO_ROTATE_RAMP).min(1.0);
+        let angle = self.auto_rotate_speed * ramp * dt;
+        match self.mode {
+            CameraMode::Orbit => self.rotation_y += angle,
+            CameraMode::Arcball => {
+                self.orientation =
+                    (Quaternion::from_angle_y(Rad(angle)) * self.orientation).normalize();
+            }
+        }
+    }
+
+    // Slide the view target across the screen plane, so the scene follows the
+    // drag whichever way the camera faces
     pub fn pan(&mut self, delta_x: f32, delta_y: f32) {
         let pan_scale = 1.0 / self.scale;
-        self.position.x -= delta_x * pan_scale;
-        self.position.y += delta_y * pan_scale;
+        let (right, up) = self.view_basis();
+        self.position += (up * delta_y - right * delta_x) * pan_scale;
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    // Switch controllers, keeping the current view direction. Leaving arcball
+    // mode drops any roll, and the elevation is clamped as usual.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == self.mode {
+            return;
+        }
+        match mode {
+            CameraMode::Arcball => self.orientation = self.orbit_orientation(),
+            CameraMode::Orbit => {
+                let eye = (self.orientation * Vector3::unit_z()).normalize();
+                self.rotation_x = (-eye.y).asin().clamp(-1.5, 1.5);
+                self.rotation_y = eye.x.atan2(eye.z);
+            }
+        }
+        self.mode = mode;
+    }
+
+    // Arcball orientation as (x, y, z, w), for saving and restoring the view
+    pub fn orientation(&self) -> [f32; 4] {
+        let q = self.orientation;
+        [q.v.x, q.v.y, q.v.z, q.s]
+    }
+
+    // Ignored unless the quaternion can be normalized
+    pub fn set_orientation(&mut self, [x, y, z, w]: [f32; 4]) {
+        let q = Quaternion::new(w, x, y, z);
+        let length = q.magnitude();
+        if length.is_finite() && length > 0.0 {
+            self.orientation = q / length;
+        }
+    }
+
+    // Rotate for a drag from `from` to `to`, in pixels from the top-left corner
+    // of a viewport of `size` pixels
+    pub fn drag_rotate(&mut self, from: (f32, f32), to: (f32, f32), size: (f32, f32)) {
+        match self.mode {
+            CameraMode::Orbit => self.rotate((to.0 - from.0) * 0.01, (to.1 - from.1) * 0.01),
+            CameraMode::Arcball => {
+                let start = Self::arcball_point(from, size);
+                let end = Self::arcball_point(to, size);
+                // The scene turns with the drag, so the camera turns the other way
+                let drag = Quaternion::from_arc(start, end, None);
+                self.orientation = (self.orientation * drag.conjugate()).normalize();
+            }
+        }
+    }
+
+    // Shoemake's mapping of a viewport position onto the unit sphere in view
+    // space; positions outside the sphere land on its silhouette
+    fn arcball_point(position: (f32, f32), size: (f32, f32)) -> Vector3<f32> {
+        let radius = 0.5 * size.0.min(size.1).max(1.0);
+        let x = (position.0 - 0.5 * size.0) / radius;
+        let y = (0.5 * size.1 - position.1) / radius;
+        let d2 = x * x + y * y;
+        if d2 <= 1.0 {
+            Vector3::new(x, y, (1.0 - d2).sqrt())
+        } else {
+            Vector3::new(x, y, 0.0) / d2.sqrt()
+        }
+    }
+
+    fn orbit_orientation(&self) -> Quaternion<f32> {
+        Quaternion::from_angle_y(Rad(self.rotation_y))
+            * Quaternion::from_angle_x(Rad(self.rotation_x))
     }
 
     pub fn rotate(&mut self, delta_x: f32, delta_y: f32) {
@@ -35,32 +250,70 @@ impl Camera {
         self.rotation_x = self.rotation_x.clamp(-1.5, 1.5);
     }
 
+    // Geometric zoom: equal deltas change the scale by equal ratios, so zooming
+    // feels the same everywhere in the range. Matches the old linear step for
+    // small deltas, and zooming in then out by the same delta returns exactly.
     pub fn zoom(&mut self, delta: f32) {
-        let zoom_factor = 1.0 + delta * 0.01;
-        self.scale *= zoom_factor;
+        self.scale *= (delta * ZOOM_RATE).exp();
         self.scale = self.scale.clamp(0.3, 5.0);
     }
 
     pub fn reset(&mut self) {
-        self.position = Vector3::new(0.0, 0.0, 800.0);
-        self.scale = 3.0;
-        self.rotation_x = 0.0;
-        self.rotation_y = std::f32::consts::FRAC_PI_2;
+        let initial = self.initial;
+        self.position = initial.position.into();
+        self.scale = initial.scale.clamp(0.3, 5.0);
+        self.rotation_x = initial.rotation_x.clamp(-1.5, 1.5);
+        self.rotation_y = initial.rotation_y;
+        self.orientation = self.orbit_orientation();
     }
 
-    pub fn build_view_projection_matrix(&self) -> Matrix4<f32> {
+    // World-space position of the eye, orbiting `position`
+    pub fn eye_position(&self) -> Vector3<f32> {
+        self.position + self.eye_offset()
+    }
+
+    // Eye relative to the point it orbits
+    fn eye_offset(&self) -> Vector3<f32> {
         let distance = 800.0 / self.scale;
 
+        if self.mode == CameraMode::Arcball {
+            return self.orientation * Vector3::new(0.0, 0.0, distance);
+        }
+
         let rot_x = cgmath::Matrix3::from_angle_x(cgmath::Rad(self.rotation_x));
         let rot_y = cgmath::Matrix3::from_angle_y(cgmath::Rad(self.rotation_y));
         let rotation = rot_y * rot_x;
 
-        let rotated_position = rotation * Vector3::new(0.0, 0.0, distance);
-        let camera_pos = Point3::from_vec(rotated_position);
+        rotation * Vector3::new(0.0, 0.0, distance)
+    }
+
+    // World-space right and up vectors of the view, for camera-facing billboards
+    pub fn view_basis(&self) -> (Vector3<f32>, Vector3<f32>) {
+        if self.mode == CameraMode::Arcball {
+            return (
+                self.orientation * Vector3::unit_x(),
+                self.orientation * Vector3::unit_y(),
+            );
+        }
+
+        let forward = (-self.eye_offset()).normalize();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        (right, up)
+    }
+
+    pub fn build_view_projection_matrix(&self) -> Matrix4<f32> {
+        let camera_pos = Point3::from_vec(self.eye_position());
+        // Arcball views can be upside down or rolled, so up follows the camera
+        let up = match self.mode {
+            CameraMode::Orbit => Vector3::unit_y(),
+            CameraMode::Arcball => self.orientation * Vector3::unit_y(),
+        };
 
-        let view = Matrix4::look_at_rh(camera_pos, Point3::new(0.0, 0.0, 0.0), Vector3::unit_y());
-        let proj = perspective(Deg(45.0), self.aspect_ratio, 0.1, 5000.0);
+        let view = Matrix4::look_at_rh(camera_pos, Point3::from_vec(self.position), up);
+        let proj = perspective(Deg(45.0), self.aspect_ratio, NEAR, FAR);
+        let jitter = Matrix4::from_translation(Vector3::new(self.jitter[0], self.jitter[1], 0.0));
 
-        proj * view
+        jitter * proj * view
     }
 }
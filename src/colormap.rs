@@ -0,0 +1,212 @@
+use wasm_bindgen::JsValue;
+
+// Entries in each built-in palette
+const BUILTIN_SIZE: u32 = 256;
+// Longer palettes are rejected rather than uploaded
+pub const MAX_COLORMAP_SIZE: u32 = 4096;
+
+// Palettes available by name, as evenly spaced sRGB stops from low to high
+pub const COLORMAPS: &[(&str, &[u32])] = &[
+    (
+        "viridis",
+        &[
+            0x440154, 0x472d7b, 0x3b528b, 0x2c728e, 0x21918c, 0x28ae80, 0x5ec962, 0xaddc30,
+            0xfde725,
+        ],
+    ),
+    (
+        "inferno",
+        &[
+            0x000004, 0x1f0c48, 0x550f6d, 0x88226a, 0xba3655, 0xe35933, 0xf98e09, 0xf9cb35,
+            0xfcffa4,
+        ],
+    ),
+    (
+        "magma",
+        &[
+            0x000004, 0x1c1044, 0x4f127b, 0x812581, 0xb5367a, 0xe55064, 0xfb8761, 0xfec287,
+            0xfcfdbf,
+        ],
+    ),
+];
+
+// Palette the scalar color modes look their colors up in, one row of RGBA8
+// entries bound as its own group like the particle sprite. Only consulted
+// while enabled through `RenderParams::colormap_enabled`.
+pub struct Colormap {
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+}
+
+impl Colormap {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Colormap Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Colormap Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let (_, stops) = COLORMAPS[0];
+        let bind_group = Self::create_bind_group(
+            device,
+            queue,
+            &bind_group_layout,
+            &sampler,
+            &gradient(stops),
+        );
+
+        Self {
+            bind_group_layout,
+            bind_group,
+            sampler,
+        }
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    // Replace the palette with RGBA8 entries from low to high; alpha is ignored
+    pub fn set_entries(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &[u8],
+    ) -> Result<(), JsValue> {
+        let entries = rgba.len() / 4;
+        if !rgba.len().is_multiple_of(4) || entries < 2 || entries > MAX_COLORMAP_SIZE as usize {
+            return Err(JsValue::from_str(&format!(
+                "Colormap must be 2 to {MAX_COLORMAP_SIZE} RGBA entries, got {} bytes",
+                rgba.len()
+            )));
+        }
+
+        self.bind_group =
+            Self::create_bind_group(device, queue, &self.bind_group_layout, &self.sampler, rgba);
+        Ok(())
+    }
+
+    // Switch to the palette called `name` in `COLORMAPS`
+    pub fn set_named(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: &str,
+    ) -> Result<(), JsValue> {
+        let (_, stops) = COLORMAPS
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = COLORMAPS.iter().map(|(name, _)| *name).collect();
+                JsValue::from_str(&format!(
+                    "Unknown colormap '{name}', expected one of: {}",
+                    names.join(", ")
+                ))
+            })?;
+        self.set_entries(device, queue, &gradient(stops))
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        rgba: &[u8],
+    ) -> wgpu::BindGroup {
+        // A single-row 2D texture stands in for a 1D one, which cannot be
+        // sampled from the vertex stage
+        let size = wgpu::Extent3d {
+            width: (rgba.len() / 4) as u32,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Colormap Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width * 4),
+                rows_per_image: Some(1),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Colormap Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+}
+
+// BUILTIN_SIZE entries interpolated linearly between evenly spaced stops
+fn gradient(stops: &[u32]) -> Vec<u8> {
+    let channel = |color: u32, shift: u32| ((color >> shift) & 0xff) as f32;
+    let mut texels = Vec::with_capacity(BUILTIN_SIZE as usize * 4);
+    for i in 0..BUILTIN_SIZE {
+        let position = i as f32 / (BUILTIN_SIZE - 1) as f32 * (stops.len() - 1) as f32;
+        let low = (position as usize).min(stops.len() - 2);
+        let t = position - low as f32;
+        for shift in [16, 8, 0] {
+            let (a, b) = (channel(stops[low], shift), channel(stops[low + 1], shift));
+            texels.push((a + (b - a) * t).round() as u8);
+        }
+        texels.push(255);
+    }
+    texels
+}
@@ -0,0 +1,278 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+// Gamma accepted by `Composite::set`; values outside are clamped
+pub const MIN_GAMMA: f32 = 0.2;
+pub const MAX_GAMMA: f32 = 5.0;
+// A floor this high already washes the scene out
+pub const MAX_AMBIENT_FLOOR: f32 = 0.5;
+// Dither amplitude beyond which the noise itself becomes visible, in 8-bit steps
+pub const MAX_DITHER_STRENGTH: f32 = 4.0;
+
+// Matches `Composite` in composite.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct CompositeUniform {
+    ambient_floor: f32,
+    gamma: f32,
+    dither_strength: f32,
+    _padding: u32,
+}
+
+// Gamma and ambient floor for viewing in a dark room, and dithering against
+// banding in the 8-bit output. While any is set the frame is drawn into an
+// offscreen texture, which this pass then copies into the real target with the
+// adjustment applied.
+pub struct Composite {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    frame_view: wgpu::TextureView,
+    uniform_buffer: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    uniform: CompositeUniform,
+}
+
+impl Composite {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+        ambient_floor: f32,
+        gamma: f32,
+        dither_strength: f32,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Composite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/composite.wgsl").into()),
+        });
+
+        let uniform = CompositeUniform {
+            ambient_floor: sanitize_ambient_floor(ambient_floor),
+            gamma: sanitize_gamma(gamma),
+            dither_strength: sanitize_dither_strength(dither_strength),
+            _padding: 0,
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Composite Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Composite Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Composite Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Composite Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_fullscreen"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_composite"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let frame_view = Self::create_frame_view(device, format, size);
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, &frame_view, &uniform_buffer);
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            frame_view,
+            uniform_buffer,
+            format,
+            uniform,
+        }
+    }
+
+    fn create_frame_view(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+    ) -> wgpu::TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Composite Frame Texture"),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        frame_view: &wgpu::TextureView,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Composite Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(frame_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    // A zero floor, a gamma of 1 and no dither would leave the frame
+    // unchanged, so the pass is skipped
+    pub fn enabled(&self) -> bool {
+        self.uniform.ambient_floor > 0.0
+            || self.uniform.gamma != 1.0
+            || self.uniform.dither_strength > 0.0
+    }
+
+    // (ambient floor, gamma, dither strength) as applied
+    pub fn settings(&self) -> (f32, f32, f32) {
+        let uniform = self.uniform;
+        (
+            uniform.ambient_floor,
+            uniform.gamma,
+            uniform.dither_strength,
+        )
+    }
+
+    // Texture the frame should be drawn into while enabled
+    pub fn frame_view(&self) -> &wgpu::TextureView {
+        &self.frame_view
+    }
+
+    pub fn set(&mut self, queue: &wgpu::Queue, ambient_floor: f32, gamma: f32) {
+        self.uniform.ambient_floor = sanitize_ambient_floor(ambient_floor);
+        self.uniform.gamma = sanitize_gamma(gamma);
+        self.write_uniform(queue);
+    }
+
+    // Peak-to-peak noise added to the output, in 8-bit steps (0 disables it)
+    pub fn set_dither_strength(&mut self, queue: &wgpu::Queue, strength: f32) {
+        self.uniform.dither_strength = sanitize_dither_strength(strength);
+        self.write_uniform(queue);
+    }
+
+    fn write_uniform(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        self.frame_view = Self::create_frame_view(device, self.format, size);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.frame_view,
+            &self.uniform_buffer,
+        );
+    }
+
+    // Copy the frame texture into `target` with the adjustment applied
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut composite_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        composite_pass.set_pipeline(&self.pipeline);
+        composite_pass.set_bind_group(0, &self.bind_group, &[]);
+        composite_pass.draw(0..3, 0..1);
+    }
+}
+
+fn sanitize_ambient_floor(floor: f32) -> f32 {
+    if floor.is_nan() {
+        0.0
+    } else {
+        floor.clamp(0.0, MAX_AMBIENT_FLOOR)
+    }
+}
+
+fn sanitize_dither_strength(strength: f32) -> f32 {
+    if strength.is_nan() {
+        0.0
+    } else {
+        strength.clamp(0.0, MAX_DITHER_STRENGTH)
+    }
+}
+
+fn sanitize_gamma(gamma: f32) -> f32 {
+    if gamma.is_nan() {
+        1.0
+    } else {
+        gamma.clamp(MIN_GAMMA, MAX_GAMMA)
+    }
+}
@@ -0,0 +1,514 @@
+// Start-up configuration for the simulation
+
+use crate::physics;
+use crate::simulation::{GROUP_CLOSE_STARS, GROUP_STREAM, NUM_GROUPS};
+use crate::spawn::{SpawnComponent, SpawnRegion};
+use wasm_bindgen::prelude::*;
+
+#[derive(Clone, Debug)]
+pub struct SimulationConfig {
+    // Allocate the particle buffer empty and upload the particles in chunks
+    // over several frames instead of one blocking upload at start-up
+    pub streaming_init: bool,
+    // Lower the particle count and turn off post effects at start-up on
+    // integrated and software GPUs; see `auto_tune`
+    pub auto_tune: bool,
+    pub distribution: DistributionConfig,
+    pub integrator: Integrator,
+    // Gravitational parameter of the central mass (G * M); initial orbital
+    // velocities are computed from it
+    pub gm: f32,
+    // Advance the physics in steps of exactly this many seconds, drawing positions
+    // interpolated between the last two steps; `None` steps once per frame
+    pub fixed_timestep: Option<f32>,
+    // Physics steps per frame multiplier, for fast-forwarding (1 is real time)
+    pub substeps_per_frame: u32,
+    pub color_mode: ColorMode,
+    // Second coloring mixed into `color_mode`, by `color_blend` (0 shows only
+    // `color_mode`, 1 only `color_mode_b`)
+    pub color_mode_b: ColorMode,
+    pub color_blend: f32,
+    pub render_mode: RenderMode,
+    // Soft glow over dense regions of the particle view, estimated from the
+    // screen-space density; 0 disables it and skips the extra passes
+    pub density_glow_strength: f32,
+    // Depth of field: blur in pixels of particles far from the focus (0
+    // disables it), and the focus distance from the eye (`None` keeps the
+    // black hole in focus)
+    pub dof_aperture: f32,
+    pub dof_focus_distance: Option<f32>,
+    // Darkening towards the screen edges in 0..1 (0 disables it), starting at
+    // `vignette_radius` half-heights from the center
+    pub vignette_strength: f32,
+    pub vignette_radius: f32,
+    // Viewing adjustment of the final frame: black is lifted to
+    // `ambient_floor` without moving white, after a display `gamma` (0 and 1
+    // leave the frame as drawn)
+    pub ambient_floor: f32,
+    pub gamma: f32,
+    // Peak-to-peak dither added to the final output, in 8-bit steps, to break
+    // up banding in dark gradients (0 disables it)
+    pub dither_strength: f32,
+    // Temporal anti-aliasing: jitter the projection and accumulate frames
+    // while the camera is still
+    pub taa: bool,
+    // Draw a crosshair at each attractor, sized by its mass
+    pub attractor_markers: bool,
+    // Opacity of each particle in 0..1; lower values let dense regions show through
+    pub particle_alpha: f32,
+    // Side of each particle's sprite billboard, in pixels
+    pub sprite_size: f32,
+    // Shrink sprites with distance (`sprite_size` at the default view
+    // distance) instead of keeping one screen size
+    pub size_attenuation: bool,
+    // With size attenuation, particles projecting smaller than this many pixels
+    // fade out and are skipped, saving overdraw when zoomed far out; 0 draws
+    // every particle
+    pub min_screen_size: f32,
+    // Color rings per doubling of radius in radial band color mode
+    pub radial_bands: f32,
+    // Extra emission for particles within `inner_glow_radius` of the center,
+    // fading out as (1 - r / radius)^falloff; a strength of 0 disables it
+    pub inner_glow_radius: f32,
+    pub inner_glow_strength: f32,
+    pub inner_glow_falloff: f32,
+    pub inner_glow_color: [f32; 3],
+    // Base color of each particle group (close stars, inflow stream) in group color mode
+    pub group_tints: [[f32; 3]; NUM_GROUPS],
+    // Draw layer of each group; higher layers are drawn over lower ones, so
+    // with alpha blending they read on top (equal layers are depth sorted)
+    pub group_layers: [u32; NUM_GROUPS],
+    pub horizon: HorizonConfig,
+    // Framing the view opens on, and returns to on reset
+    pub camera: CameraConfig,
+    // Drag sensitivity curve for rotating and panning: 1 is linear, higher
+    // exponents damp slow drags and amplify fast ones
+    pub rotate_curve_exponent: f32,
+    // Particle speeds are clamped to this after every velocity update (0 or
+    // infinity disables it). Non-physical, but keeps slingshots near the center
+    // from flinging particles off screen.
+    pub max_speed: f32,
+    // Particles closer to the center than this are absorbed; 0 keeps every particle
+    pub absorb_radius: f32,
+    // Softening length of the central mass's gravity: r^2 becomes
+    // r^2 + softening^2, capping the pull on close passes (0 for none)
+    pub softening: f32,
+    // Absorbed particles start again on a circular orbit this far out, in the
+    // XZ plane, instead of being removed; 0 removes them
+    pub respawn_radius: f32,
+    // Flash briefly where absorbed particles cross the horizon
+    pub accretion_flash_enabled: bool,
+    // Particles injected per simulated second, starting from none; 0 has every
+    // particle present from the start
+    pub inflow_rate: f32,
+    // Non-physical disk warp: orbits precess around an axis tilted
+    // `warp_amplitude` radians from vertical, at `precession_rate` radians per
+    // second near the center (0 keeps the disk flat)
+    pub warp_amplitude: f32,
+    pub precession_rate: f32,
+    // Particle temperature gained per second per unit of acceleration, and the
+    // fraction lost per second; drives the temperature color mode
+    pub heating_rate: f32,
+    pub cooling_rate: f32,
+    // Endless flow: particles leaving a box of half-size `wrap_bounds` come
+    // back through the opposite face, instead of bouncing off the boundary
+    pub wrap_enabled: bool,
+    pub wrap_bounds: [f32; 3],
+    // The steady-state callback fires once the total energy has varied by less
+    // than `steady_state_energy_tolerance` of itself, and the centroid by less
+    // than `steady_state_drift_tolerance` world units, for
+    // `steady_state_window` simulated seconds
+    pub steady_state_energy_tolerance: f32,
+    pub steady_state_drift_tolerance: f32,
+    pub steady_state_window: f32,
+    // Which parameter follows `set_reactive_value`, and how
+    pub reactive: ReactiveMapping,
+    // Clear color behind the particles
+    pub background: [f32; 3],
+    // Clear to transparent and composite the canvas over the page beneath it,
+    // where the browser supports premultiplied alpha; `background` is unused
+    pub transparent_background: bool,
+    // Distance fog for depth cueing; a density of 0 disables it
+    pub fog_density: f32,
+    // Color distant particles fade towards; `None` follows the background
+    pub fog_color: Option<[f32; 3]>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            streaming_init: false,
+            auto_tune: true,
+            distribution: DistributionConfig::default(),
+            integrator: Integrator::SemiImplicitEuler,
+            gm: 40000.0,
+            fixed_timestep: None,
+            substeps_per_frame: 1,
+            color_mode: ColorMode::Speed,
+            color_mode_b: ColorMode::Group,
+            color_blend: 0.0,
+            render_mode: RenderMode::Particles,
+            density_glow_strength: 0.0,
+            dof_aperture: 0.0,
+            dof_focus_distance: None,
+            vignette_strength: 0.0,
+            vignette_radius: 0.75,
+            ambient_floor: 0.0,
+            gamma: 1.0,
+            dither_strength: 1.0,
+            taa: false,
+            attractor_markers: false,
+            particle_alpha: 0.9,
+            sprite_size: 3.0,
+            size_attenuation: false,
+            min_screen_size: 0.0,
+            radial_bands: 4.0,
+            inner_glow_radius: 150.0,
+            inner_glow_strength: 0.0,
+            inner_glow_falloff: 2.0,
+            inner_glow_color: [1.0, 0.55, 0.2],
+            group_tints: [[1.0, 0.75, 0.35], [0.25, 0.6, 1.0]],
+            group_layers: [0; NUM_GROUPS],
+            horizon: HorizonConfig::default(),
+            camera: CameraConfig::default(),
+            rotate_curve_exponent: 1.0,
+            max_speed: 140.0,
+            absorb_radius: 0.0,
+            softening: 0.0,
+            respawn_radius: 0.0,
+            accretion_flash_enabled: false,
+            inflow_rate: 0.0,
+            warp_amplitude: 0.3,
+            precession_rate: 0.0,
+            heating_rate: 50.0,
+            cooling_rate: 0.5,
+            wrap_enabled: false,
+            wrap_bounds: [physics::BOUNDARY; 3],
+            steady_state_energy_tolerance: 0.005,
+            steady_state_drift_tolerance: 2.0,
+            steady_state_window: 10.0,
+            reactive: ReactiveMapping::default(),
+            background: [0.01, 0.01, 0.05],
+            transparent_background: false,
+            fog_density: 0.0,
+            fog_color: None,
+        }
+    }
+}
+
+// Time integration scheme; the values match `integrator` in update.wgsl
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integrator {
+    // Velocity first, then position with the new velocity (symplectic)
+    SemiImplicitEuler = 0,
+    // Position with the old velocity, then velocity; drifts outward over time
+    ExplicitEuler = 1,
+    // Kick-drift-kick leapfrog, second order and symplectic
+    Leapfrog = 2,
+}
+
+// How particles are colored; the values match `color_mode` in render.wgsl
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    // Blue (slow) to red (fast)
+    Speed = 0,
+    // Tinted by particle group, so the inflow stream stands out from the disk
+    Group = 1,
+    // Magnitude of the gravitational acceleration, on a log scale, showing
+    // the steep force gradient near the black hole
+    Acceleration = 2,
+    // Blackbody color of the particle temperature: matter glows as it
+    // spirals in and reddens as it cools on the way out
+    Temperature = 3,
+    // Rings evenly spaced in log radius (`radial_bands` per doubling), which
+    // make the disk's radial structure and differential rotation visible
+    RadialBands = 4,
+}
+
+// What the main view shows
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+    // Individual particles, colored by `ColorMode`
+    Particles = 0,
+    // Screen-space particle density through a colormap
+    Heatmap = 1,
+}
+
+// Shape of the initial particle distribution
+#[derive(Clone, Debug)]
+pub struct DistributionConfig {
+    // Vertical squash applied to the close stars: small values give a thin disk,
+    // values near 1.0 a puffy torus
+    pub disk_flatten: f32,
+    // Close stars get an elevation angle in -spread..spread (radians)
+    pub disk_height_spread: f32,
+    // Close-star speed as a multiple of the circular orbital speed: 1.0 gives a
+    // stable disk, less spirals inward and more spirals outward or escapes
+    pub orbital_fraction: f32,
+    // Each initial velocity component gets a random offset in -jitter..jitter,
+    // breaking up the otherwise perfectly laminar stream (0 disables it)
+    pub velocity_jitter: f32,
+    // Close-star surface density falls off as r^-exponent: 1 (the default)
+    // spreads them evenly in radius, higher values pack them toward the
+    // center and lower ones push them outward
+    pub radial_density_exponent: f32,
+    // Standard deviations of Gaussian offsets added to the initial velocities,
+    // as (radial, tangential, vertical) about the disk axis: a velocity
+    // ellipsoid that heats the cold orbits into a thicker disk (0 disables it)
+    pub velocity_dispersion: [f32; 3],
+    // Minimum distance between initial particles, placed by Poisson-disk
+    // sampling for an even texture without random clumps; 0 (the default)
+    // draws them independently. Applies to the close stars and to box,
+    // annulus, ring and cone spawn regions.
+    pub poisson_spacing: f32,
+    // Normal of the disk plane; the default +Y keeps the disk in the XZ plane
+    pub disk_orientation: [f32; 3],
+    // Dark-matter halo around the black hole, a logarithmic potential
+    // 0.5 * halo_speed^2 * ln(r^2 + halo_core_radius^2). Far outside the core
+    // its circular speed levels off at `halo_speed`, flattening the rotation
+    // curve; 0 (the default) leaves only the central mass. Initial orbital
+    // speeds include it.
+    pub halo_speed: f32,
+    pub halo_core_radius: f32,
+    // Custom scene built from spawn regions, by a preset or `set_spawn_regions`;
+    // replaces the default disk and stream when non-empty
+    pub regions: Vec<SpawnComponent>,
+}
+
+impl Default for DistributionConfig {
+    fn default() -> Self {
+        Self {
+            disk_flatten: 0.3,
+            disk_height_spread: 0.5,
+            orbital_fraction: 0.8,
+            velocity_jitter: 0.0,
+            radial_density_exponent: 1.0,
+            velocity_dispersion: [0.0; 3],
+            poisson_spacing: 0.0,
+            disk_orientation: [0.0, 1.0, 0.0],
+            halo_speed: 0.0,
+            halo_core_radius: 50.0,
+            regions: Vec::new(),
+        }
+    }
+}
+
+// Named presets selectable at runtime, as (name, description)
+pub const DISTRIBUTIONS: &[(&str, &str)] = &[
+    (
+        "black_hole_disk",
+        "Flattened disk of close stars with a stream falling in from afar",
+    ),
+    (
+        "colliding_rings",
+        "Two rotating rings on a collision course across the black hole",
+    ),
+    (
+        "spiral_galaxy",
+        "Two-armed spiral disk in circular orbit around the black hole",
+    ),
+];
+
+impl DistributionConfig {
+    // The preset called `name` in `DISTRIBUTIONS`
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "black_hole_disk" => Some(Self::default()),
+            "colliding_rings" => Some(Self::colliding_rings()),
+            "spiral_galaxy" => Some(Self::spiral_galaxy()),
+            _ => None,
+        }
+    }
+
+    // Two rotating rings on a collision course across the black hole
+    pub fn colliding_rings() -> Self {
+        let ring = |x: f32, vx: f32, group: u32| SpawnComponent {
+            region: SpawnRegion::Ring {
+                center: [x, 0.0, 0.0],
+                radius: 70.0,
+                tube_radius: 10.0,
+            },
+            weight: 1.0,
+            group,
+            velocity: [vx, 0.0, 0.0],
+            orbital_fraction: 0.3,
+        };
+
+        Self {
+            regions: vec![
+                ring(-250.0, 60.0, GROUP_CLOSE_STARS),
+                ring(250.0, -60.0, GROUP_STREAM),
+            ],
+            ..Self::default()
+        }
+    }
+
+    // Two logarithmic spiral arms around a compact bulge, everything on circular
+    // orbits. A halo keeps the rotation curve nearly flat out to the rim; with
+    // only the central mass the rotation would be Keplerian, and the arms would
+    // shear and wind up over a few inner orbits.
+    pub fn spiral_galaxy() -> Self {
+        let bulge = SpawnComponent {
+            region: SpawnRegion::Annulus {
+                center: [0.0; 3],
+                inner_radius: 20.0,
+                outer_radius: 45.0,
+                thickness: 12.0,
+            },
+            weight: 0.15,
+            group: GROUP_CLOSE_STARS,
+            velocity: [0.0; 3],
+            orbital_fraction: 1.0,
+        };
+        let disk = SpawnComponent {
+            region: SpawnRegion::SpiralGalaxy {
+                center: [0.0; 3],
+                arms: 2,
+                pitch_angle: 0.3,
+                arm_contrast: 0.85,
+                inner_radius: 40.0,
+                outer_radius: 320.0,
+                thickness: 6.0,
+            },
+            weight: 0.85,
+            group: GROUP_STREAM,
+            velocity: [0.0; 3],
+            orbital_fraction: 1.0,
+        };
+
+        Self {
+            halo_speed: 30.0,
+            halo_core_radius: 60.0,
+            regions: vec![bulge, disk],
+            ..Self::default()
+        }
+    }
+}
+
+// Parameter driven by the host's reactive value (e.g. audio amplitude)
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReactiveTarget {
+    // The value is ignored
+    None = 0,
+    // Gravitational parameter of the central mass
+    Gm = 1,
+    ParticleAlpha = 2,
+    InnerGlowStrength = 3,
+    DensityGlowStrength = 4,
+}
+
+// Routing of the reactive value: the target is set to `base + value * range`
+#[derive(Clone, Copy, Debug)]
+pub struct ReactiveMapping {
+    pub target: ReactiveTarget,
+    pub base: f32,
+    pub range: f32,
+}
+
+impl Default for ReactiveMapping {
+    fn default() -> Self {
+        Self {
+            target: ReactiveTarget::None,
+            base: 0.0,
+            range: 1.0,
+        }
+    }
+}
+
+// How dragging rotates the view
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    // Azimuth and elevation around the vertical axis, stopping short of the poles
+    Orbit = 0,
+    // Drag positions on a virtual sphere, rotating freely in any direction
+    Arcball = 1,
+}
+
+// What dragging with a mouse button does
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseAction {
+    None = 0,
+    Rotate = 1,
+    Pan = 2,
+}
+
+// Initial camera state; rotations are in radians
+#[derive(Clone, Copy, Debug)]
+pub struct CameraConfig {
+    pub mode: CameraMode,
+    // Point the view orbits and looks at; the black hole is at the origin
+    pub position: [f32; 3],
+    // Zoom factor, clamped to the camera's zoom range
+    pub scale: f32,
+    // Elevation, clamped to -1.5..1.5 (negative looks down on the disk from above)
+    pub rotation_x: f32,
+    // Azimuth around the vertical axis
+    pub rotation_y: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            mode: CameraMode::Orbit,
+            position: [0.0, 0.0, 0.0],
+            scale: 3.0,
+            rotation_x: 0.0,
+            rotation_y: std::f32::consts::FRAC_PI_2,
+        }
+    }
+}
+
+// Event horizon silhouette drawn at the origin
+#[derive(Clone, Debug)]
+pub struct HorizonConfig {
+    pub enabled: bool,
+    pub radius: f32,
+    // Width (world units) and brightness of the photon ring glow around the edge
+    pub ring_width: f32,
+    pub ring_intensity: f32,
+    pub ring_color: [f32; 3],
+    pub depth_bias: DepthBias,
+}
+
+impl Default for HorizonConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            radius: 8.0,
+            ring_width: 2.0,
+            ring_intensity: 1.5,
+            ring_color: [1.0, 0.65, 0.3],
+            depth_bias: DepthBias::default(),
+        }
+    }
+}
+
+// Depth bias for overlay pipelines drawn on top of the particles, to stop
+// coplanar draws from z-fighting. With the Depth32Float buffer and the `Less`
+// compare, negative values pull an overlay towards the camera and positive values
+// push it behind. `constant` is in units of the smallest depth step at the
+// primitive's depth; -1..-8 is usually enough, with `slope_scale` around -1.0 for
+// overlays seen at grazing angles. All zero (the default) disables the bias.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DepthBias {
+    pub constant: i32,
+    pub slope_scale: f32,
+    // Largest absolute bias applied; 0 means unclamped
+    pub clamp: f32,
+}
+
+impl DepthBias {
+    pub fn state(&self) -> wgpu::DepthBiasState {
+        wgpu::DepthBiasState {
+            constant: self.constant,
+            slope_scale: self.slope_scale,
+            clamp: self.clamp,
+        }
+    }
+}
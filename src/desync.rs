@@ -0,0 +1,170 @@
+// Development check that the particles the render pass draws are the ones the
+// physics writes. Every CHECK_INTERVAL frames a few particles are copied out of
+// each written buffer and of the buffer bound in its place for drawing, and
+// compared once read back; a stale render bind group shows up as a mismatch
+// instead of as frozen or out-of-date particles.
+
+use crate::simulation::Particle;
+use crate::utils::console_log;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Particles compared per check, spread evenly through the active ones
+const SAMPLES: u32 = 8;
+// Frames between checks
+const CHECK_INTERVAL: u32 = 120;
+// Buffer pairs compared per check
+pub const MAX_PAIRS: usize = 4;
+
+// A buffer the physics writes and the buffer a render bind group reads in its
+// place, named for the report
+pub struct BufferPair<'a> {
+    pub name: &'static str,
+    pub written: &'a wgpu::Buffer,
+    pub drawn: &'a wgpu::Buffer,
+}
+
+pub struct DesyncCheck {
+    enabled: bool,
+    // Per pair, SAMPLES particles from the written buffer followed by the
+    // same ones from the drawn buffer
+    staging_buffer: wgpu::Buffer,
+    frames: u32,
+    // Pair names and indices copied this frame, until the copy is mapped
+    copy_pending: Option<(Vec<&'static str>, Vec<u32>)>,
+    in_flight: Arc<AtomicBool>,
+    // Pairs and indices whose two copies differed in readbacks not yet reported
+    mismatches: Arc<Mutex<Vec<(&'static str, u32)>>>,
+}
+
+impl DesyncCheck {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Desync Staging Buffer"),
+            size: (MAX_PAIRS * 2) as u64 * SAMPLES as u64 * std::mem::size_of::<Particle>() as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            enabled: true,
+            staging_buffer,
+            frames: 0,
+            copy_pending: None,
+            in_flight: Arc::new(AtomicBool::new(false)),
+            mismatches: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    // Queue copies of the sampled particles from both buffers of each pair (at
+    // most MAX_PAIRS) on check frames, once the physics has run
+    pub fn encode(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        pairs: &[BufferPair],
+        particle_count: u32,
+    ) {
+        self.report();
+        self.frames = self.frames.wrapping_add(1);
+        if !self.enabled
+            || particle_count == 0
+            || !self.frames.is_multiple_of(CHECK_INTERVAL)
+            || self.in_flight.load(Ordering::Acquire)
+        {
+            return;
+        }
+
+        let size = std::mem::size_of::<Particle>() as u64;
+        let samples = SAMPLES.min(particle_count);
+        let indices: Vec<u32> = (0..samples)
+            .map(|i| (i as u64 * particle_count as u64 / samples as u64) as u32)
+            .collect();
+        let pairs = &pairs[..pairs.len().min(MAX_PAIRS)];
+        for (pair_slot, pair) in pairs.iter().enumerate() {
+            let base = (pair_slot * 2) as u64 * SAMPLES as u64 * size;
+            for (slot, &index) in indices.iter().enumerate() {
+                let offset = index as u64 * size;
+                let slot = base + slot as u64 * size;
+                encoder.copy_buffer_to_buffer(
+                    pair.written,
+                    offset,
+                    &self.staging_buffer,
+                    slot,
+                    size,
+                );
+                encoder.copy_buffer_to_buffer(
+                    pair.drawn,
+                    offset,
+                    &self.staging_buffer,
+                    SAMPLES as u64 * size + slot,
+                    size,
+                );
+            }
+        }
+        let names = pairs.iter().map(|pair| pair.name).collect();
+        self.copy_pending = Some((names, indices));
+    }
+
+    // Start mapping the staging buffer; must be called after the encoder passed
+    // to `encode` has been submitted
+    pub fn map_results(&mut self) {
+        let Some((names, indices)) = self.copy_pending.take() else {
+            return;
+        };
+        self.in_flight.store(true, Ordering::Release);
+
+        let staging = self.staging_buffer.clone();
+        let in_flight = self.in_flight.clone();
+        let mismatches = self.mismatches.clone();
+        self.staging_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    {
+                        let data = staging.slice(..).get_mapped_range();
+                        let particles: &[Particle] = bytemuck::cast_slice(&data);
+                        let pairs = particles.chunks(2 * SAMPLES as usize);
+                        if let Ok(mut mismatches) = mismatches.lock() {
+                            for (&name, pair) in names.iter().zip(pairs) {
+                                let (written, drawn) = pair.split_at(SAMPLES as usize);
+                                mismatches.extend(indices.iter().enumerate().filter_map(
+                                    |(slot, &index)| {
+                                        let differs = bytemuck::bytes_of(&written[slot])
+                                            != bytemuck::bytes_of(&drawn[slot]);
+                                        differs.then_some((name, index))
+                                    },
+                                ));
+                            }
+                        }
+                    }
+                    staging.unmap();
+                }
+                in_flight.store(false, Ordering::Release);
+            });
+    }
+
+    fn report(&mut self) {
+        let mismatches = match self.mismatches.lock() {
+            Ok(mut mismatches) => std::mem::take(&mut *mismatches),
+            Err(_) => return,
+        };
+        let mut names: Vec<&str> = mismatches.iter().map(|&(name, _)| name).collect();
+        names.dedup();
+        for name in names {
+            let indices: Vec<u32> = mismatches
+                .iter()
+                .filter(|&&(other, _)| other == name)
+                .map(|&(_, index)| index)
+                .collect();
+            console_log!(
+                "⚠️ Particle buffer desync in {}: the render pass is not drawing what the physics wrote (particles {:?})",
+                name,
+                indices
+            );
+        }
+    }
+}
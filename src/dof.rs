@@ -0,0 +1,272 @@
+use crate::camera::{Camera, FAR, NEAR};
+use bytemuck::{Pod, Zeroable};
+use cgmath::InnerSpace;
+use wgpu::util::DeviceExt;
+
+// Largest blur radius in pixels; the gather cost does not depend on it, but
+// wide radii spread the fixed taps thin
+pub const MAX_BLUR_RADIUS: f32 = 8.0;
+
+// Matches `DepthOfField` in dof.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct DofUniform {
+    focus_distance: f32,
+    aperture: f32,
+    near: f32,
+    far: f32,
+    max_radius: f32,
+    _padding: [u32; 3],
+}
+
+// Depth of field post-process. While enabled the main pass draws into an
+// offscreen scene texture, which is then blurred into the frame by depth.
+pub struct DepthOfField {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    scene_view: wgpu::TextureView,
+    uniform_buffer: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    aperture: f32,
+    // Fixed focus distance; `None` keeps the black hole in focus
+    focus_distance: Option<f32>,
+}
+
+impl DepthOfField {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+        depth_view: &wgpu::TextureView,
+        aperture: f32,
+        focus_distance: Option<f32>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Depth of Field Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/dof.wgsl").into()),
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Depth of Field Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[DofUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth of Field Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth of Field Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Depth of Field Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_fullscreen"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_dof"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let scene_view = Self::create_scene_view(device, format, size);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &scene_view,
+            depth_view,
+            &uniform_buffer,
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            scene_view,
+            uniform_buffer,
+            format,
+            aperture: aperture.max(0.0),
+            focus_distance: focus_distance.filter(|d| d.is_finite() && *d > 0.0),
+        }
+    }
+
+    fn create_scene_view(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+    ) -> wgpu::TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Depth of Field Scene Texture"),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        scene_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth of Field Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(scene_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.aperture > 0.0
+    }
+
+    pub fn focus_distance(&self) -> Option<f32> {
+        self.focus_distance
+    }
+
+    // Texture the main pass should draw into while enabled
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self.scene_view
+    }
+
+    // `aperture` is the blur in pixels of particles far from the focus (0
+    // disables the pass); `focus_distance` of `None` follows the black hole
+    pub fn set(&mut self, aperture: f32, focus_distance: Option<f32>) {
+        self.aperture = aperture.max(0.0);
+        self.focus_distance = focus_distance.filter(|d| d.is_finite() && *d > 0.0);
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+        if !self.enabled() {
+            return;
+        }
+
+        let uniform = DofUniform {
+            focus_distance: self
+                .focus_distance
+                .unwrap_or_else(|| camera.eye_position().magnitude()),
+            aperture: self.aperture,
+            near: NEAR,
+            far: FAR,
+            max_radius: MAX_BLUR_RADIUS,
+            _padding: [0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // The depth texture is recreated with the surface, so the bind group is
+    // rebuilt even when the size is unchanged
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        size: (u32, u32),
+        depth_view: &wgpu::TextureView,
+    ) {
+        self.scene_view = Self::create_scene_view(device, self.format, size);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.scene_view,
+            depth_view,
+            &self.uniform_buffer,
+        );
+    }
+
+    // Blur the scene texture into `target` by depth
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut dof_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth of Field Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        dof_pass.set_pipeline(&self.pipeline);
+        dof_pass.set_bind_group(0, &self.bind_group, &[]);
+        dof_pass.draw(0..3, 0..1);
+    }
+}
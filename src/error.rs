@@ -0,0 +1,211 @@
+// Typed GPU errors captured through wgpu error scopes, so a failure names the
+// resource that caused it instead of only showing up in the browser console
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use wasm_bindgen::JsValue;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuErrorKind {
+    Validation,
+    OutOfMemory,
+    Internal,
+    // Checked before creating a resource the device could not hold
+    LimitExceeded,
+}
+
+#[derive(Debug)]
+pub struct GpuError {
+    // Label of the resource being created when the error was raised
+    pub label: &'static str,
+    pub kind: GpuErrorKind,
+    pub message: String,
+}
+
+impl GpuErrorKind {
+    fn classify(error: wgpu::Error) -> (Self, String) {
+        match error {
+            wgpu::Error::Validation { description, .. } => (Self::Validation, description),
+            wgpu::Error::OutOfMemory { source } => (Self::OutOfMemory, source.to_string()),
+            wgpu::Error::Internal { description, .. } => (Self::Internal, description),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Validation => "Validation error",
+            Self::OutOfMemory => "Out of memory",
+            Self::Internal => "Internal GPU error",
+            Self::LimitExceeded => "Device limit exceeded",
+        }
+    }
+
+    // Short identifier handed to JS error callbacks
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Validation => "validation",
+            Self::OutOfMemory => "out-of-memory",
+            Self::Internal => "internal",
+            Self::LimitExceeded => "limit-exceeded",
+        }
+    }
+}
+
+impl GpuError {
+    fn new(label: &'static str, error: wgpu::Error) -> Self {
+        let (kind, message) = GpuErrorKind::classify(error);
+        Self {
+            label,
+            kind,
+            message,
+        }
+    }
+
+    pub fn limit_exceeded(label: &'static str, message: String) -> Self {
+        Self {
+            label,
+            kind: GpuErrorKind::LimitExceeded,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} while creating {}: {}",
+            self.kind.name(),
+            self.label,
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+impl From<GpuError> for JsValue {
+    fn from(error: GpuError) -> Self {
+        JsValue::from_str(&error.to_string())
+    }
+}
+
+type ScopeResult = Pin<Box<dyn Future<Output = Option<wgpu::Error>>>>;
+
+// Collects one error scope per labelled resource; `finish` waits for all of them
+// and reports the first failure
+#[derive(Default)]
+pub struct ErrorScopes {
+    pending: Vec<(&'static str, ScopeResult)>,
+}
+
+impl ErrorScopes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Run `create` inside error scopes attributed to `label`
+    pub fn capture<T>(
+        &mut self,
+        device: &wgpu::Device,
+        label: &'static str,
+        create: impl FnOnce() -> T,
+    ) -> T {
+        device.push_error_scope(wgpu::ErrorFilter::Internal);
+        device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let value = create();
+        let validation = device.pop_error_scope();
+        let out_of_memory = device.pop_error_scope();
+        let internal = device.pop_error_scope();
+
+        self.pending.push((
+            label,
+            Box::pin(async move {
+                match validation.await {
+                    Some(error) => Some(error),
+                    None => match out_of_memory.await {
+                        Some(error) => Some(error),
+                        None => internal.await,
+                    },
+                }
+            }),
+        ));
+        value
+    }
+
+    pub async fn finish(self) -> Result<(), GpuError> {
+        for (label, result) in self.pending {
+            if let Some(error) = result.await {
+                let error = GpuError::new(label, error);
+                crate::utils::console_log!("❌ {}", error);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+}
+
+// An error raised outside any error scope while the simulation is running;
+// `kind` is `None` when the device itself was lost
+#[derive(Debug)]
+pub struct RuntimeGpuError {
+    pub kind: Option<GpuErrorKind>,
+    pub message: String,
+}
+
+impl RuntimeGpuError {
+    pub fn kind_id(&self) -> &'static str {
+        self.kind.map_or("device-lost", GpuErrorKind::id)
+    }
+}
+
+// Catches GPU errors after initialization: uncaptured validation, memory and
+// internal errors, and loss of the device. The handlers can run at any time, so
+// errors are queued and collected once per frame with `take`.
+pub struct ErrorMonitor {
+    errors: Arc<Mutex<Vec<RuntimeGpuError>>>,
+}
+
+impl ErrorMonitor {
+    pub fn install(device: &wgpu::Device) -> Self {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+
+        let queue = errors.clone();
+        device.on_uncaptured_error(Box::new(move |error| {
+            let (kind, message) = GpuErrorKind::classify(error);
+            if let Ok(mut queue) = queue.lock() {
+                queue.push(RuntimeGpuError {
+                    kind: Some(kind),
+                    message,
+                });
+            }
+        }));
+
+        let queue = errors.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Destroying the device on purpose is not a failure
+            if reason == wgpu::DeviceLostReason::Destroyed {
+                return;
+            }
+            if let Ok(mut queue) = queue.lock() {
+                queue.push(RuntimeGpuError {
+                    kind: None,
+                    message,
+                });
+            }
+        });
+
+        Self { errors }
+    }
+
+    // Errors raised since the last call, oldest first
+    pub fn take(&self) -> Vec<RuntimeGpuError> {
+        self.errors
+            .lock()
+            .map(|mut errors| std::mem::take(&mut *errors))
+            .unwrap_or_default()
+    }
+}
@@ -1,5 +1,7 @@
+use crate::autotune::{self, GpuClass};
+use crate::error::ErrorScopes;
 use crate::utils::console_log;
-use wasm_bindgen::JsValue;
+use wasm_bindgen::{JsCast, JsValue};
 
 pub struct Graphics {
     pub surface: wgpu::Surface<'static>,
@@ -7,12 +9,159 @@ pub struct Graphics {
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
     pub size: (u32, u32),
+    // False while the canvas has no area; nothing can be drawn until a nonzero resize
+    surface_configured: bool,
     pub depth_texture: wgpu::Texture,
     pub depth_view: wgpu::TextureView,
+    // Best format for offscreen HDR targets on this adapter
+    hdr_format: wgpu::TextureFormat,
+    // Compositing modes the surface supports, the first being the default
+    alpha_modes: Vec<wgpu::CompositeAlphaMode>,
+    // Whether the adapter can run the update shader; without it the physics
+    // falls back to the CPU
+    pub compute_supported: bool,
+    // Rough kind of GPU, and what identified it (empty when nothing did)
+    pub gpu_class: GpuClass,
+    pub gpu_description: String,
+}
+
+// What a quick adapter request found, without a surface or device
+pub struct SupportReport {
+    pub supported: bool,
+    pub compute_supported: bool,
+    pub adapter_name: Option<String>,
+}
+
+// Probe for a WebGPU adapter. The adapter is released on return and no device
+// is ever created, so nothing is left holding GPU resources.
+pub async fn probe_support() -> SupportReport {
+    let unsupported = SupportReport {
+        supported: false,
+        compute_supported: false,
+        adapter_name: None,
+    };
+
+    // Requesting an adapter without `navigator.gpu` fails noisily, so check first
+    let global = js_sys::global();
+    let has_gpu = js_sys::Reflect::get(&global, &"navigator".into())
+        .and_then(|navigator| js_sys::Reflect::get(&navigator, &"gpu".into()))
+        .is_ok_and(|gpu| !gpu.is_undefined() && !gpu.is_null());
+    if !has_gpu {
+        return unsupported;
+    }
+
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::BROWSER_WEBGPU,
+        flags: wgpu::InstanceFlags::default(),
+        backend_options: wgpu::BackendOptions::default(),
+    });
+    let Some(adapter) = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        })
+        .await
+    else {
+        return unsupported;
+    };
+
+    let compute_supported = supports_compute(&adapter);
+    let name = adapter.get_info().name;
+
+    SupportReport {
+        supported: true,
+        compute_supported,
+        adapter_name: (!name.is_empty()).then_some(name),
+    }
+}
+
+// The browser's `GPUAdapterInfo` for a high-performance adapter, as its
+// non-empty fields joined by spaces and whether it is a fallback (software)
+// adapter. `None` where the browser doesn't expose adapter info.
+async fn browser_adapter_info() -> Option<(String, bool)> {
+    let get = |target: &JsValue, key: &str| js_sys::Reflect::get(target, &key.into()).ok();
+    let gpu = get(&js_sys::global(), "navigator").and_then(|navigator| get(&navigator, "gpu"))?;
+    let request: js_sys::Function = get(&gpu, "requestAdapter")?.dyn_into().ok()?;
+    let options = js_sys::Object::new();
+    js_sys::Reflect::set(
+        &options,
+        &"powerPreference".into(),
+        &"high-performance".into(),
+    )
+    .ok()?;
+    let promise: js_sys::Promise = request.call1(&gpu, &options).ok()?.dyn_into().ok()?;
+    let adapter = wasm_bindgen_futures::JsFuture::from(promise).await.ok()?;
+    if adapter.is_null() || adapter.is_undefined() {
+        return None;
+    }
+    let info = get(&adapter, "info").filter(|info| !info.is_undefined())?;
+
+    let description = ["vendor", "architecture", "device", "description"]
+        .iter()
+        .filter_map(|key| get(&info, key)?.as_string())
+        .filter(|value| !value.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    // Moved from the adapter onto its info in newer browsers
+    let fallback = [&info, &adapter]
+        .iter()
+        .any(|target| get(target, "isFallbackAdapter").is_some_and(|value| value.is_truthy()));
+    Some((description, fallback))
+}
+
+// The update shader runs 64 invocations per workgroup
+fn supports_compute(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        && adapter.limits().max_compute_invocations_per_workgroup >= 64
+}
+
+fn select_hdr_format(adapter: &wgpu::Adapter) -> wgpu::TextureFormat {
+    let features = adapter.get_texture_format_features(wgpu::TextureFormat::Rgba16Float);
+    let usable = features
+        .allowed_usages
+        .contains(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+        && features.flags.contains(
+            wgpu::TextureFormatFeatureFlags::FILTERABLE
+                | wgpu::TextureFormatFeatureFlags::BLENDABLE,
+        );
+    if usable {
+        wgpu::TextureFormat::Rgba16Float
+    } else {
+        wgpu::TextureFormat::Rgba8Unorm
+    }
+}
+
+fn select_alpha_mode(
+    modes: &[wgpu::CompositeAlphaMode],
+    transparent: bool,
+) -> wgpu::CompositeAlphaMode {
+    let premultiplied = wgpu::CompositeAlphaMode::PreMultiplied;
+    if transparent {
+        if modes.contains(&premultiplied) {
+            return premultiplied;
+        }
+        console_log!("⚠️ Transparent canvas not supported, falling back to opaque");
+    }
+    // Opaque when offered, so a transparent default never leaks through
+    let opaque = wgpu::CompositeAlphaMode::Opaque;
+    if modes.contains(&opaque) {
+        opaque
+    } else {
+        modes[0]
+    }
 }
 
 impl Graphics {
-    pub async fn new(canvas: web_sys::HtmlCanvasElement) -> Result<Self, JsValue> {
+    // `transparent` asks for a premultiplied-alpha canvas that composites over
+    // the page beneath it, where the surface supports one
+    pub async fn new(
+        canvas: web_sys::HtmlCanvasElement,
+        transparent: bool,
+    ) -> Result<Self, JsValue> {
         console_log!("Setting up WebGPU...");
 
         // Create WebGPU instance
@@ -45,9 +194,36 @@ impl Graphics {
                 force_fallback_adapter: false,
             })
             .await
-            .unwrap_or_else(|| panic!("Failed to find an appropriate adapter"));
+            .ok_or_else(|| {
+                JsValue::from_str(
+                    "No WebGPU adapter available for this canvas (is WebGPU enabled?)",
+                )
+            })?;
 
-        console_log!("Adapter: {:?}", adapter.get_info());
+        let info = adapter.get_info();
+        console_log!("Adapter: {:?}", info);
+        // wgpu reports nothing about WebGPU adapters, so ask the browser
+        let (gpu_description, fallback_adapter) =
+            if info.backend == wgpu::Backend::BrowserWebGpu && info.name.is_empty() {
+                browser_adapter_info().await.unwrap_or_default()
+            } else {
+                (info.name.clone(), false)
+            };
+        let gpu_class = autotune::classify(
+            info.device_type,
+            info.vendor,
+            &gpu_description,
+            fallback_adapter,
+        );
+        console_log!(
+            "🖥️ GPU class: {} ({})",
+            gpu_class.name(),
+            if gpu_description.is_empty() {
+                "no adapter details"
+            } else {
+                &gpu_description
+            }
+        );
 
         // Try using Default trait to get minimal device descriptor
         console_log!("Using Default::default() for DeviceDescriptor");
@@ -57,8 +233,15 @@ impl Graphics {
             .await
             .map_err(|e| JsValue::from_str(&format!("Failed to create device: {e:?}")))?;
 
-        // Configure the surface
-        let size = (1024, 768);
+        // Configure the surface at the canvas size. A canvas inside a hidden container
+        // can have no area yet, in which case configuration waits for a resize.
+        let canvas_size = (canvas.width(), canvas.height());
+        let surface_configured = canvas_size.0 > 0 && canvas_size.1 > 0;
+        if !surface_configured {
+            console_log!("📐 Canvas has no area yet, waiting for a resize before rendering");
+        }
+        // Textures cannot be empty, so stand-ins are created at 1x1 until then
+        let size = (canvas_size.0.max(1), canvas_size.1.max(1));
         let surface_caps = surface.get_capabilities(&adapter);
 
         let surface_format = surface_caps
@@ -68,36 +251,59 @@ impl Graphics {
             .copied()
             .unwrap_or(surface_caps.formats[0]);
 
+        let hdr_format = select_hdr_format(&adapter);
+        console_log!(
+            "🎨 Surface format: {:?}, HDR format: {:?}",
+            surface_format,
+            hdr_format
+        );
+
+        // Frames can only be recorded if the surface can be copied from
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if surface_caps.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            usage |= wgpu::TextureUsages::COPY_SRC;
+        }
+
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: surface_format,
             width: size.0,
             height: size.1,
             present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
+            alpha_mode: select_alpha_mode(&surface_caps.alpha_modes, transparent),
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
 
-        surface.configure(&device, &config);
+        let mut scopes = ErrorScopes::new();
+        if surface_configured {
+            scopes.capture(&device, "Surface Configuration", || {
+                surface.configure(&device, &config)
+            });
+        }
 
         // Create depth texture
-        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth Texture"),
-            size: wgpu::Extent3d {
-                width: size.0,
-                height: size.1,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
+        let depth_texture = scopes.capture(&device, "Depth Texture", || {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Depth Texture"),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Depth32Float,
+                // Sampled by the depth of field pass
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
         });
 
         let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        scopes.finish().await?;
 
         console_log!("WebGPU initialized successfully!");
 
@@ -107,38 +313,74 @@ impl Graphics {
             queue,
             config,
             size,
+            surface_configured,
             depth_texture,
             depth_view,
+            hdr_format,
+            alpha_modes: surface_caps.alpha_modes,
+            compute_supported: supports_compute(&adapter),
+            gpu_class,
+            gpu_description,
         })
     }
 
-    pub fn resize(&mut self, new_width: u32, new_height: u32) {
-        if new_width > 0 && new_height > 0 {
-            self.size.0 = new_width;
-            self.size.1 = new_height;
-            self.config.width = new_width;
-            self.config.height = new_height;
+    // Whether the canvas shows the page through wherever alpha is below 1
+    pub fn is_transparent(&self) -> bool {
+        self.config.alpha_mode == wgpu::CompositeAlphaMode::PreMultiplied
+    }
+
+    // Switch between an opaque and a transparent canvas; returns whether the
+    // canvas is transparent afterwards
+    pub fn set_transparent(&mut self, transparent: bool) -> bool {
+        self.config.alpha_mode = select_alpha_mode(&self.alpha_modes, transparent);
+        if self.surface_configured {
             self.surface.configure(&self.device, &self.config);
+        }
+        self.is_transparent()
+    }
 
-            // Recreate depth texture for new size
-            self.depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("Depth Texture"),
-                size: wgpu::Extent3d {
-                    width: new_width,
-                    height: new_height,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Depth32Float,
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                view_formats: &[],
-            });
+    pub fn is_configured(&self) -> bool {
+        self.surface_configured
+    }
+
+    // Rgba16Float when it can be rendered to, sampled, filtered and blended,
+    // otherwise Rgba8Unorm
+    pub fn preferred_hdr_format(&self) -> wgpu::TextureFormat {
+        self.hdr_format
+    }
 
-            self.depth_view = self
-                .depth_texture
-                .create_view(&wgpu::TextureViewDescriptor::default());
+    // Resizing to zero area stops rendering until the next nonzero size
+    pub fn resize(&mut self, new_width: u32, new_height: u32) {
+        if new_width == 0 || new_height == 0 {
+            self.surface_configured = false;
+            return;
         }
+
+        self.size.0 = new_width;
+        self.size.1 = new_height;
+        self.config.width = new_width;
+        self.config.height = new_height;
+        self.surface.configure(&self.device, &self.config);
+        self.surface_configured = true;
+
+        // Recreate depth texture for new size
+        self.depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Depth Texture"),
+            size: wgpu::Extent3d {
+                width: new_width,
+                height: new_height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        self.depth_view = self
+            .depth_texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
     }
 }
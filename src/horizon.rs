@@ -0,0 +1,169 @@
+use crate::camera::Camera;
+use crate::config::HorizonConfig;
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+// Billboard half-size as a multiple of the ring width, so the glow fades out fully
+const RING_EXTENT: f32 = 4.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct HorizonUniform {
+    view_proj: [[f32; 4]; 4],
+    camera_right: [f32; 4],
+    camera_up: [f32; 4],
+    ring_color: [f32; 4],
+    radius: f32,
+    ring_width: f32,
+    ring_intensity: f32,
+    extent: f32,
+}
+
+// The black hole's event horizon, drawn as an antialiased billboard over the particles
+pub struct Horizon {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    config: HorizonConfig,
+}
+
+impl Horizon {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        config: HorizonConfig,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Horizon Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/horizon.wgsl").into()),
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Horizon Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[HorizonUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Horizon Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Horizon Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Horizon Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Horizon Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            // Particles in front of the horizon hide it; it never writes depth itself,
+            // so the ring glow does not cut holes into particles drawn behind it
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: config.depth_bias.state(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            uniform_buffer,
+            config,
+        }
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.config.enabled = visible;
+    }
+
+    pub fn set_ring(&mut self, width: f32, intensity: f32) {
+        self.config.ring_width = width.max(0.0);
+        self.config.ring_intensity = intensity.max(0.0);
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+        if !self.config.enabled {
+            return;
+        }
+
+        let (right, up) = camera.view_basis();
+        let [r, g, b] = self.config.ring_color;
+        let uniform = HorizonUniform {
+            view_proj: camera.build_view_projection_matrix().into(),
+            camera_right: [right.x, right.y, right.z, 0.0],
+            camera_up: [up.x, up.y, up.z, 0.0],
+            ring_color: [r, g, b, 1.0],
+            radius: self.config.radius,
+            ring_width: self.config.ring_width,
+            ring_intensity: self.config.ring_intensity,
+            extent: self.config.radius + self.config.ring_width * RING_EXTENT,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // Draw after the particles so it can use their depth
+    pub fn render_pass<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if !self.config.enabled {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
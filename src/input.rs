@@ -1,19 +1,70 @@
+use crate::config::MouseAction;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::{HtmlCanvasElement, KeyboardEvent, MouseEvent, TouchEvent, WheelEvent};
+use web_sys::{EventTarget, HtmlCanvasElement, KeyboardEvent, PointerEvent, WheelEvent};
+
+// Exponents accepted for the drag response curve
+pub const MIN_CURVE_EXPONENT: f32 = 0.5;
+pub const MAX_CURVE_EXPONENT: f32 = 3.0;
+// Drag speed in pixels per second that the response curve leaves unchanged
+const CURVE_REFERENCE_SPEED: f32 = 500.0;
+// Shortest time a drag delta is taken to span, for events sharing a timestamp
+const MIN_DRAG_SECONDS: f32 = 0.001;
+const REDUCED_MOTION_QUERY: &str = "(prefers-reduced-motion: reduce)";
+// Largest drawing buffer side; the texture size every WebGPU device supports
+const MAX_CANVAS_SIZE: u32 = 8192;
+
+// One pointer that is down on the canvas
+#[derive(Copy, Clone)]
+pub struct PointerState {
+    // Client pixels
+    pub position: (f32, f32),
+    // Fingers rotate and pinch; mice and pens go through the button actions
+    pub touch: bool,
+}
 
 pub struct InputState {
     pub mouse_pos: (f32, f32),
     pub last_mouse_pos: (f32, f32),
+    // Event timestamps (ms) of `mouse_pos` and `last_mouse_pos`, turning drag
+    // deltas into speeds for the response curve
+    pub mouse_time: f64,
+    pub last_mouse_time: f64,
+    // Canvas bounds in client pixels (left, top, width, height) when the drag began
+    pub drag_bounds: (f32, f32, f32, f32),
+    // Cursor over the canvas in canvas pixels, `None` when it is outside
+    pub hover_pos: Option<(f32, f32)>,
     pub is_dragging: bool,
     pub is_rotating: bool,
     pub zoom_delta: f32,
+    // Drag action of the left, middle and right buttons
+    pub button_actions: [MouseAction; 3],
+    // Keep the browser's context menu off the canvas
+    pub suppress_context_menu: bool,
+    // Event timestamp (ms, same clock as requestAnimationFrame) of the oldest
+    // camera input not yet applied, for latency measurement
+    pub input_time: Option<f64>,
+    // Timestamp of the latest event of any kind from the user, for idle detection
+    pub last_input_time: f64,
     pub pause_pressed: bool,
     pub reset_pressed: bool,
-    // Touch state
-    pub touch_count: u32,
+    // Page Visibility: whether the tab is hidden, and whether it came back since
+    // the last frame
+    pub page_hidden: bool,
+    pub page_shown: bool,
+    // `prefers-reduced-motion` as last reported, until the next frame takes it
+    pub reduced_motion_changed: Option<bool>,
+    // Drawing buffer size the canvas was last resized to, until the next frame
+    // takes it
+    pub resized: Option<(u32, u32)>,
+    // Drag response: rotation and pan follow drag speed^exponent, with 1
+    // linear and higher values slower for fine moves and faster for sweeps
+    pub rotate_curve_exponent: f32,
+    // Pointers currently down, by pointerId
+    pub pointers: HashMap<i32, PointerState>,
     pub last_pinch_distance: f32,
 }
 
@@ -22,198 +73,341 @@ impl InputState {
         Self {
             mouse_pos: (0.0, 0.0),
             last_mouse_pos: (0.0, 0.0),
+            mouse_time: 0.0,
+            last_mouse_time: 0.0,
+            drag_bounds: (0.0, 0.0, 1.0, 1.0),
+            hover_pos: None,
             is_dragging: false,
             is_rotating: false,
             zoom_delta: 0.0,
+            button_actions: [MouseAction::Rotate, MouseAction::None, MouseAction::Pan],
+            suppress_context_menu: true,
+            input_time: None,
+            last_input_time: 0.0,
             pause_pressed: false,
             reset_pressed: false,
-            touch_count: 0,
+            page_hidden: false,
+            page_shown: false,
+            reduced_motion_changed: None,
+            resized: None,
+            rotate_curve_exponent: 1.0,
+            pointers: HashMap::new(),
             last_pinch_distance: 0.0,
         }
     }
+
+    // Positions of the fingers down, oldest pointer first
+    fn touches(&self) -> Vec<(f32, f32)> {
+        let mut touches: Vec<_> = self
+            .pointers
+            .iter()
+            .filter(|(_, pointer)| pointer.touch)
+            .map(|(id, pointer)| (*id, pointer.position))
+            .collect();
+        touches.sort_by_key(|(id, _)| *id);
+        touches.into_iter().map(|(_, position)| position).collect()
+    }
+
+    // Restart the one-finger rotation or the pinch from the fingers now down,
+    // so adding or lifting a finger never makes the camera jump
+    fn rebase_touches(&mut self) {
+        let touches = self.touches();
+        self.is_rotating = touches.len() == 1;
+        self.last_pinch_distance = pinch_distance(&touches);
+        if let Some(&position) = touches.first() {
+            self.last_mouse_pos = position;
+            self.mouse_pos = position;
+        }
+        self.last_mouse_time = self.mouse_time;
+    }
+}
+
+// Drawing buffer size matching the canvas's laid-out size in device pixels;
+// zero on either side while it has no area
+pub fn display_size(canvas: &HtmlCanvasElement) -> (u32, u32) {
+    let ratio = web_sys::window().map_or(1.0, |window| window.device_pixel_ratio());
+    let side = |css: i32| ((css.max(0) as f64 * ratio).round() as u32).min(MAX_CANVAS_SIZE);
+    (side(canvas.client_width()), side(canvas.client_height()))
+}
+
+fn client_bounds(canvas: &HtmlCanvasElement) -> (f32, f32, f32, f32) {
+    let rect = canvas.get_bounding_client_rect();
+    (
+        rect.left() as f32,
+        rect.top() as f32,
+        rect.width() as f32,
+        rect.height() as f32,
+    )
+}
+
+// Whether the system currently asks for reduced motion
+pub fn prefers_reduced_motion() -> bool {
+    web_sys::window()
+        .and_then(|window| window.match_media(REDUCED_MOTION_QUERY).ok().flatten())
+        .is_some_and(|query| query.matches())
+}
+
+// Signed power response to a drag of `delta` pixels over `seconds`: the delta
+// is scaled by (speed / CURVE_REFERENCE_SPEED)^(exponent - 1), keeping its
+// direction. Going by speed rather than per-frame distance makes the same hand
+// motion turn the view equally far at any frame rate.
+fn curve_delta((x, y): (f32, f32), seconds: f32, exponent: f32) -> (f32, f32) {
+    let length = (x * x + y * y).sqrt();
+    if exponent == 1.0 || length == 0.0 {
+        return (x, y);
+    }
+    let speed = length / seconds.max(MIN_DRAG_SECONDS);
+    let scale = (speed / CURVE_REFERENCE_SPEED).powf(exponent - 1.0);
+    (x * scale, y * scale)
 }
 
-fn get_pinch_distance(event: &TouchEvent) -> f32 {
-    let touches = event.touches();
-    if touches.length() >= 2 {
-        let t1 = touches.get(0).unwrap();
-        let t2 = touches.get(1).unwrap();
-        let dx = t2.client_x() as f32 - t1.client_x() as f32;
-        let dy = t2.client_y() as f32 - t1.client_y() as f32;
-        (dx * dx + dy * dy).sqrt()
-    } else {
-        0.0
+// Distance between the first two fingers, 0 with fewer than two
+fn pinch_distance(touches: &[(f32, f32)]) -> f32 {
+    match touches {
+        [a, b, ..] => ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt(),
+        _ => 0.0,
     }
 }
 
+fn client_position(event: &PointerEvent) -> (f32, f32) {
+    (event.client_x() as f32, event.client_y() as f32)
+}
+
+type Listener = Closure<dyn FnMut(web_sys::Event)>;
+type ResizeCallback = Closure<dyn FnMut(js_sys::Array)>;
+
 pub struct InputHandler {
     state: Rc<RefCell<InputState>>,
-    _closures: Vec<Closure<dyn FnMut(web_sys::Event)>>,
+    // Registered listeners, kept so they stay alive and can be detached again
+    listeners: Vec<(EventTarget, &'static str, Listener)>,
+    // Follows CSS size changes of the canvas
+    resize_observer: Option<(web_sys::ResizeObserver, ResizeCallback)>,
 }
 
 impl InputHandler {
     pub fn new() -> Result<Self, JsValue> {
         Ok(Self {
             state: Rc::new(RefCell::new(InputState::new())),
-            _closures: Vec::new(),
+            listeners: Vec::new(),
+            resize_observer: None,
         })
     }
 
+    fn listen(
+        &mut self,
+        target: &EventTarget,
+        event: &'static str,
+        closure: Listener,
+    ) -> Result<(), JsValue> {
+        target.add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())?;
+        self.listeners.push((target.clone(), event, closure));
+        Ok(())
+    }
+
+    // Detach every listener registered by `setup_event_listeners`
+    pub fn remove_event_listeners(&mut self) {
+        for (target, event, closure) in self.listeners.drain(..) {
+            let _ =
+                target.remove_event_listener_with_callback(event, closure.as_ref().unchecked_ref());
+        }
+        if let Some((observer, _)) = self.resize_observer.take() {
+            observer.disconnect();
+        }
+    }
+
     pub fn setup_event_listeners(&mut self, canvas: HtmlCanvasElement) -> Result<(), JsValue> {
-        let window = web_sys::window().unwrap();
-        let document = window.document().unwrap();
+        let window = web_sys::window()
+            .ok_or_else(|| JsValue::from_str("No window available to attach input listeners"))?;
+        let document = window
+            .document()
+            .ok_or_else(|| JsValue::from_str("No document available to attach input listeners"))?;
+
+        // Fingers must reach the listeners instead of scrolling or zooming the page
+        canvas.style().set_property("touch-action", "none")?;
+
+        // Layout changes: the drawing buffer follows the canvas's CSS size
+        {
+            let state = self.state.clone();
+            let target = canvas.clone();
+            let closure: ResizeCallback = Closure::new(move |_entries: js_sys::Array| {
+                let (width, height) = display_size(&target);
+                if (width, height) == (target.width(), target.height()) {
+                    return;
+                }
+                // At zero size the surface waits for the next nonzero one
+                target.set_width(width);
+                target.set_height(height);
+                state.borrow_mut().resized = Some((width, height));
+            });
+            let observer = web_sys::ResizeObserver::new(closure.as_ref().unchecked_ref())?;
+            observer.observe(&canvas);
+            self.resize_observer = Some((observer, closure));
+        }
 
-        // Mouse down
+        // Pointer down: mouse, pen and touch alike
         {
             let state = self.state.clone();
+            let target = canvas.clone();
             let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
-                let mouse_event = event.dyn_into::<MouseEvent>().unwrap();
-                let mut state = state.borrow_mut();
+                let pointer_event = event.dyn_into::<PointerEvent>().unwrap();
+                let touch = pointer_event.pointer_type() == "touch";
+                // Keep receiving this pointer's moves and release off the canvas
+                let _ = target.set_pointer_capture(pointer_event.pointer_id());
 
-                if mouse_event.button() == 0 {
-                    state.is_rotating = true;
-                } else if mouse_event.button() == 2 {
-                    state.is_dragging = true;
+                let mut state = state.borrow_mut();
+                state.drag_bounds = client_bounds(&target);
+                let position = client_position(&pointer_event);
+                state
+                    .pointers
+                    .insert(pointer_event.pointer_id(), PointerState { position, touch });
+                state.mouse_time = pointer_event.time_stamp();
+
+                if touch {
+                    pointer_event.prevent_default();
+                    state.rebase_touches();
+                    return;
                 }
 
-                state.last_mouse_pos =
-                    (mouse_event.client_x() as f32, mouse_event.client_y() as f32);
-                state.mouse_pos = state.last_mouse_pos;
+                let action = usize::try_from(pointer_event.button())
+                    .ok()
+                    .and_then(|button| state.button_actions.get(button).copied())
+                    .unwrap_or(MouseAction::None);
+                match action {
+                    MouseAction::Rotate => state.is_rotating = true,
+                    MouseAction::Pan => state.is_dragging = true,
+                    MouseAction::None => {}
+                }
+                state.last_mouse_pos = position;
+                state.mouse_pos = position;
+                state.last_mouse_time = state.mouse_time;
             }) as Box<dyn FnMut(web_sys::Event)>);
 
-            canvas
-                .add_event_listener_with_callback("mousedown", closure.as_ref().unchecked_ref())?;
-            self._closures.push(closure);
+            self.listen(&canvas, "pointerdown", closure)?;
         }
 
-        // Mouse move
+        // Middle-button autoscroll starts from the compatibility mousedown,
+        // which the pointerdown above cannot cancel
         {
             let state = self.state.clone();
             let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
-                let mouse_event = event.dyn_into::<MouseEvent>().unwrap();
-                let mut state = state.borrow_mut();
-                state.mouse_pos = (mouse_event.client_x() as f32, mouse_event.client_y() as f32);
+                let mouse_event = event.dyn_into::<web_sys::MouseEvent>().unwrap();
+                if mouse_event.button() == 1
+                    && state.borrow().button_actions[1] != MouseAction::None
+                {
+                    mouse_event.prevent_default();
+                }
             }) as Box<dyn FnMut(web_sys::Event)>);
 
-            canvas
-                .add_event_listener_with_callback("mousemove", closure.as_ref().unchecked_ref())?;
-            self._closures.push(closure);
+            self.listen(&canvas, "mousedown", closure)?;
         }
 
-        // Mouse up
+        // Pointer move
         {
             let state = self.state.clone();
-            let closure = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+            let target = canvas.clone();
+            let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
+                let pointer_event = event.dyn_into::<PointerEvent>().unwrap();
                 let mut state = state.borrow_mut();
-                state.is_dragging = false;
-                state.is_rotating = false;
-            }) as Box<dyn FnMut(web_sys::Event)>);
+                let position = client_position(&pointer_event);
+                let pointer = state
+                    .pointers
+                    .get_mut(&pointer_event.pointer_id())
+                    .map(|pointer| {
+                        pointer.position = position;
+                        pointer.touch
+                    });
+
+                if pointer == Some(true) {
+                    state.input_time.get_or_insert(pointer_event.time_stamp());
+                    let touches = state.touches();
+                    if touches.len() >= 2 {
+                        let distance = pinch_distance(&touches);
+                        if state.last_pinch_distance > 0.0 {
+                            let delta = distance - state.last_pinch_distance;
+                            state.zoom_delta += delta * 5.0; // Scale for sensitivity
+                        }
+                        state.last_pinch_distance = distance;
+                    } else {
+                        state.mouse_pos = position;
+                        state.mouse_time = pointer_event.time_stamp();
+                    }
+                    return;
+                }
 
-            document
-                .add_event_listener_with_callback("mouseup", closure.as_ref().unchecked_ref())?;
-            self._closures.push(closure);
-        }
+                state.mouse_pos = position;
+                state.mouse_time = pointer_event.time_stamp();
+                if state.is_rotating || state.is_dragging {
+                    state.input_time.get_or_insert(pointer_event.time_stamp());
+                }
 
-        // Prevent context menu
-        {
-            let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
-                event.prevent_default();
+                // Offsets are in CSS pixels; the canvas may be scaled by its styles
+                let scale_x = target.width() as f32 / target.client_width().max(1) as f32;
+                let scale_y = target.height() as f32 / target.client_height().max(1) as f32;
+                state.hover_pos = Some((
+                    pointer_event.offset_x() as f32 * scale_x,
+                    pointer_event.offset_y() as f32 * scale_y,
+                ));
             }) as Box<dyn FnMut(web_sys::Event)>);
 
-            canvas.add_event_listener_with_callback(
-                "contextmenu",
-                closure.as_ref().unchecked_ref(),
-            )?;
-            self._closures.push(closure);
+            self.listen(&canvas, "pointermove", closure)?;
         }
 
-        // Wheel zoom
+        // Pointer leave
         {
             let state = self.state.clone();
-            let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
-                let wheel_event = event.dyn_into::<WheelEvent>().unwrap();
-                wheel_event.prevent_default();
-                let mut state = state.borrow_mut();
-                state.zoom_delta = -wheel_event.delta_y() as f32;
+            let closure = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+                state.borrow_mut().hover_pos = None;
             }) as Box<dyn FnMut(web_sys::Event)>);
 
-            canvas.add_event_listener_with_callback("wheel", closure.as_ref().unchecked_ref())?;
-            self._closures.push(closure);
+            self.listen(&canvas, "pointerleave", closure)?;
         }
 
-        // Touch start
-        {
+        // Pointer up, or taken away by the browser
+        for event in ["pointerup", "pointercancel"] {
             let state = self.state.clone();
             let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
-                event.prevent_default();
-                let touch_event = event.dyn_into::<TouchEvent>().unwrap();
+                let pointer_event = event.dyn_into::<PointerEvent>().unwrap();
                 let mut state = state.borrow_mut();
-                let touches = touch_event.touches();
-                state.touch_count = touches.length();
-
-                if let Some(touch) = touches.get(0) {
-                    state.last_mouse_pos = (touch.client_x() as f32, touch.client_y() as f32);
-                    state.mouse_pos = state.last_mouse_pos;
-                    state.is_rotating = state.touch_count == 1;
-                }
-
-                if state.touch_count >= 2 {
-                    state.last_pinch_distance = get_pinch_distance(&touch_event);
+                let Some(pointer) = state.pointers.remove(&pointer_event.pointer_id()) else {
+                    return;
+                };
+                if pointer.touch {
+                    state.mouse_time = pointer_event.time_stamp();
+                    state.rebase_touches();
+                } else {
+                    state.is_dragging = false;
+                    state.is_rotating = false;
                 }
             }) as Box<dyn FnMut(web_sys::Event)>);
 
-            canvas
-                .add_event_listener_with_callback("touchstart", closure.as_ref().unchecked_ref())?;
-            self._closures.push(closure);
+            self.listen(&canvas, event, closure)?;
         }
 
-        // Touch move
+        // Context menu
         {
             let state = self.state.clone();
             let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
-                event.prevent_default();
-                let touch_event = event.dyn_into::<TouchEvent>().unwrap();
-                let mut state = state.borrow_mut();
-                let touches = touch_event.touches();
-
-                if touches.length() == 1 {
-                    // Single touch: rotate
-                    if let Some(touch) = touches.get(0) {
-                        state.mouse_pos = (touch.client_x() as f32, touch.client_y() as f32);
-                    }
-                } else if touches.length() >= 2 {
-                    // Pinch to zoom
-                    let new_distance = get_pinch_distance(&touch_event);
-                    if state.last_pinch_distance > 0.0 {
-                        let delta = new_distance - state.last_pinch_distance;
-                        state.zoom_delta = delta * 5.0; // Scale for sensitivity
-                    }
-                    state.last_pinch_distance = new_distance;
+                if state.borrow().suppress_context_menu {
+                    event.prevent_default();
                 }
             }) as Box<dyn FnMut(web_sys::Event)>);
 
-            canvas
-                .add_event_listener_with_callback("touchmove", closure.as_ref().unchecked_ref())?;
-            self._closures.push(closure);
+            self.listen(&canvas, "contextmenu", closure)?;
         }
 
-        // Touch end
+        // Wheel zoom
         {
             let state = self.state.clone();
             let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
-                event.prevent_default();
-                let touch_event = event.dyn_into::<TouchEvent>().unwrap();
+                let wheel_event = event.dyn_into::<WheelEvent>().unwrap();
+                wheel_event.prevent_default();
                 let mut state = state.borrow_mut();
-                state.touch_count = touch_event.touches().length();
-                if state.touch_count == 0 {
-                    state.is_rotating = false;
-                    state.last_pinch_distance = 0.0;
-                }
+                // Several wheel events can arrive within one frame; keep them all
+                state.zoom_delta += -wheel_event.delta_y() as f32;
+                state.input_time.get_or_insert(wheel_event.time_stamp());
             }) as Box<dyn FnMut(web_sys::Event)>);
 
-            canvas
-                .add_event_listener_with_callback("touchend", closure.as_ref().unchecked_ref())?;
-            self._closures.push(closure);
+            self.listen(&canvas, "wheel", closure)?;
         }
 
         // Keyboard
@@ -233,24 +427,80 @@ impl InputHandler {
                 }
             }) as Box<dyn FnMut(web_sys::Event)>);
 
-            document
-                .add_event_listener_with_callback("keydown", closure.as_ref().unchecked_ref())?;
-            self._closures.push(closure);
+            self.listen(&canvas, "keydown", closure)?;
+        }
+
+        // Tab visibility
+        {
+            let state = self.state.clone();
+            let target = document.clone();
+            let closure = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+                let mut state = state.borrow_mut();
+                let hidden = target.hidden();
+                if state.page_hidden && !hidden {
+                    state.page_shown = true;
+                }
+                state.page_hidden = hidden;
+                // Zoom left over when the tab went away is stale by the time it returns
+                if hidden {
+                    state.zoom_delta = 0.0;
+                }
+            }) as Box<dyn FnMut(web_sys::Event)>);
+
+            self.listen(&document, "visibilitychange", closure)?;
+        }
+
+        // Changes to the reduced motion preference; the value at start-up is
+        // applied directly, see `prefers_reduced_motion`
+        if let Some(query) = window.match_media(REDUCED_MOTION_QUERY)? {
+            let state = self.state.clone();
+            let target = query.clone();
+            let closure = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+                state.borrow_mut().reduced_motion_changed = Some(target.matches());
+            }) as Box<dyn FnMut(web_sys::Event)>);
+
+            self.listen(&query, "change", closure)?;
+        }
+
+        // Any of these means someone is at the controls
+        for event in ["pointerdown", "pointermove", "wheel", "keydown"] {
+            let state = self.state.clone();
+            let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
+                state.borrow_mut().last_input_time = event.time_stamp();
+            }) as Box<dyn FnMut(web_sys::Event)>);
+
+            self.listen(&canvas, event, closure)?;
         }
 
+        // Keyboard input is scoped to the canvas, so it has to be focusable
+        if !canvas.has_attribute("tabindex") {
+            canvas.set_attribute("tabindex", "0")?;
+        }
+        canvas.focus()?;
+
         Ok(())
     }
 
-    pub fn update_camera(&self, camera: &mut crate::camera::Camera) {
+    // Apply pending input to the camera. Returns the timestamp of the oldest
+    // input this reflects, if the camera moved.
+    pub fn update_camera(&self, camera: &mut crate::camera::Camera) -> Option<f64> {
         let mut state = self.state.borrow_mut();
+        let mut moved = false;
+        let seconds = ((state.mouse_time - state.last_mouse_time) / 1000.0) as f32;
 
         if state.is_rotating {
             let delta_x = state.mouse_pos.0 - state.last_mouse_pos.0;
             let delta_y = state.mouse_pos.1 - state.last_mouse_pos.1;
 
             if delta_x.abs() > 0.1 || delta_y.abs() > 0.1 {
-                camera.rotate(delta_x * 0.01, delta_y * 0.01);
+                let (left, top, width, height) = state.drag_bounds;
+                let from = (state.last_mouse_pos.0 - left, state.last_mouse_pos.1 - top);
+                let (dx, dy) =
+                    curve_delta((delta_x, delta_y), seconds, state.rotate_curve_exponent);
+                camera.drag_rotate(from, (from.0 + dx, from.1 + dy), (width, height));
                 state.last_mouse_pos = state.mouse_pos;
+                state.last_mouse_time = state.mouse_time;
+                moved = true;
             }
         }
 
@@ -259,20 +509,96 @@ impl InputHandler {
             let delta_y = state.mouse_pos.1 - state.last_mouse_pos.1;
 
             if delta_x.abs() > 0.1 || delta_y.abs() > 0.1 {
-                camera.pan(delta_x, delta_y);
+                let (dx, dy) =
+                    curve_delta((delta_x, delta_y), seconds, state.rotate_curve_exponent);
+                camera.pan(dx, dy);
                 state.last_mouse_pos = state.mouse_pos;
+                state.last_mouse_time = state.mouse_time;
+                moved = true;
             }
         }
 
         if state.zoom_delta.abs() > 0.1 {
             camera.zoom(state.zoom_delta);
             state.zoom_delta = 0.0;
+            moved = true;
         }
 
         if state.reset_pressed {
             camera.reset();
             state.reset_pressed = false;
         }
+
+        if moved {
+            state.input_time.take()
+        } else {
+            // Input that moved nothing, like a click without a drag, has no frame to wait for
+            if !state.is_rotating && !state.is_dragging && state.zoom_delta == 0.0 {
+                state.input_time = None;
+            }
+            None
+        }
+    }
+
+    // Set what dragging with `button` (0 left, 1 middle, 2 right) does
+    pub fn set_button_action(&self, button: u32, action: MouseAction) -> Result<(), JsValue> {
+        let mut state = self.state.borrow_mut();
+        let slot = usize::try_from(button)
+            .ok()
+            .and_then(|button| state.button_actions.get_mut(button))
+            .ok_or_else(|| {
+                JsValue::from_str(&format!(
+                    "Mouse button must be 0 (left), 1 (middle) or 2 (right), got {button}"
+                ))
+            })?;
+        *slot = action;
+        Ok(())
+    }
+
+    // Clamped to MIN/MAX_CURVE_EXPONENT; NaN restores the linear response
+    pub fn set_rotate_curve_exponent(&self, exponent: f32) {
+        self.state.borrow_mut().rotate_curve_exponent = if exponent.is_nan() {
+            1.0
+        } else {
+            exponent.clamp(MIN_CURVE_EXPONENT, MAX_CURVE_EXPONENT)
+        };
+    }
+
+    pub fn set_suppress_context_menu(&self, suppress: bool) {
+        self.state.borrow_mut().suppress_context_menu = suppress;
+    }
+
+    pub fn hover_position(&self) -> Option<(f32, f32)> {
+        self.state.borrow().hover_pos
+    }
+
+    // Seconds since the last user input at `now` (ms, requestAnimationFrame
+    // clock); zero while a button or finger is held down
+    pub fn idle_seconds(&self, now: f64) -> f32 {
+        let state = self.state.borrow();
+        if state.is_rotating || state.is_dragging || !state.pointers.is_empty() {
+            return 0.0;
+        }
+        ((now - state.last_input_time).max(0.0) / 1000.0) as f32
+    }
+
+    pub fn page_hidden(&self) -> bool {
+        self.state.borrow().page_hidden
+    }
+
+    // True once after the tab becomes visible again
+    pub fn page_shown(&self) -> bool {
+        std::mem::take(&mut self.state.borrow_mut().page_shown)
+    }
+
+    // New drawing buffer size, once after the canvas is resized
+    pub fn resized(&self) -> Option<(u32, u32)> {
+        self.state.borrow_mut().resized.take()
+    }
+
+    // The reduced motion preference, once after it changes
+    pub fn reduced_motion_changed(&self) -> Option<bool> {
+        self.state.borrow_mut().reduced_motion_changed.take()
     }
 
     pub fn pause_toggled(&self) -> bool {
@@ -1,57 +1,950 @@
 use wasm_bindgen::prelude::*;
 
+mod accretion;
+mod accretion_rate;
+mod autotune;
 mod camera;
+mod colormap;
+mod composite;
+mod config;
+#[cfg(debug_assertions)]
+mod desync;
+mod dof;
+mod error;
 mod graphics;
+mod horizon;
 mod input;
+mod orbit;
+mod physics;
+mod picking;
+mod poisson;
+mod query;
+mod ranges;
+mod readback;
+mod recording;
+mod reduction;
+mod render;
+mod replay;
+mod secondary;
 mod simulation;
+mod spawn;
+mod sprite;
+mod steady;
+mod taa;
+mod thumbnail;
+mod tween;
+mod upscale;
 mod utils;
 
 // Import the console_log macro from utils
 #[allow(unused_imports)]
 use utils::console_log;
 
+use accretion_rate::AccretionRate;
+use autotune::TunedDefaults;
 use camera::Camera;
+use config::{
+    CameraMode, ColorMode, DistributionConfig, Integrator, MouseAction, ReactiveMapping,
+    ReactiveTarget, RenderMode, SimulationConfig, DISTRIBUTIONS,
+};
+use error::{ErrorMonitor, ErrorScopes};
 use graphics::Graphics;
+use horizon::Horizon;
 use input::InputHandler;
-use simulation::Simulation;
-use utils::set_panic_hook;
+use recording::Recorder;
+use render::Renderer;
+use secondary::{OrbitingMass, MAX_ECCENTRICITY};
+use simulation::{Simulation, NUM_GROUPS};
+use steady::SteadyStateDetector;
+use thumbnail::ThumbnailTarget;
+use tween::Tweens;
+use upscale::InternalTarget;
+use utils::{clamp_color, set_panic_hook};
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt::Write;
 use std::rc::Rc;
 use wasm_bindgen_futures::spawn_local;
 
-// Global application state
+// Application state for one simulation instance
 pub struct AppState {
     graphics: Graphics,
     simulation: Simulation,
+    horizon: Horizon,
+    renderer: Renderer,
     camera: Camera,
     input_handler: InputHandler,
-    paused: bool,
+    // Stop advancing the particles; the view keeps rendering
+    physics_paused: bool,
+    // Physics paused automatically while the tab is hidden, independent of
+    // `physics_paused` so coming back never overrides a manual pause
+    auto_paused: bool,
+    // Slow the physics and hold the auto-rotation, following
+    // `prefers-reduced-motion` unless set explicitly
+    reduced_motion: bool,
+    // Stop drawing frames; the physics keeps running
+    render_paused: bool,
     last_time: f32,
+    background: [f32; 3],
+    // Fog keeps matching the background until it is given its own color
+    fog_follows_background: bool,
+    fog_density: f32,
+    // Optional frame-rate cap, and when the last frame under it was drawn (ms)
+    max_fps: Option<f32>,
+    last_frame_time: f64,
+    // Highlight whatever particle is under the cursor
+    hover_highlight: bool,
+    highlight: Option<u32>,
+    // Recent delays from an input event to the frame that applied it (ms)
+    input_latencies: VecDeque<f32>,
+    // Durations of the last FRAME_TIME_WINDOW frames (ms)
+    frame_times: VecDeque<f32>,
+    reactive: ReactiveMapping,
+    // Latest value from the host, applied once at the start of the next frame
+    pending_reactive_value: Option<f32>,
+    // Parameters easing towards targets set by `animate_param`
+    tweens: Tweens,
+    // Kept after stopping until its pending frames have been stored
+    recorder: Option<Recorder>,
+    error_monitor: ErrorMonitor,
+    // Fixed low-resolution target the scene is drawn into, then upscaled
+    internal: Option<InternalTarget>,
+    // Called with `{ kind, message }` for each GPU error after start-up
+    error_callback: Option<js_sys::Function>,
+    steady_state: SteadyStateDetector,
+    accretion_rate: AccretionRate,
+    // Called once with the energy and centroid when the system settles
+    steady_state_callback: Option<js_sys::Function>,
+    // Host callbacks due this frame; see `take_pending_callbacks`
+    pending_callbacks: Vec<PendingCallback>,
+}
+
+// A host callback and its argument, queued during a frame and called once the
+// frame has released the state, so the callback can use the handle
+struct PendingCallback {
+    name: &'static str,
+    callback: js_sys::Function,
+    details: JsValue,
+}
+
+impl PendingCallback {
+    fn call(self) {
+        if let Err(e) = self.callback.call1(&JsValue::NULL, &self.details) {
+            console_log!("⚠️ {} callback threw: {:?}", self.name, e);
+        }
+    }
 }
 
 impl AppState {
-    pub async fn new(canvas: web_sys::HtmlCanvasElement) -> Result<Self, JsValue> {
+    pub async fn new(
+        canvas: web_sys::HtmlCanvasElement,
+        config: SimulationConfig,
+    ) -> Result<Self, JsValue> {
         console_log!("Initializing Black Hole Simulation...");
 
-        let graphics = Graphics::new(canvas).await?;
-        let simulation =
-            Simulation::new(&graphics.device, &graphics.queue, graphics.config.format)?;
-        let camera = Camera::new();
+        let graphics = Graphics::new(canvas, config.transparent_background).await?;
+        let device = &graphics.device;
+        let error_monitor = ErrorMonitor::install(device);
+
+        // Every GPU resource below is created inside a labelled error scope, so a
+        // broken shader or pipeline is reported by name
+        let mut scopes = ErrorScopes::new();
+        let mut simulation = Simulation::new(
+            device,
+            &graphics.queue,
+            graphics.config.format,
+            &config,
+            &mut scopes,
+        )?;
+        simulation.set_viewport(&graphics.queue, graphics.size.0, graphics.size.1);
+        if !graphics.compute_supported {
+            console_log!(
+                "⚠️ No compute shaders, stepping {} particles on the CPU",
+                CPU_FALLBACK_PARTICLES
+            );
+            simulation.set_cpu_physics(&graphics.queue, true);
+            simulation.set_active_particles(CPU_FALLBACK_PARTICLES);
+        }
+        let horizon = scopes.capture(device, "Horizon", || {
+            Horizon::new(device, graphics.config.format, config.horizon.clone())
+        });
+        let renderer = scopes.capture(device, "Renderer", || {
+            Renderer::new(
+                device,
+                graphics.config.format,
+                graphics.size,
+                &graphics.depth_view,
+                &simulation,
+                &config,
+            )
+        });
+        scopes.finish().await?;
+        let camera = Camera::new(config.camera);
         let input_handler = InputHandler::new()?;
+        input_handler.set_rotate_curve_exponent(config.rotate_curve_exponent);
 
-        Ok(Self {
+        let mut state = Self {
             graphics,
             simulation,
+            horizon,
+            renderer,
             camera,
             input_handler,
-            paused: false,
+            physics_paused: false,
+            auto_paused: false,
+            reduced_motion: false,
+            render_paused: false,
             last_time: 0.0,
+            background: config.background,
+            fog_follows_background: config.fog_color.is_none(),
+            fog_density: config.fog_density,
+            max_fps: None,
+            last_frame_time: 0.0,
+            hover_highlight: true,
+            highlight: None,
+            input_latencies: VecDeque::with_capacity(INPUT_LATENCY_WINDOW),
+            frame_times: VecDeque::with_capacity(FRAME_TIME_WINDOW),
+            reactive: config.reactive,
+            pending_reactive_value: None,
+            tweens: Tweens::default(),
+            recorder: None,
+            error_monitor,
+            internal: None,
+            error_callback: None,
+            steady_state: SteadyStateDetector::new(
+                config.steady_state_energy_tolerance,
+                config.steady_state_drift_tolerance,
+                config.steady_state_window,
+            ),
+            steady_state_callback: None,
+            pending_callbacks: Vec::new(),
+            accretion_rate: AccretionRate::default(),
+        };
+        if config.auto_tune {
+            state.auto_tune();
+        }
+        Ok(state)
+    }
+
+    // Whether a frame should be drawn at this rAF timestamp under the frame-rate cap
+    fn frame_due(&mut self, time: f64) -> bool {
+        if let Some(max_fps) = self.max_fps {
+            // Allow a little timestamp jitter so a 30fps cap on a 60Hz display
+            // doesn't drop to 20fps
+            let interval = 1000.0 / max_fps as f64;
+            if time - self.last_frame_time < interval - FRAME_CAP_TOLERANCE_MS {
+                return false;
+            }
+        }
+        self.last_frame_time = time;
+        true
+    }
+
+    // Cap the frame rate (e.g. 30fps for ambient displays); `None` draws every display frame
+    pub fn set_max_fps(&mut self, max_fps: Option<f32>) {
+        self.max_fps = max_fps.filter(|fps| fps.is_finite() && *fps > 0.0);
+    }
+
+    // Step the physics at a fixed rate, interpolating what is drawn in between;
+    // `None` steps once per frame
+    pub fn set_fixed_timestep(&mut self, step: Option<f32>) {
+        self.simulation
+            .set_fixed_timestep(&self.graphics.queue, step);
+    }
+
+    // Science mode: every physics step advances exactly SCIENCE_TIMESTEP of
+    // simulated time, however fast frames arrive, and the clock starts from 0
+    // so `sim_time` lines up with the orbit and energy diagnostics
+    pub fn set_science_mode(&mut self, enabled: bool) {
+        self.simulation
+            .set_fixed_timestep(&self.graphics.queue, enabled.then_some(SCIENCE_TIMESTEP));
+        self.simulation.reset_sim_time();
+        console_log!("🔬 Science mode {}", if enabled { "on" } else { "off" });
+    }
+
+    pub fn sim_time(&self) -> f64 {
+        self.simulation.sim_time()
+    }
+
+    pub fn reset_sim_time(&mut self) {
+        self.simulation.reset_sim_time();
+    }
+
+    // Fast-forward by running the physics `substeps` times per frame (1 is real
+    // time); capped so a single frame stays responsive
+    pub fn set_substeps(&mut self, substeps: u32) {
+        let applied = self.simulation.set_substeps(substeps);
+        if applied != substeps {
+            console_log!(
+                "⏩ Substeps clamped to {} (1 to {})",
+                applied,
+                simulation::MAX_SUBSTEPS_PER_FRAME
+            );
+        }
+    }
+
+    // Restart from one of the presets listed by `available_distributions`
+    pub fn set_distribution(&mut self, name: &str) -> Result<(), JsValue> {
+        let distribution = DistributionConfig::named(name).ok_or_else(|| {
+            let names: Vec<_> = DISTRIBUTIONS.iter().map(|(name, _)| *name).collect();
+            JsValue::from_str(&format!(
+                "Unknown distribution '{name}', expected one of: {}",
+                names.join(", ")
+            ))
+        })?;
+
+        self.simulation
+            .set_distribution(&self.graphics.queue, distribution);
+        console_log!("🌌 Distribution: {}", name);
+        Ok(())
+    }
+
+    // Restart from a scripted scene: an array of spawn components in the form
+    // read by `SpawnComponent::from_js`. The other distribution settings are
+    // kept; an empty array goes back to the default disk and stream.
+    pub fn set_spawn_regions(&mut self, components: &JsValue) -> Result<(), JsValue> {
+        if !js_sys::Array::is_array(components) {
+            return Err(JsValue::from_str("Spawn regions must be an array"));
+        }
+        let regions = js_sys::Array::from(components)
+            .iter()
+            .enumerate()
+            .map(|(i, component)| {
+                spawn::SpawnComponent::from_js(&component)
+                    .map_err(|err| JsValue::from_str(&format!("Spawn component {i}: {err}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if !regions.is_empty() && regions.iter().all(|c| c.weight <= 0.0) {
+            return Err(JsValue::from_str(
+                "At least one spawn component needs a positive weight",
+            ));
+        }
+
+        let count = regions.len();
+        let distribution = DistributionConfig {
+            regions,
+            ..self.simulation.distribution().clone()
+        };
+        self.simulation
+            .set_distribution(&self.graphics.queue, distribution);
+        console_log!("🌌 Scene of {} spawn regions", count);
+        Ok(())
+    }
+
+    // Restart with the close stars' surface density going as r^-exponent;
+    // 1 is the default even spread in radius, higher concentrates them inward
+    pub fn set_radial_density_exponent(&mut self, exponent: f32) {
+        self.simulation
+            .set_radial_density_exponent(&self.graphics.queue, exponent);
+    }
+
+    // Restart with the particles spread evenly at least `spacing` apart
+    // instead of independently at random (0)
+    pub fn set_poisson_spacing(&mut self, spacing: f32) {
+        self.simulation
+            .set_poisson_spacing(&self.graphics.queue, spacing);
+    }
+
+    // Restart from particles supplied as PARTICLE_FLOATS values each:
+    // x, y, z, group, vx, vy, vz and one unused value, matching the start of
+    // the GPU layout. Returns the new particle count. `set_distribution` goes
+    // back to generated ones.
+    pub fn load_particles(&mut self, data: &[f32]) -> Result<u32, JsValue> {
+        if data.is_empty() || !data.len().is_multiple_of(PARTICLE_FLOATS) {
+            return Err(JsValue::from_str(&format!(
+                "Particle data must be a non-empty multiple of {PARTICLE_FLOATS} floats, got {}",
+                data.len()
+            )));
+        }
+        if data.iter().any(|v| !v.is_finite()) {
+            return Err(JsValue::from_str(
+                "Particle data contains NaN or infinite values",
+            ));
+        }
+
+        let particles = data
+            .as_chunks::<PARTICLE_FLOATS>()
+            .0
+            .iter()
+            .map(|p| simulation::Particle {
+                position: [p[0], p[1], p[2]],
+                group: (p[3].max(0.0) as u32).min(NUM_GROUPS as u32 - 1),
+                velocity: [p[4], p[5], p[6]],
+                flags: 0,
+                acceleration: [0.0; 3],
+                temperature: 0.0,
+            })
+            .collect();
+        let count = self
+            .simulation
+            .load_particles(&self.graphics.queue, particles)?;
+        console_log!("📥 Loaded {} particles", count);
+        Ok(count)
+    }
+
+    // Per-frame input from the host, e.g. an audio amplitude computed with Web
+    // Audio; routed to a parameter by `set_reactive_mapping`. Only the latest
+    // value before each frame is used.
+    pub fn set_reactive_value(&mut self, value: f32) {
+        if value.is_finite() {
+            self.pending_reactive_value = Some(value);
+        }
+    }
+
+    // Drive `target` with `base + value * range` from now on
+    pub fn set_reactive_mapping(&mut self, target: ReactiveTarget, base: f32, range: f32) {
+        self.reactive = ReactiveMapping {
+            target,
+            base,
+            range,
+        };
+    }
+
+    fn apply_reactive_value(&mut self, value: f32) {
+        let mapped = self.reactive.base + value * self.reactive.range;
+        let queue = &self.graphics.queue;
+        match self.reactive.target {
+            ReactiveTarget::None => {}
+            ReactiveTarget::Gm => self.simulation.set_gm(mapped),
+            ReactiveTarget::ParticleAlpha => self.simulation.set_particle_alpha(queue, mapped),
+            ReactiveTarget::InnerGlowStrength => {
+                self.simulation.set_inner_glow_strength(queue, mapped)
+            }
+            ReactiveTarget::DensityGlowStrength => {
+                self.renderer.set_density_glow_strength(queue, mapped)
+            }
+        }
+    }
+
+    // Ease the parameter `name` from its current value to `target` over
+    // `duration` seconds of real time, e.g. raising gm to tighten the disk for
+    // a recording. Targets are clamped to the parameter's range; several
+    // parameters can ease at once, and a new animation of one that is already
+    // easing starts over from where it has got to.
+    pub fn animate_param(&mut self, name: &str, target: f32, duration: f32) -> Result<(), JsValue> {
+        let range = ranges::parameter_ranges()
+            .into_iter()
+            .find(|range| range.name == name);
+        let (Some(range), Some(from)) = (range, self.animated_param(name)) else {
+            return Err(JsValue::from_str(&format!(
+                "Cannot animate {name:?}; expected one of {}",
+                ANIMATED_PARAMS.join(", ")
+            )));
+        };
+        if !target.is_finite() || !duration.is_finite() || duration < 0.0 {
+            return Err(JsValue::from_str(&format!(
+                "Invalid animation of {name} to {target} over {duration}s"
+            )));
+        }
+        self.tweens.start(
+            range.name,
+            from,
+            target.clamp(range.min, range.max),
+            duration,
+        );
+        Ok(())
+    }
+
+    // Stop every running animation where it is
+    pub fn cancel_animations(&mut self) {
+        self.tweens.cancel();
+    }
+
+    // Current value of a parameter in ANIMATED_PARAMS
+    fn animated_param(&self, name: &str) -> Option<f32> {
+        let params = self.simulation.params();
+        let render_params = self.simulation.render_params();
+        Some(match name {
+            "gm" => params.gm,
+            "absorb_radius" => params.absorb_radius,
+            "heating_rate" => params.heating_rate,
+            "cooling_rate" => params.cooling_rate,
+            "warp_amplitude" => params.warp_amplitude,
+            "precession_rate" => params.precession_rate,
+            "particle_alpha" => render_params.particle_alpha,
+            "sprite_size" => render_params.sprite_size,
+            "min_screen_size" => render_params.min_screen_size,
+            "radial_bands" => render_params.radial_bands,
+            _ => return None,
         })
     }
 
+    fn set_animated_param(&mut self, name: &str, value: f32) {
+        let params = *self.simulation.params();
+        let queue = &self.graphics.queue;
+        match name {
+            "gm" => self.simulation.set_gm(value),
+            "absorb_radius" => self.simulation.set_absorb_radius(value),
+            "heating_rate" => self
+                .simulation
+                .set_temperature_rates(value, params.cooling_rate),
+            "cooling_rate" => self
+                .simulation
+                .set_temperature_rates(params.heating_rate, value),
+            "warp_amplitude" => self.simulation.set_warp(value, params.precession_rate),
+            "precession_rate" => self.simulation.set_warp(params.warp_amplitude, value),
+            "particle_alpha" => self.simulation.set_particle_alpha(queue, value),
+            "sprite_size" => self.simulation.set_sprite_size(queue, value),
+            "min_screen_size" => self.simulation.set_min_screen_size(queue, value),
+            "radial_bands" => self.simulation.set_radial_bands(queue, value),
+            _ => {}
+        }
+    }
+
+    // Average delay in ms from a camera input event to the start of the frame
+    // that applied it, over the last INPUT_LATENCY_WINDOW inputs; `None` before
+    // any input. Presentation adds roughly another frame on top.
+    pub fn input_latency_ms(&self) -> Option<f32> {
+        if self.input_latencies.is_empty() {
+            return None;
+        }
+        Some(self.input_latencies.iter().sum::<f32>() / self.input_latencies.len() as f32)
+    }
+
+    // How many of the last FRAME_TIME_WINDOW frames took under 8.5 ms, 8.5-17 ms,
+    // 17-34 ms and longer, to tell steady low frame rates from hitches
+    pub fn frame_time_histogram(&self) -> [u32; FRAME_TIME_BUCKETS_MS.len() + 1] {
+        let mut counts = [0; FRAME_TIME_BUCKETS_MS.len() + 1];
+        for &time in &self.frame_times {
+            let bucket = FRAME_TIME_BUCKETS_MS
+                .iter()
+                .position(|&bound| time < bound)
+                .unwrap_or(FRAME_TIME_BUCKETS_MS.len());
+            counts[bucket] += 1;
+        }
+        counts
+    }
+
+    // Particles swallowed by the black hole per simulated second, averaged
+    // over the last few seconds: a luminosity proxy for the disk
+    pub fn accretion_rate(&self) -> f32 {
+        self.accretion_rate.rate()
+    }
+
+    // `{ name: { min, max, default, step } }` for every numeric tunable, named
+    // after the setter argument, so host UIs can configure their sliders
+    pub fn parameter_ranges(&self) -> JsValue {
+        let ranges = js_sys::Object::new();
+        for range in ranges::parameter_ranges() {
+            let entry = js_sys::Object::new();
+            // Setting a string key on a fresh object cannot fail
+            for (key, value) in [
+                ("min", range.min),
+                ("max", range.max),
+                ("default", range.default),
+                ("step", range.step),
+            ] {
+                let _ = js_sys::Reflect::set(&entry, &key.into(), &value.into());
+            }
+            let _ = js_sys::Reflect::set(&ranges, &range.name.into(), &entry);
+        }
+        ranges.into()
+    }
+
+    // Live values of the simulation and view parameters as a plain object, for
+    // initializing host controls or sharing a setup. Numeric keys are named as
+    // in `parameter_ranges` and the URL query, so e.g.
+    // `new URLSearchParams(snapshot)` on a page link reloads the ones the query
+    // understands; enums are their numeric values and colors [r, g, b].
+    // Parameters added later belong here too.
+    pub fn current_params(&self) -> JsValue {
+        let params = self.simulation.params();
+        let render_params = self.simulation.render_params();
+        let distribution = self.simulation.distribution();
+        let (vignette_strength, vignette_radius) = self.renderer.vignette();
+        let (ambient_floor, gamma, dither_strength) = self.renderer.composite_settings();
+        let triple = |[r, g, b]: [f32; 3]| {
+            JsValue::from(js_sys::Array::of3(&r.into(), &g.into(), &b.into()))
+        };
+        let [fog_r, fog_g, fog_b, _] = render_params.fog_color;
+
+        let [dispersion_radial, dispersion_tangential, dispersion_vertical] =
+            distribution.velocity_dispersion;
+        let [orientation_x, orientation_y, orientation_z] = distribution.disk_orientation;
+
+        let entries: [(&str, JsValue); 56] = [
+            // Physics
+            ("gm", params.gm.into()),
+            ("softening", params.softening.into()),
+            ("integrator", params.integrator.into()),
+            ("particle_count", params.particle_count.into()),
+            ("absorb_radius", params.absorb_radius.into()),
+            ("respawn_radius", params.respawn_radius.into()),
+            ("max_speed", params.max_speed.into()),
+            ("inflow_rate", self.simulation.inflow_rate().into()),
+            ("heating_rate", params.heating_rate.into()),
+            ("cooling_rate", params.cooling_rate.into()),
+            ("warp_amplitude", params.warp_amplitude.into()),
+            ("precession_rate", params.precession_rate.into()),
+            ("wrap_enabled", (params.wrap_enabled != 0).into()),
+            ("wrap_bounds", triple(params.wrap_bounds)),
+            // Time
+            (
+                "fixed_timestep",
+                self.simulation
+                    .fixed_timestep()
+                    .map_or(JsValue::NULL, JsValue::from),
+            ),
+            ("substeps", self.simulation.substeps_per_frame().into()),
+            (
+                "time_scale",
+                if self.reduced_motion {
+                    REDUCED_MOTION_TIME_SCALE
+                } else {
+                    1.0
+                }
+                .into(),
+            ),
+            ("physics_paused", self.physics_paused.into()),
+            // Initial distribution
+            (
+                "radial_density_exponent",
+                distribution.radial_density_exponent.into(),
+            ),
+            ("poisson_spacing", distribution.poisson_spacing.into()),
+            ("disk_flatten", distribution.disk_flatten.into()),
+            ("disk_height_spread", distribution.disk_height_spread.into()),
+            ("orbital_fraction", distribution.orbital_fraction.into()),
+            ("velocity_jitter", distribution.velocity_jitter.into()),
+            ("velocity_dispersion_radial", dispersion_radial.into()),
+            (
+                "velocity_dispersion_tangential",
+                dispersion_tangential.into(),
+            ),
+            ("velocity_dispersion_vertical", dispersion_vertical.into()),
+            ("disk_orientation_x", orientation_x.into()),
+            ("disk_orientation_y", orientation_y.into()),
+            ("disk_orientation_z", orientation_z.into()),
+            ("halo_speed", distribution.halo_speed.into()),
+            ("halo_core_radius", distribution.halo_core_radius.into()),
+            // Particle drawing
+            ("render_mode", (self.renderer.mode() as u32).into()),
+            ("color_mode", render_params.color_mode.into()),
+            ("color_mode_b", render_params.color_mode_b.into()),
+            ("color_blend", render_params.color_blend.into()),
+            ("particle_alpha", render_params.particle_alpha.into()),
+            ("sprite_size", render_params.sprite_size.into()),
+            (
+                "size_attenuation",
+                (render_params.size_attenuation != 0).into(),
+            ),
+            ("min_screen_size", render_params.min_screen_size.into()),
+            ("radial_bands", render_params.radial_bands.into()),
+            // Scene
+            ("background", triple(self.background)),
+            ("fog_color", triple([fog_r, fog_g, fog_b])),
+            ("fog_density", self.fog_density.into()),
+            (
+                "density_glow_strength",
+                self.renderer.density_glow_strength().into(),
+            ),
+            ("vignette_strength", vignette_strength.into()),
+            ("vignette_radius", vignette_radius.into()),
+            ("ambient_floor", ambient_floor.into()),
+            ("gamma", gamma.into()),
+            ("dither_strength", dither_strength.into()),
+            // Camera
+            ("scale", self.camera.scale.into()),
+            ("rotx", self.camera.rotation_x.into()),
+            ("roty", self.camera.rotation_y.into()),
+            ("target_x", self.camera.position.x.into()),
+            ("target_y", self.camera.position.y.into()),
+            ("target_z", self.camera.position.z.into()),
+        ];
+        let snapshot = js_sys::Object::new();
+        // Setting a string key on a fresh object cannot fail
+        for (key, value) in entries {
+            let _ = js_sys::Reflect::set(&snapshot, &key.into(), &value);
+        }
+        snapshot.into()
+    }
+
+    // Pick the particle count and quality settings for the kind of GPU this is
+    // running on: integrated and software GPUs get fewer particles and lose
+    // TAA, depth of field and the density glow, while discrete and
+    // unrecognised ones keep their current count and effects. It only ever
+    // lowers the particle count.
+    // Runs at start-up unless `auto_tune` is off in the config, and anything
+    // it sets can be changed afterwards. Returns `{ gpu_class,
+    // gpu_description, particles, post_effects }`.
+    pub fn auto_tune(&mut self) -> JsValue {
+        let class = self.graphics.gpu_class;
+        let TunedDefaults {
+            particles,
+            post_effects,
+        } = autotune::defaults_for(class);
+
+        // An inflow builds up to the whole buffer on its own
+        if let Some(particles) = particles.filter(|_| self.simulation.inflow_rate() <= 0.0) {
+            if particles < self.simulation.active_particle_count() {
+                self.simulation.set_active_particles(particles);
+            }
+        }
+        if !post_effects {
+            self.renderer.set_taa(false);
+            let focus_distance = self.renderer.depth_of_field_focus();
+            self.renderer.set_depth_of_field(0.0, focus_distance);
+            self.renderer
+                .set_density_glow_strength(&self.graphics.queue, 0.0);
+        }
+        let particles = self.simulation.active_particle_count();
+        console_log!(
+            "🎚️ Auto-tuned for a {} GPU: {} particles, post effects {}",
+            class.name(),
+            particles,
+            if post_effects { "kept" } else { "off" }
+        );
+
+        let entries: [(&str, JsValue); 4] = [
+            ("gpu_class", class.name().into()),
+            (
+                "gpu_description",
+                self.graphics.gpu_description.as_str().into(),
+            ),
+            ("particles", particles.into()),
+            ("post_effects", post_effects.into()),
+        ];
+        let settings = js_sys::Object::new();
+        // Setting a string key on a fresh object cannot fail
+        for (key, value) in entries {
+            let _ = js_sys::Reflect::set(&settings, &key.into(), &value);
+        }
+        settings.into()
+    }
+
+    // Orbit of particle `index` as `{ periapsis, apoapsis, semi_major_axis,
+    // period, kepler_period, passages, absorbed }`, in world units and
+    // simulated seconds. The first call for an index starts following it, and
+    // null is returned until it has passed both periapsis and apoapsis; `period`
+    // stays null until a second periapsis.
+    pub fn orbit_stats(&mut self, index: u32) -> Result<JsValue, JsValue> {
+        let count = self.simulation.active_particle_count();
+        if index >= count {
+            return Err(JsValue::from_str(&format!(
+                "Particle {index} is not active ({count} particles)"
+            )));
+        }
+        if self.simulation.tracked_orbit().map(|o| o.index()) != Some(index) {
+            self.simulation
+                .track_orbit(&self.graphics.device, Some(index));
+            console_log!("🪐 Tracking the orbit of particle {}", index);
+            return Ok(JsValue::NULL);
+        }
+
+        let Some(stats) = self.simulation.orbit_stats() else {
+            return Ok(JsValue::NULL);
+        };
+        let entry = js_sys::Object::new();
+        let fields: [(&str, JsValue); 7] = [
+            ("periapsis", stats.periapsis.into()),
+            ("apoapsis", stats.apoapsis.into()),
+            ("semi_major_axis", stats.semi_major_axis.into()),
+            ("period", stats.period.map_or(JsValue::NULL, JsValue::from)),
+            ("kepler_period", stats.kepler_period.into()),
+            ("passages", stats.passages.into()),
+            ("absorbed", stats.absorbed.into()),
+        ];
+        for (key, value) in fields {
+            let _ = js_sys::Reflect::set(&entry, &key.into(), &value);
+        }
+        Ok(entry.into())
+    }
+
+    pub fn stop_orbit_tracking(&mut self) {
+        self.simulation.track_orbit(&self.graphics.device, None);
+    }
+
+    // Slowly turn the view around the black hole at `speed` rad/s once there
+    // has been no input for `idle_seconds`, e.g. for ambient displays
+    pub fn set_auto_rotate(&mut self, enabled: bool, speed: f32, idle_seconds: f32) {
+        self.camera.set_auto_rotate(enabled, speed, idle_seconds);
+    }
+
+    // Run the physics at REDUCED_MOTION_TIME_SCALE and hold the auto-rotation.
+    // Follows the `prefers-reduced-motion` media query by default; a call here
+    // holds until the system setting next changes.
+    pub fn set_reduced_motion(&mut self, enabled: bool) {
+        if enabled != self.reduced_motion {
+            console_log!("🐢 Reduced motion {}", if enabled { "on" } else { "off" });
+        }
+        self.reduced_motion = enabled;
+        self.camera.set_auto_rotate_suppressed(enabled);
+    }
+
+    // Record `seconds` of frames at `fps` into IndexedDB, replacing any
+    // recording still in progress. Frames are only captured while rendering.
+    pub fn start_recording(&mut self, fps: f32, seconds: f32) -> Result<(), JsValue> {
+        let config = &self.graphics.config;
+        if !config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(JsValue::from_str(
+                "This browser's canvas cannot be read back for recording",
+            ));
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.stop();
+        }
+        self.recorder = Some(Recorder::start(
+            config.width,
+            config.height,
+            config.format,
+            fps,
+            seconds,
+        )?);
+        Ok(())
+    }
+
+    // Stop recording and return where the frames were stored (see
+    // `Recorder::stop`), or null if nothing was recorded
+    pub fn stop_recording(&mut self) -> JsValue {
+        match &mut self.recorder {
+            Some(recorder) => recorder.stop(),
+            None => JsValue::NULL,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.as_ref().is_some_and(Recorder::is_active)
+    }
+
+    // Frames captured but not yet in the database
+    pub fn recording_pending(&self) -> u32 {
+        self.recorder.as_ref().map_or(0, Recorder::pending)
+    }
+
+    // Receive GPU errors raised while running, such as a failed draw or a lost
+    // device, as `{ kind, message }`; `kind` is "validation", "out-of-memory",
+    // "internal" or "device-lost". They are logged either way.
+    pub fn set_error_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.error_callback = callback;
+    }
+
+    // Be called back once the simulation has settled, with `{ time,
+    // kinetic_energy, potential_energy, centroid }`; setting a callback starts
+    // watching afresh, so each one fires at most once
+    pub fn set_steady_state_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.steady_state_callback = callback;
+        self.steady_state.reset();
+    }
+
+    // Relative energy variation, centroid drift in world units, and the
+    // simulated seconds both must stay within for a steady state
+    pub fn set_steady_state_tolerances(&mut self, energy: f32, drift: f32, window: f32) {
+        self.steady_state.set_tolerances(energy, drift, window);
+    }
+
+    fn check_steady_state(&mut self) {
+        if self.steady_state_callback.is_none() {
+            return;
+        }
+        let Some(stats) = self.simulation.reduce_positions() else {
+            return;
+        };
+        if !self.steady_state.observe(&stats) {
+            return;
+        }
+
+        console_log!("⚖️ Steady state reached at {:.1}s simulated", stats.time);
+        let details = js_sys::Object::new();
+        let centroid = js_sys::Array::new();
+        for value in stats.centroid {
+            centroid.push(&value.into());
+        }
+        let fields: [(&str, JsValue); 4] = [
+            ("time", stats.time.into()),
+            ("kinetic_energy", stats.kinetic_energy.into()),
+            ("potential_energy", stats.potential_energy.into()),
+            ("centroid", centroid.into()),
+        ];
+        for (name, value) in fields {
+            let _ = js_sys::Reflect::set(&details, &name.into(), &value);
+        }
+        if let Some(callback) = &self.steady_state_callback {
+            self.pending_callbacks.push(PendingCallback {
+                name: "Steady state",
+                callback: callback.clone(),
+                details: details.into(),
+            });
+        }
+    }
+
+    // Host callbacks queued by the last `update`, to be called once the state
+    // is no longer borrowed
+    fn take_pending_callbacks(&mut self) -> Vec<PendingCallback> {
+        std::mem::take(&mut self.pending_callbacks)
+    }
+
+    fn report_gpu_errors(&mut self, current_time: f32) {
+        for error in self.error_monitor.take() {
+            console_log!(
+                "❌ GPU {} at {:.1}s ({} particles, {:?} view): {}",
+                error.kind_id(),
+                current_time / 1000.0,
+                self.simulation.active_particle_count(),
+                self.renderer.mode(),
+                error.message
+            );
+            if let Some(callback) = &self.error_callback {
+                let details = js_sys::Object::new();
+                let _ = js_sys::Reflect::set(&details, &"kind".into(), &error.kind_id().into());
+                let _ = js_sys::Reflect::set(&details, &"message".into(), &error.message.into());
+                self.pending_callbacks.push(PendingCallback {
+                    name: "GPU error",
+                    callback: callback.clone(),
+                    details: details.into(),
+                });
+            }
+        }
+    }
+
+    // Physics advances unless paused by the user or by a hidden tab
+    fn physics_running(&self) -> bool {
+        !self.physics_paused && !self.auto_paused
+    }
+
     pub fn update(&mut self, current_time: f32) {
+        self.report_gpu_errors(current_time);
+        self.check_steady_state();
+        if let Some(stats) = self.simulation.reduce_positions() {
+            self.accretion_rate.observe(&stats);
+        }
+
+        // A hidden tab pauses the physics; on return the time spent away is
+        // dropped instead of being taken as one giant step
+        let hidden = self.input_handler.page_hidden();
+        if hidden != self.auto_paused {
+            console_log!(
+                "{} Tab {}",
+                if hidden { "💤" } else { "👀" },
+                if hidden {
+                    "hidden, pausing physics"
+                } else {
+                    "visible again"
+                }
+            );
+            self.auto_paused = hidden;
+        }
+        if self.input_handler.page_shown() {
+            self.last_time = 0.0;
+        }
+        if let Some(reduced) = self.input_handler.reduced_motion_changed() {
+            self.set_reduced_motion(reduced);
+        }
+        if let Some((width, height)) = self.input_handler.resized() {
+            self.resize(width, height);
+        }
+
+        if let Some(value) = self.pending_reactive_value.take() {
+            self.apply_reactive_value(value);
+        }
+
         // requestAnimationFrame provides time in milliseconds
+        if self.last_time > 0.0 {
+            if self.frame_times.len() == FRAME_TIME_WINDOW {
+                self.frame_times.pop_front();
+            }
+            self.frame_times.push_back(current_time - self.last_time);
+        }
         let dt = if self.last_time > 0.0 {
             (current_time - self.last_time) / 1000.0 // Convert to seconds
         } else {
@@ -59,34 +952,67 @@ impl AppState {
         };
         self.last_time = current_time;
 
+        for (name, value) in self.tweens.advance(dt) {
+            self.set_animated_param(name, value);
+        }
+
         // Update camera based on input
-        self.input_handler.update_camera(&mut self.camera);
+        if let Some(input_time) = self.input_handler.update_camera(&mut self.camera) {
+            if self.input_latencies.len() == INPUT_LATENCY_WINDOW {
+                self.input_latencies.pop_front();
+            }
+            self.input_latencies
+                .push_back((current_time as f64 - input_time).max(0.0) as f32);
+        }
+        self.camera
+            .update(dt, self.input_handler.idle_seconds(current_time as f64));
 
         // Check for pause toggle first
         if self.input_handler.pause_toggled() {
-            self.paused = !self.paused;
+            // Only the physics, so the paused scene can still be looked around
+            self.set_physics_paused(!self.physics_paused);
             console_log!(
                 "Simulation {}",
-                if self.paused { "paused" } else { "resumed" }
+                if self.physics_paused {
+                    "paused"
+                } else {
+                    "resumed"
+                }
             );
         }
 
+        if self.hover_highlight {
+            if self.input_handler.hover_position().is_none() {
+                if self.highlight.is_some() {
+                    self.set_highlight(None);
+                }
+            } else if let Some(hit) = self.simulation.take_pick() {
+                if hit != self.highlight {
+                    self.set_highlight(hit);
+                }
+            }
+        }
+
+        // Keep streaming the initial particles in, even while paused
+        self.simulation.stream_particles(&self.graphics.queue);
+
         // Update simulation if not paused
-        if !self.paused {
-            self.simulation.update(&self.graphics.queue, dt);
+        if self.physics_running() {
+            let time_scale = if self.reduced_motion {
+                REDUCED_MOTION_TIME_SCALE
+            } else {
+                1.0
+            };
+            self.simulation
+                .update(&self.graphics.queue, dt * time_scale);
         }
     }
 
     pub fn render(&mut self) -> Result<(), wasm_bindgen::JsValue> {
-        let frame = self
-            .graphics
-            .surface
-            .get_current_texture()
-            .map_err(|e| JsValue::from_str(&format!("Failed to get surface texture: {e:?}")))?;
-
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        // Nothing to draw into until the canvas has an area
+        if !self.graphics.is_configured() {
+            return Ok(());
+        }
 
         let mut encoder =
             self.graphics
@@ -96,143 +1022,1646 @@ impl AppState {
                 });
 
         // Run compute pass if not paused
-        if !self.paused {
-            self.simulation.compute_pass(&mut encoder);
-        }
-
-        // Run render pass
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.01,
-                            g: 0.01,
-                            b: 0.05,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.graphics.depth_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
+        if self.physics_running() {
+            self.simulation
+                .compute_pass(&self.graphics.queue, &mut encoder);
+            self.simulation.orbit_pass(&mut encoder);
+            #[cfg(debug_assertions)]
+            self.simulation.desync_pass(&mut encoder);
+            self.simulation.record_replay(&mut encoder);
+        }
+        self.simulation
+            .reduction_pass(&self.graphics.queue, &mut encoder);
+
+        // Compute only: submit the physics and leave the last frame on screen
+        if self.render_paused {
+            self.graphics
+                .queue
+                .submit(std::iter::once(encoder.finish()));
+            self.simulation.after_submit();
+            return Ok(());
+        }
+
+        let frame = self
+            .graphics
+            .surface
+            .get_current_texture()
+            .map_err(|e| JsValue::from_str(&format!("Failed to get surface texture: {e:?}")))?;
+
+        let surface_view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        // With a fixed internal resolution everything up to the upscale is drawn
+        // at that size
+        let (target, depth_view) = match &self.internal {
+            Some(internal) => (internal.color_view(), internal.depth_view()),
+            None => (&surface_view, &self.graphics.depth_view),
+        };
+        let hover = self
+            .input_handler
+            .hover_position()
+            .and_then(|cursor| match &self.internal {
+                Some(internal) => internal.to_internal(self.graphics.size, cursor),
+                None => Some(cursor),
             });
+        if let (true, Some(cursor)) = (self.hover_highlight, hover) {
+            self.simulation.pick_pass(
+                &self.graphics.queue,
+                &mut encoder,
+                cursor,
+                self.render_size(),
+            );
+        }
 
-            // Update camera uniforms before rendering
-            self.simulation
-                .update_camera(&self.graphics.queue, &self.camera);
-            self.simulation.render_pass(&mut render_pass);
+        if self.camera.advance_turntable() {
+            console_log!("🎠 Turntable finished");
+        }
+
+        // Update camera uniforms before rendering
+        self.renderer.begin_taa(
+            &self.graphics.queue,
+            &mut self.camera,
+            self.simulation.restarts(),
+        );
+        self.simulation
+            .update_camera(&self.graphics.queue, &self.camera);
+        self.horizon.update(&self.graphics.queue, &self.camera);
+        self.renderer.update_markers(
+            &self.graphics.queue,
+            &self.camera,
+            &self.simulation.attractors(),
+        );
+        self.renderer
+            .update_depth_of_field(&self.graphics.queue, &self.camera);
+
+        self.renderer.render(
+            &mut encoder,
+            &self.simulation,
+            &self.horizon,
+            (target, depth_view),
+            self.clear_color(),
+        );
+        if let Some(internal) = &self.internal {
+            internal.blit(
+                &mut encoder,
+                &surface_view,
+                self.graphics.size,
+                self.clear_color(),
+            );
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.flush();
+            recorder.capture(
+                &self.graphics.device,
+                &mut encoder,
+                &frame.texture,
+                self.last_time as f64,
+            );
         }
 
         self.graphics
             .queue
             .submit(std::iter::once(encoder.finish()));
+        self.simulation.after_submit();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.after_submit();
+        }
         frame.present();
 
         Ok(())
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) {
-        self.graphics.resize(width, height);
-        self.camera.set_aspect_ratio(width as f32 / height as f32);
+    // Pause or resume both the physics and the rendering
+    pub fn set_paused(&mut self, paused: bool) {
+        self.set_physics_paused(paused);
+        self.set_render_paused(paused);
     }
-}
 
-// Global state wrapped in Rc<RefCell<>> for sharing between closures
-static mut APP_STATE: Option<Rc<RefCell<AppState>>> = None;
+    pub fn is_paused(&self) -> bool {
+        self.physics_paused && self.render_paused
+    }
 
-#[wasm_bindgen(start)]
-pub fn start() -> Result<(), JsValue> {
-    set_panic_hook();
+    pub fn set_physics_paused(&mut self, paused: bool) {
+        self.physics_paused = paused;
+        // Resuming always returns to the live state
+        if !paused {
+            self.simulation.scrub(None);
+        }
+    }
+
+    // For profiling the compute work on its own
+    pub fn set_render_paused(&mut self, paused: bool) {
+        self.render_paused = paused;
+    }
 
-    // Initialize logging
-    #[cfg(target_arch = "wasm32")]
-    console_log::init_with_level(log::Level::Info).unwrap();
+    // Keep the last `length` frames for scrubbing; returns the length actually used
+    pub fn set_replay_length(&mut self, length: u32) -> u32 {
+        self.simulation
+            .set_replay_length(&self.graphics.device, length)
+    }
+
+    // Pause and show a recorded frame, 0 being the oldest
+    pub fn scrub(&mut self, frame: u32) {
+        self.physics_paused = true;
+        self.simulation.scrub(Some(frame));
+    }
+
+    pub fn replay_frames(&self) -> u32 {
+        self.simulation.replay_frames()
+    }
+
+    pub fn set_horizon_visible(&mut self, visible: bool) {
+        self.horizon.set_visible(visible);
+    }
 
-    console_log!("Starting Black Hole Simulation...");
+    pub fn set_horizon_ring(&mut self, width: f32, intensity: f32) {
+        self.horizon.set_ring(width, intensity);
+    }
 
-    spawn_local(async {
-        if let Err(e) = run().await {
-            console_log!("Error running application: {:?}", e);
+    // Cut away particles on the side of the plane the normal points to
+    pub fn set_clip_plane(
+        &mut self,
+        nx: f32,
+        ny: f32,
+        nz: f32,
+        distance: f32,
+    ) -> Result<(), JsValue> {
+        let length = (nx * nx + ny * ny + nz * nz).sqrt();
+        if !length.is_finite() || length == 0.0 || !distance.is_finite() {
+            return Err(JsValue::from_str(
+                "Clip plane needs a finite, non-zero normal and a finite distance",
+            ));
         }
-    });
 
-    Ok(())
-}
+        let normal = [nx / length, ny / length, nz / length];
+        self.simulation
+            .set_clip_plane(&self.graphics.queue, normal, distance);
+        Ok(())
+    }
 
-async fn run() -> Result<(), JsValue> {
-    let window = web_sys::window().unwrap();
-    let document = window.document().unwrap();
+    // Edge darkening in 0..1 starting `radius` half-heights from the center
+    pub fn set_vignette(&mut self, strength: f32, radius: f32) {
+        self.renderer
+            .set_vignette(&self.graphics.queue, strength, radius);
+    }
 
-    let canvas = document
-        .get_element_by_id("gpu-canvas")
-        .unwrap()
-        .dyn_into::<web_sys::HtmlCanvasElement>()?;
+    // Lift black to `ambient_floor` (0..0.5) after a display `gamma`, for
+    // dark-room viewing; 0 and 1 leave the frame as drawn
+    pub fn set_display_adjustment(&mut self, ambient_floor: f32, gamma: f32) {
+        self.renderer
+            .set_composite(&self.graphics.queue, ambient_floor, gamma);
+    }
 
-    // Set canvas size
-    let width = 1024u32;
-    let height = 768u32;
-    canvas.set_width(width);
-    canvas.set_height(height);
-    canvas.style().set_property("width", "100vw")?;
-    canvas.style().set_property("height", "100vh")?;
+    // Dither the final output by `strength` 8-bit steps against banding
+    pub fn set_dither_strength(&mut self, strength: f32) {
+        self.renderer
+            .set_dither_strength(&self.graphics.queue, strength);
+    }
 
-    // Initialize application state
-    let app_state = AppState::new(canvas.clone()).await?;
-    let app_state_rc = Rc::new(RefCell::new(app_state));
+    // Temporal anti-aliasing of the particle view; converges while the camera
+    // is still and starts over when it moves
+    pub fn set_taa(&mut self, enabled: bool) {
+        self.renderer.set_taa(enabled);
+    }
 
-    // Set up input handlers
-    {
-        let mut app_state_borrow = app_state_rc.borrow_mut();
-        app_state_borrow
-            .input_handler
-            .setup_event_listeners(canvas)?;
+    pub fn set_attractor_markers(&mut self, enabled: bool) {
+        self.renderer.set_attractor_markers(enabled);
     }
 
-    // Store global state for animation loop
-    unsafe {
-        APP_STATE = Some(app_state_rc.clone());
+    // Blur by depth: `aperture` pixels of blur far from the focus (0 turns it
+    // off), focused `focus_distance` from the eye or, if `None`, on the black hole
+    pub fn set_depth_of_field(&mut self, aperture: f32, focus_distance: Option<f32>) {
+        self.renderer.set_depth_of_field(aperture, focus_distance);
     }
 
-    // Start the render loop
-    request_animation_frame();
+    // Debug lines along each sampled particle's velocity, `scale` world units
+    // long per unit of speed
+    pub fn set_velocity_vectors(&mut self, enabled: bool, scale: f32) {
+        self.renderer
+            .set_velocity_vectors(&self.graphics.queue, enabled, scale);
+    }
 
-    Ok(())
-}
+    // Glow over dense regions in the particle view; 0 turns it off
+    pub fn set_density_glow_strength(&mut self, strength: f32) {
+        self.renderer
+            .set_density_glow_strength(&self.graphics.queue, strength);
+    }
 
-fn request_animation_frame() {
-    let closure = Closure::once_into_js(Box::new(|time: f64| {
-        animation_frame(time as f32);
-    }));
+    // Color of a particle group (0: close stars, 1: inflow stream), shown in group color mode
+    pub fn set_group_color(&mut self, group: u32, r: f32, g: f32, b: f32) -> Result<(), JsValue> {
+        if group as usize >= NUM_GROUPS {
+            return Err(JsValue::from_str(&format!(
+                "Unknown particle group {group}, expected 0 to {}",
+                NUM_GROUPS - 1
+            )));
+        }
+        self.simulation.set_group_color(
+            &self.graphics.queue,
+            group as usize,
+            clamp_color([r, g, b]),
+        );
+        Ok(())
+    }
 
-    web_sys::window()
-        .unwrap()
-        .request_animation_frame(closure.as_ref().unchecked_ref())
-        .unwrap();
-}
-
-fn animation_frame(time: f32) {
-    unsafe {
-        if let Some(Some(app_state)) = (&raw const APP_STATE).as_ref() {
-            let mut app = app_state.borrow_mut();
-            app.update(time);
-            if let Err(e) = app.render() {
-                console_log!("Render error: {:?}", e);
-            }
+    // Draw layer of a particle group: higher layers are drawn over lower ones,
+    // e.g. to keep the hot inner disk on top of the inflow stream
+    pub fn set_group_layer(&mut self, group: u32, layer: u32) -> Result<(), JsValue> {
+        if group as usize >= NUM_GROUPS {
+            return Err(JsValue::from_str(&format!(
+                "Unknown particle group {group}, expected 0 to {}",
+                NUM_GROUPS - 1
+            )));
         }
+        self.simulation
+            .set_group_layer(&self.graphics.queue, group as usize, layer);
+        Ok(())
     }
 
-    // Request next frame
-    request_animation_frame();
+    // Sprite drawn on each particle, as `width` x `height` RGBA8 texels
+    pub fn set_particle_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<(), JsValue> {
+        self.simulation.set_particle_texture(
+            &self.graphics.device,
+            &self.graphics.queue,
+            width,
+            height,
+            rgba,
+        )?;
+        console_log!("✨ Particle sprite set to {}x{}", width, height);
+        Ok(())
+    }
+
+    // Speed, acceleration, temperature and radial band colors are looked up
+    // in this palette of RGBA8 entries, from low to high
+    pub fn set_colormap(&mut self, rgba: &[u8]) -> Result<(), JsValue> {
+        self.simulation
+            .set_colormap(&self.graphics.device, &self.graphics.queue, rgba)?;
+        console_log!("🎨 Colormap set to {} entries", rgba.len() / 4);
+        Ok(())
+    }
+
+    // As `set_colormap`, with a palette listed by `available_colormaps`
+    pub fn set_builtin_colormap(&mut self, name: &str) -> Result<(), JsValue> {
+        self.simulation
+            .set_named_colormap(&self.graphics.device, &self.graphics.queue, name)?;
+        console_log!("🎨 Colormap: {}", name);
+        Ok(())
+    }
+
+    // Return to the color modes' own palettes
+    pub fn clear_colormap(&mut self) {
+        self.simulation.clear_colormap(&self.graphics.queue);
+    }
+
+    // Side of each particle sprite in pixels
+    pub fn set_sprite_size(&mut self, size: f32) {
+        self.simulation.set_sprite_size(&self.graphics.queue, size);
+    }
+
+    pub fn set_size_attenuation(&mut self, enabled: bool) {
+        self.simulation
+            .set_size_attenuation(&self.graphics.queue, enabled);
+    }
+
+    // Skip particles smaller than `size` pixels on screen when zoomed out
+    pub fn set_min_screen_size(&mut self, size: f32) {
+        self.simulation
+            .set_min_screen_size(&self.graphics.queue, size);
+    }
+
+    pub fn set_radial_bands(&mut self, bands: f32) {
+        self.simulation
+            .set_radial_bands(&self.graphics.queue, bands);
+    }
+
+    // Particle opacity in 0..1
+    pub fn set_particle_alpha(&mut self, alpha: f32) {
+        self.simulation
+            .set_particle_alpha(&self.graphics.queue, alpha);
+    }
+
+    // Emission boost within `radius` of the center; a strength of 0 turns it off
+    pub fn set_inner_glow(
+        &mut self,
+        radius: f32,
+        strength: f32,
+        falloff: f32,
+        r: f32,
+        g: f32,
+        b: f32,
+    ) {
+        self.simulation.set_inner_glow(
+            &self.graphics.queue,
+            radius,
+            strength,
+            falloff,
+            clamp_color([r, g, b]),
+        );
+    }
+
+    // Exponential distance fog; a density of 0 turns it off
+    pub fn set_fog(&mut self, density: f32, r: f32, g: f32, b: f32) {
+        let color = clamp_color([r, g, b]);
+        self.fog_density = density.max(0.0);
+        self.fog_follows_background = false;
+        self.simulation
+            .set_fog(&self.graphics.queue, self.fog_density, color);
+    }
+
+    // Clear color, with components clamped to [0, 1]
+    pub fn set_background(&mut self, r: f32, g: f32, b: f32) {
+        self.background = clamp_color([r, g, b]);
+        if self.fog_follows_background {
+            self.simulation
+                .set_fog(&self.graphics.queue, self.fog_density, self.background);
+        }
+    }
+
+    // Let the page show through the canvas behind the particles. Returns false
+    // if the browser only supports an opaque canvas.
+    pub fn set_transparent_background(&mut self, transparent: bool) -> bool {
+        let applied = self.graphics.set_transparent(transparent);
+        console_log!(
+            "🪟 Canvas background: {}",
+            if applied { "transparent" } else { "opaque" }
+        );
+        applied == transparent
+    }
+
+    pub fn set_clip_enabled(&mut self, enabled: bool) {
+        self.simulation
+            .set_clip_enabled(&self.graphics.queue, enabled);
+    }
+
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.simulation.set_integrator(integrator);
+    }
+
+    // Restart and run the same initial conditions under two integrators, overlaid
+    pub fn enable_ab_compare(&mut self, integrator_a: Integrator, integrator_b: Integrator) {
+        self.simulation.enable_ab_compare(
+            &self.graphics.device,
+            &self.graphics.queue,
+            integrator_a,
+            integrator_b,
+        );
+    }
+
+    pub fn disable_ab_compare(&mut self) {
+        self.simulation.disable_ab_compare(&self.graphics.queue);
+    }
+
+    // Step the particles on the CPU instead of in a compute shader; restarts
+    // the simulation when turned on
+    pub fn set_cpu_physics(&mut self, enabled: bool) {
+        self.simulation
+            .set_cpu_physics(&self.graphics.queue, enabled);
+    }
+
+    // Dial the simulated and drawn particle count within the allocated buffer;
+    // returns the count actually used
+    pub fn set_active_particles(&mut self, count: u32) -> u32 {
+        self.simulation.set_active_particles(count)
+    }
+
+    pub fn active_particles(&self) -> u32 {
+        self.simulation.active_particle_count()
+    }
+
+    // Build the disk up from nothing by injecting `rate` particles per
+    // simulated second (0 stops injecting)
+    pub fn set_inflow_rate(&mut self, rate: f32) {
+        self.simulation.set_inflow_rate(&self.graphics.queue, rate);
+    }
+
+    pub fn set_max_speed(&mut self, max_speed: f32) {
+        self.simulation.set_max_speed(max_speed);
+    }
+
+    pub fn set_warp(&mut self, amplitude: f32, precession_rate: f32) {
+        self.simulation.set_warp(amplitude, precession_rate);
+    }
+
+    // Secondary mass (a gravitational parameter like `gm`) orbiting the black
+    // hole in the disk plane, from periapsis on +X at the current time; its
+    // pull raises wakes and opens a gap. Replaces any previous one.
+    pub fn add_orbiting_mass(
+        &mut self,
+        mass: f32,
+        semi_major: f32,
+        eccentricity: f32,
+    ) -> Result<(), JsValue> {
+        if !(mass.is_finite() && mass > 0.0) {
+            return Err(JsValue::from_str(&format!(
+                "Orbiting mass must be positive, got {mass}"
+            )));
+        }
+        if !(semi_major.is_finite() && semi_major > 0.0 && semi_major <= physics::BOUNDARY) {
+            return Err(JsValue::from_str(&format!(
+                "Semi-major axis must be in (0, {}], got {semi_major}",
+                physics::BOUNDARY
+            )));
+        }
+        if !(0.0..=MAX_ECCENTRICITY).contains(&eccentricity) {
+            return Err(JsValue::from_str(&format!(
+                "Eccentricity must be in [0, {MAX_ECCENTRICITY}], got {eccentricity}"
+            )));
+        }
+        self.simulation.set_orbiting_mass(Some(OrbitingMass {
+            gm: mass,
+            semi_major,
+            eccentricity,
+        }));
+        console_log!(
+            "🪐 Orbiting mass {} at a = {}, e = {}",
+            mass,
+            semi_major,
+            eccentricity
+        );
+        Ok(())
+    }
+
+    pub fn remove_orbiting_mass(&mut self) {
+        self.simulation.set_orbiting_mass(None);
+    }
+
+    pub fn set_wrap(&mut self, enabled: bool, half_x: f32, half_y: f32, half_z: f32) {
+        self.simulation.set_wrap(enabled, [half_x, half_y, half_z]);
+    }
+
+    pub fn set_temperature_rates(&mut self, heating: f32, cooling: f32) {
+        self.simulation.set_temperature_rates(heating, cooling);
+    }
+
+    // Absorb particles that come closer to the center than `radius` (0 disables absorption)
+    pub fn set_absorb_radius(&mut self, radius: f32) {
+        self.simulation.set_absorb_radius(radius);
+    }
+
+    // Soften the central mass's gravity over `softening`, capping the pull on
+    // close passes (0 for none)
+    pub fn set_softening(&mut self, softening: f32) {
+        self.simulation.set_softening(softening);
+    }
+
+    // Send absorbed particles back onto a circular orbit at `radius` instead
+    // of removing them (0 removes them)
+    pub fn set_respawn_radius(&mut self, radius: f32) {
+        self.simulation.set_respawn_radius(radius);
+    }
+
+    // One switch for robust dynamics: the symplectic integrator on small fixed
+    // steps, real-time speed, the default speed clamp, softened gravity, and
+    // absorption at the horizon so close passes are swallowed instead of
+    // slingshotting, with the swallowed particles respawned further out
+    pub fn apply_stability_preset(&mut self) {
+        let defaults = SimulationConfig::default();
+        self.simulation
+            .set_integrator(Integrator::SemiImplicitEuler);
+        self.simulation
+            .set_fixed_timestep(&self.graphics.queue, Some(STABLE_TIMESTEP));
+        self.simulation.set_substeps(1);
+        self.simulation.set_max_speed(defaults.max_speed);
+        self.simulation.set_absorb_radius(defaults.horizon.radius);
+        self.simulation.set_softening(STABLE_SOFTENING);
+        self.simulation.set_respawn_radius(STABLE_RESPAWN_RADIUS);
+        console_log!("🛡️ Stability preset applied");
+    }
+
+    pub fn set_accretion_flash_enabled(&mut self, enabled: bool) {
+        self.simulation.set_accretion_flash_enabled(enabled);
+    }
+
+    pub fn set_color_mode(&mut self, mode: ColorMode) {
+        self.simulation.set_color_mode(&self.graphics.queue, mode);
+    }
+
+    // Mix two color modes, e.g. 30% of the way from speed to group coloring
+    pub fn set_color_blend(&mut self, mode_a: ColorMode, mode_b: ColorMode, blend: f32) {
+        self.simulation
+            .set_color_blend(&self.graphics.queue, mode_a, mode_b, blend);
+    }
+
+    // Turn the view exactly `revolutions` times around the black hole over the
+    // next `frames` rendered frames, at constant angular velocity. The last
+    // frame leads straight back into the first, so a recording of exactly
+    // those frames loops without a seam.
+    pub fn start_turntable(&mut self, revolutions: u32, frames: u32) -> Result<(), JsValue> {
+        if revolutions == 0 || frames < 2 {
+            return Err(JsValue::from_str(&format!(
+                "A turntable needs at least 1 revolution and 2 frames, got {revolutions} and {frames}"
+            )));
+        }
+        self.camera.start_turntable(revolutions, frames);
+        console_log!(
+            "🎠 Turntable: {} revolution(s) over {} frames",
+            revolutions,
+            frames
+        );
+        Ok(())
+    }
+
+    pub fn stop_turntable(&mut self) {
+        self.camera.stop_turntable();
+    }
+
+    pub fn turntable_active(&self) -> bool {
+        self.camera.turntable_active()
+    }
+
+    // Development only: periodically check that the render pass draws the
+    // particle buffer the compute pass writes, warning in the console if not.
+    // On by default in debug builds.
+    #[cfg(debug_assertions)]
+    pub fn set_desync_check(&mut self, enabled: bool) {
+        self.simulation.set_desync_check(enabled);
+    }
+
+    // Development only: rebuild the particle pipelines from new shader sources
+    // (e.g. fetched from the dev server) and swap them in. Compile and validation
+    // errors are returned instead of replacing the working pipelines.
+    #[cfg(debug_assertions)]
+    pub async fn reload_shaders(
+        state: &Rc<RefCell<Self>>,
+        compute_src: &str,
+        render_src: &str,
+    ) -> Result<(), JsValue> {
+        let (pipelines, scopes) = {
+            let app = state.borrow();
+            let mut scopes = ErrorScopes::new();
+            let pipelines = app.simulation.build_pipelines(
+                &app.graphics.device,
+                app.graphics.config.format,
+                compute_src,
+                render_src,
+                &mut scopes,
+            );
+            (pipelines, scopes)
+        };
+
+        if let Err(error) = scopes.finish().await {
+            return Err(JsValue::from_str(&format!("Shader reload failed: {error}")));
+        }
+
+        state.borrow_mut().simulation.set_pipelines(pipelines);
+        console_log!("🔄 Shaders reloaded");
+        Ok(())
+    }
+
+    // CSV of id, position and velocity for the live particles, read back from the
+    // GPU. With `max_rows` set, larger sets are thinned to every n-th particle.
+    // Absorbed particles are left out.
+    pub async fn export_particles_csv(
+        state: &Rc<RefCell<Self>>,
+        max_rows: Option<u32>,
+    ) -> Result<String, JsValue> {
+        let (device, queue, buffer, count) = {
+            let app = state.borrow();
+            let (buffer, count) = app.simulation.live_particles();
+            (
+                app.graphics.device.clone(),
+                app.graphics.queue.clone(),
+                buffer.clone(),
+                count,
+            )
+        };
+
+        let stride = match max_rows {
+            Some(max_rows) if max_rows > 0 => count.div_ceil(max_rows).max(1),
+            _ => 1,
+        };
+        let rows = count.div_ceil(stride);
+        if rows > CSV_EXPORT_WARN_ROWS {
+            console_log!(
+                "⚠️ Exporting {} particles (~{} MB of CSV); pass max_rows to sample a subset",
+                rows,
+                rows as u64 * 80 / (1024 * 1024)
+            );
+        }
+
+        let size = count as u64 * std::mem::size_of::<simulation::Particle>() as u64;
+        let data = readback::read_buffer(&device, &queue, &buffer, size).await?;
+        let particles: &[simulation::Particle] = bytemuck::cast_slice(&data);
+
+        let mut csv = String::with_capacity(rows as usize * 80 + 32);
+        csv.push_str("id,x,y,z,vx,vy,vz\n");
+        let mut written = 0;
+        for (id, particle) in particles.iter().enumerate().step_by(stride as usize) {
+            if particle.flags & simulation::FLAG_ABSORBED != 0 {
+                continue;
+            }
+            let [x, y, z] = particle.position;
+            let [vx, vy, vz] = particle.velocity;
+            // Writing into a String cannot fail
+            let _ = writeln!(csv, "{id},{x},{y},{z},{vx},{vy},{vz}");
+            written += 1;
+        }
+
+        console_log!("📄 Exported {} particles as CSV", written);
+        Ok(csv)
+    }
+
+    // RGBA bytes of the current state drawn at `width` x `height` into a
+    // target of its own, for previews. Only the particles and the horizon are
+    // drawn: no glow, depth of field, vignette or display adjustment. The live
+    // view is left as it was.
+    pub async fn render_thumbnail(
+        state: &Rc<RefCell<Self>>,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, JsValue> {
+        let target = {
+            let mut app = state.borrow_mut();
+            let app = &mut *app;
+            let target = ThumbnailTarget::new(
+                &app.graphics.device,
+                app.graphics.config.format,
+                (width, height),
+            )?;
+            app.draw_thumbnail(&target);
+            target
+        };
+        let data = target.read().await?;
+        console_log!("🖼️ Rendered a {}x{} thumbnail", width, height);
+        Ok(data)
+    }
+
+    fn draw_thumbnail(&mut self, target: &ThumbnailTarget) {
+        let (width, height) = target.size();
+        self.camera.set_jitter([0.0, 0.0]);
+        self.simulation
+            .set_viewport(&self.graphics.queue, width, height);
+        self.camera.set_aspect_ratio(width as f32 / height as f32);
+        self.simulation
+            .update_camera(&self.graphics.queue, &self.camera);
+        self.horizon.update(&self.graphics.queue, &self.camera);
+
+        let mut encoder =
+            self.graphics
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Thumbnail Encoder"),
+                });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Thumbnail Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.color_view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color()),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: target.depth_view(),
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        self.simulation.render_pass(&mut render_pass);
+        self.horizon.render_pass(&mut render_pass);
+        drop(render_pass);
+        target.copy(&mut encoder);
+        self.graphics
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        // Written after the submit, so the thumbnail keeps its own viewport
+        let (width, height) = self.render_size();
+        self.simulation
+            .set_viewport(&self.graphics.queue, width, height);
+        self.camera.set_aspect_ratio(width as f32 / height as f32);
+        self.simulation
+            .update_camera(&self.graphics.queue, &self.camera);
+        self.horizon.update(&self.graphics.queue, &self.camera);
+    }
+
+    // Draw one particle highlighted, or none. With hover highlighting on, the
+    // next pick under the cursor replaces it.
+    pub fn set_highlight(&mut self, index: Option<u32>) {
+        self.highlight = index;
+        self.simulation.set_highlight(&self.graphics.queue, index);
+    }
+
+    pub fn set_hover_highlight(&mut self, enabled: bool) {
+        self.hover_highlight = enabled;
+        if !enabled {
+            self.set_highlight(None);
+        }
+    }
+
+    pub fn set_camera_mode(&mut self, mode: CameraMode) {
+        self.camera.set_mode(mode);
+    }
+
+    // What dragging with `button` (0 left, 1 middle, 2 right) does; by default
+    // left rotates, right pans and middle does nothing
+    pub fn set_mouse_button_action(
+        &mut self,
+        button: u32,
+        action: MouseAction,
+    ) -> Result<(), JsValue> {
+        self.input_handler.set_button_action(button, action)
+    }
+
+    // Non-linear drag response for rotating and panning: the view moves as the
+    // drag speed to the power `exponent`, so above 1 slow drags make fine
+    // adjustments and fast ones sweep further, at any frame rate. 1 is linear.
+    pub fn set_rotate_curve_exponent(&mut self, exponent: f32) {
+        self.input_handler.set_rotate_curve_exponent(exponent);
+    }
+
+    // Whether right-clicking the canvas is kept from opening the browser menu
+    pub fn set_suppress_context_menu(&mut self, suppress: bool) {
+        self.input_handler.set_suppress_context_menu(suppress);
+    }
+
+    pub fn camera_orientation(&self) -> [f32; 4] {
+        self.camera.orientation()
+    }
+
+    pub fn set_camera_orientation(&mut self, orientation: [f32; 4]) {
+        self.camera.set_orientation(orientation);
+    }
+
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        self.renderer.set_mode(mode);
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.graphics.resize(width, height);
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.apply_render_size();
+    }
+
+    // Size everything up to the upscale is drawn at
+    fn render_size(&self) -> (u32, u32) {
+        self.internal
+            .as_ref()
+            .map_or(self.graphics.size, InternalTarget::size)
+    }
+
+    // Resize the render targets, viewport and camera aspect to `render_size`
+    fn apply_render_size(&mut self) {
+        let (width, height) = self.render_size();
+        let depth_view = match &self.internal {
+            Some(internal) => internal.depth_view(),
+            None => &self.graphics.depth_view,
+        };
+        self.renderer.resize(
+            &self.graphics.device,
+            &self.graphics.queue,
+            width,
+            height,
+            depth_view,
+        );
+        self.simulation
+            .set_viewport(&self.graphics.queue, width, height);
+        self.camera.set_aspect_ratio(width as f32 / height as f32);
+    }
+
+    // Render at a fixed `(width, height)` and upscale to the canvas with
+    // nearest-neighbor filtering, for a pixelated look or to go easier on
+    // weak GPUs; `None` renders at the canvas size again
+    pub fn set_internal_resolution(&mut self, size: Option<(u32, u32)>) -> Result<(), JsValue> {
+        self.internal = match size {
+            Some(size) => Some(InternalTarget::new(
+                &self.graphics.device,
+                self.graphics.config.format,
+                size,
+            )?),
+            None => None,
+        };
+        self.apply_render_size();
+        Ok(())
+    }
+
+    fn clear_color(&self) -> wgpu::Color {
+        if self.graphics.is_transparent() {
+            wgpu::Color::TRANSPARENT
+        } else {
+            wgpu::Color {
+                r: self.background[0] as f64,
+                g: self.background[1] as f64,
+                b: self.background[2] as f64,
+                a: 1.0,
+            }
+        }
+    }
+}
+
+// Number of recent inputs averaged by `input_latency_ms`
+const INPUT_LATENCY_WINDOW: usize = 30;
+
+// Parameters `animate_param` accepts; each has an entry in ranges.rs
+const ANIMATED_PARAMS: [&str; 10] = [
+    "gm",
+    "absorb_radius",
+    "heating_rate",
+    "cooling_rate",
+    "warp_amplitude",
+    "precession_rate",
+    "particle_alpha",
+    "sprite_size",
+    "min_screen_size",
+    "radial_bands",
+];
+
+// Frames counted by `frame_time_histogram`, about five seconds at 60fps
+const FRAME_TIME_WINDOW: usize = 300;
+
+// Upper bounds of the frame time histogram buckets (ms), a little above the
+// 120, 60 and 30 Hz frame periods so timestamp jitter does not split a
+// steady rate across two buckets
+const FRAME_TIME_BUCKETS_MS: [f32; 3] = [8.5, 17.0, 34.0];
+
+// Particles simulated when the physics falls back to the CPU
+const CPU_FALLBACK_PARTICLES: u32 = 16384;
+
+// Physics step of the stability preset, in seconds
+const STABLE_TIMESTEP: f32 = 1.0 / 120.0;
+// Gravity softening length of the stability preset
+const STABLE_SOFTENING: f32 = 2.0;
+// Where the stability preset respawns absorbed particles, out past the disk
+const STABLE_RESPAWN_RADIUS: f32 = 250.0;
+// Step length of science mode, a power of two so simulated time sums exactly
+const SCIENCE_TIMESTEP: f32 = 1.0 / 128.0;
+// Physics speed under reduced motion
+const REDUCED_MOTION_TIME_SCALE: f32 = 0.25;
+
+// Values per particle accepted by `load_particles`: the position, group and
+// velocity words of `Particle`, which also carries GPU-computed acceleration
+const PARTICLE_FLOATS: usize = 8;
+
+// Larger CSV exports log a size warning
+const CSV_EXPORT_WARN_ROWS: u32 = 50_000;
+
+// Slack allowed when deciding whether a capped frame is due
+const FRAME_CAP_TOLERANCE_MS: f64 = 1.0;
+
+// Canvas used by the automatic start-up when no id is given
+const DEFAULT_CANVAS_ID: &str = "gpu-canvas";
+
+// Runs when the module is initialised. Pages without the default canvas create
+// their simulations explicitly, so its absence is not an error here.
+#[wasm_bindgen(start)]
+pub fn start() -> Result<(), JsValue> {
+    let has_default_canvas = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id(DEFAULT_CANVAS_ID))
+        .is_some();
+    if !has_default_canvas {
+        return Ok(());
+    }
+
+    start_on(DEFAULT_CANVAS_ID)
+}
+
+// Start the simulation on the canvas element with the given id. The simulation
+// keeps running on its own; use `create_simulation` to get a handle to it.
+#[wasm_bindgen]
+pub fn start_on(canvas_id: &str) -> Result<(), JsValue> {
+    init_runtime();
+
+    let canvas = find_canvas(canvas_id)?;
+
+    console_log!("Starting Black Hole Simulation on #{}...", canvas_id);
+
+    let canvas_id = canvas_id.to_string();
+    spawn_local(async move {
+        if let Err(e) = SimulationHandle::create(canvas).await {
+            let reason = e.as_string().unwrap_or_else(|| format!("{e:?}"));
+            console_log!(
+                "❌ Simulation on #{} failed to start: {}",
+                canvas_id,
+                reason
+            );
+        }
+    });
+
+    Ok(())
+}
+
+// Create an independent simulation on the canvas with the given id
+#[wasm_bindgen]
+pub async fn create_simulation(canvas_id: String) -> Result<SimulationHandle, JsValue> {
+    init_runtime();
+
+    let canvas = find_canvas(&canvas_id)?;
+    SimulationHandle::create(canvas).await
+}
+
+// Feature detection for host pages: resolves to `{ supported, compute_supported,
+// adapter_name }` without creating a surface or device. `adapter_name` is null
+// when the browser does not report one.
+#[wasm_bindgen]
+pub async fn check_webgpu_support() -> JsValue {
+    let report = graphics::probe_support().await;
+    console_log!(
+        "🔍 WebGPU supported: {}, compute: {}",
+        report.supported,
+        report.compute_supported
+    );
+
+    let result = js_sys::Object::new();
+    let adapter_name = report
+        .adapter_name
+        .map_or(JsValue::NULL, |name| JsValue::from_str(&name));
+    // Setting a key on a fresh object cannot fail
+    let _ = js_sys::Reflect::set(&result, &"supported".into(), &report.supported.into());
+    let _ = js_sys::Reflect::set(
+        &result,
+        &"compute_supported".into(),
+        &report.compute_supported.into(),
+    );
+    let _ = js_sys::Reflect::set(&result, &"adapter_name".into(), &adapter_name);
+    result.into()
+}
+
+// Initial distributions accepted by `set_distribution`, as `{ name, description }` objects
+#[wasm_bindgen]
+pub fn available_distributions() -> Vec<JsValue> {
+    DISTRIBUTIONS
+        .iter()
+        .map(|(name, description)| {
+            let entry = js_sys::Object::new();
+            // Setting a string key on a fresh object cannot fail
+            let _ = js_sys::Reflect::set(&entry, &"name".into(), &(*name).into());
+            let _ = js_sys::Reflect::set(&entry, &"description".into(), &(*description).into());
+            entry.into()
+        })
+        .collect()
+}
+
+// Palettes accepted by `set_builtin_colormap`
+#[wasm_bindgen]
+pub fn available_colormaps() -> Vec<JsValue> {
+    colormap::COLORMAPS
+        .iter()
+        .map(|(name, _)| (*name).into())
+        .collect()
+}
+
+// Panic hook and logging are process-wide, so only set them up once
+fn init_runtime() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        set_panic_hook();
+
+        // Initialize logging; this only fails if the host already installed a logger
+        #[cfg(target_arch = "wasm32")]
+        if console_log::init_with_level(log::Level::Info).is_err() {
+            console_log!("A logger is already installed, keeping it");
+        }
+    });
+}
+
+fn find_canvas(canvas_id: &str) -> Result<web_sys::HtmlCanvasElement, JsValue> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| JsValue::from_str("No document available to look up the canvas"))?;
+
+    let element = document
+        .get_element_by_id(canvas_id)
+        .ok_or_else(|| JsValue::from_str(&format!("Canvas #{canvas_id} not found")))?;
+
+    element
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|_| JsValue::from_str(&format!("Element #{canvas_id} is not a <canvas> element")))
+}
+
+type FrameCallback = Closure<dyn FnMut(f64)>;
+
+// A running simulation owned by JS. Each handle has its own state, input
+// listeners and animation loop; the loop runs until `stop` is called.
+#[wasm_bindgen]
+pub struct SimulationHandle {
+    state: Rc<RefCell<AppState>>,
+    frame_callback: Rc<RefCell<Option<FrameCallback>>>,
+    // Id of the pending requestAnimationFrame, so `stop` can cancel it
+    frame_id: Rc<Cell<i32>>,
+}
+
+impl SimulationHandle {
+    async fn create(canvas: web_sys::HtmlCanvasElement) -> Result<Self, JsValue> {
+        // Size the drawing buffer to the canvas as laid out. A canvas without
+        // area yet (e.g. in a hidden container) starts at zero and configures
+        // its surface once the resize observer sees a nonzero size.
+        let (width, height) = input::display_size(&canvas);
+        canvas.set_width(width);
+        canvas.set_height(height);
+
+        // Initialize application state, with any settings from the page URL
+        let mut config = SimulationConfig::default();
+        query::apply_page_query(&mut config);
+        let mut app_state = AppState::new(canvas.clone(), config).await?;
+
+        // Set up input handlers, scoped to this canvas
+        app_state.input_handler.setup_event_listeners(canvas)?;
+        // Applied now rather than on the first frame, so a host's own
+        // `set_reduced_motion` straight after creation is not overridden
+        app_state.set_reduced_motion(input::prefers_reduced_motion());
+
+        let handle = Self {
+            state: Rc::new(RefCell::new(app_state)),
+            frame_callback: Rc::new(RefCell::new(None)),
+            frame_id: Rc::new(Cell::new(0)),
+        };
+
+        // Start the render loop
+        handle.start_loop()?;
+
+        Ok(handle)
+    }
+
+    fn start_loop(&self) -> Result<(), JsValue> {
+        let state = self.state.clone();
+        let frame_callback = self.frame_callback.clone();
+        let frame_id = self.frame_id.clone();
+
+        *self.frame_callback.borrow_mut() = Some(Closure::new(move |time: f64| {
+            let callbacks = {
+                let mut app = state.borrow_mut();
+                if app.frame_due(time) {
+                    app.update(time as f32);
+                    if let Err(e) = app.render() {
+                        console_log!("Render error: {:?}", e);
+                    }
+                }
+                app.take_pending_callbacks()
+            };
+            // Outside the borrow, so callbacks may call back into the handle
+            for callback in callbacks {
+                callback.call();
+            }
+
+            // Request next frame
+            if let Some(callback) = frame_callback.borrow().as_ref() {
+                match request_animation_frame(callback) {
+                    Ok(id) => frame_id.set(id),
+                    Err(e) => {
+                        console_log!("Failed to request animation frame: {:?}", e);
+                    }
+                }
+            }
+        }));
+
+        if let Some(callback) = self.frame_callback.borrow().as_ref() {
+            self.frame_id.set(request_animation_frame(callback)?);
+        }
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl SimulationHandle {
+    // Stop the animation loop and detach the input listeners
+    pub fn stop(&self) {
+        if let Some(window) = web_sys::window() {
+            let _ = window.cancel_animation_frame(self.frame_id.get());
+        }
+        self.frame_callback.borrow_mut().take();
+        self.state
+            .borrow_mut()
+            .input_handler
+            .remove_event_listeners();
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.state.borrow_mut().set_paused(paused);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.borrow().is_paused()
+    }
+
+    pub fn set_physics_paused(&self, paused: bool) {
+        self.state.borrow_mut().set_physics_paused(paused);
+    }
+
+    pub fn is_physics_paused(&self) -> bool {
+        self.state.borrow().physics_paused
+    }
+
+    pub fn set_render_paused(&self, paused: bool) {
+        self.state.borrow_mut().set_render_paused(paused);
+    }
+
+    pub fn is_render_paused(&self) -> bool {
+        self.state.borrow().render_paused
+    }
+
+    pub fn set_color_mode(&self, mode: ColorMode) {
+        self.state.borrow_mut().set_color_mode(mode);
+    }
+
+    pub fn set_color_blend(&self, mode_a: ColorMode, mode_b: ColorMode, blend: f32) {
+        self.state
+            .borrow_mut()
+            .set_color_blend(mode_a, mode_b, blend);
+    }
+
+    pub fn start_turntable(&self, revolutions: u32, frames: u32) -> Result<(), JsValue> {
+        self.state.borrow_mut().start_turntable(revolutions, frames)
+    }
+
+    pub fn stop_turntable(&self) {
+        self.state.borrow_mut().stop_turntable();
+    }
+
+    pub fn turntable_active(&self) -> bool {
+        self.state.borrow().turntable_active()
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn set_desync_check(&self, enabled: bool) {
+        self.state.borrow_mut().set_desync_check(enabled);
+    }
+
+    // Development only; resolves once the new shaders are in use, rejects with the
+    // compile errors otherwise
+    #[cfg(debug_assertions)]
+    pub fn reload_shaders(&self, compute_src: String, render_src: String) -> js_sys::Promise {
+        let state = self.state.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            AppState::reload_shaders(&state, &compute_src, &render_src).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    // Resolves with the CSV text; see `AppState::export_particles_csv`
+    pub fn export_particles_csv(&self, max_rows: Option<u32>) -> js_sys::Promise {
+        let state = self.state.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let csv = AppState::export_particles_csv(&state, max_rows).await?;
+            Ok(JsValue::from_str(&csv))
+        })
+    }
+
+    // Resolves with a Uint8Array of RGBA rows from the top; see
+    // `AppState::render_thumbnail`
+    pub fn render_thumbnail(&self, width: u32, height: u32) -> js_sys::Promise {
+        let state = self.state.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let data = AppState::render_thumbnail(&state, width, height).await?;
+            Ok(js_sys::Uint8Array::from(data.as_slice()).into())
+        })
+    }
+
+    pub fn set_highlight(&self, index: Option<u32>) {
+        self.state.borrow_mut().set_highlight(index);
+    }
+
+    pub fn set_hover_highlight(&self, enabled: bool) {
+        self.state.borrow_mut().set_hover_highlight(enabled);
+    }
+
+    pub fn set_max_fps(&self, max_fps: Option<f32>) {
+        self.state.borrow_mut().set_max_fps(max_fps);
+    }
+
+    pub fn set_distribution(&self, name: &str) -> Result<(), JsValue> {
+        self.state.borrow_mut().set_distribution(name)
+    }
+
+    pub fn set_spawn_regions(&self, components: JsValue) -> Result<(), JsValue> {
+        self.state.borrow_mut().set_spawn_regions(&components)
+    }
+
+    pub fn set_radial_density_exponent(&self, exponent: f32) {
+        self.state
+            .borrow_mut()
+            .set_radial_density_exponent(exponent);
+    }
+
+    pub fn set_poisson_spacing(&self, spacing: f32) {
+        self.state.borrow_mut().set_poisson_spacing(spacing);
+    }
+
+    pub fn current_params(&self) -> JsValue {
+        self.state.borrow().current_params()
+    }
+
+    pub fn auto_tune(&self) -> JsValue {
+        self.state.borrow_mut().auto_tune()
+    }
+
+    pub fn load_particles(&self, data: &[f32]) -> Result<u32, JsValue> {
+        self.state.borrow_mut().load_particles(data)
+    }
+
+    pub fn set_fixed_timestep(&self, step: Option<f32>) {
+        self.state.borrow_mut().set_fixed_timestep(step);
+    }
+
+    pub fn set_science_mode(&self, enabled: bool) {
+        self.state.borrow_mut().set_science_mode(enabled);
+    }
+
+    // Simulated seconds since science mode was toggled or the clock reset
+    pub fn sim_time(&self) -> f64 {
+        self.state.borrow().sim_time()
+    }
+
+    pub fn reset_sim_time(&self) {
+        self.state.borrow_mut().reset_sim_time();
+    }
+
+    pub fn set_substeps(&self, substeps: u32) {
+        self.state.borrow_mut().set_substeps(substeps);
+    }
+
+    pub fn set_camera_mode(&self, mode: CameraMode) {
+        self.state.borrow_mut().set_camera_mode(mode);
+    }
+
+    pub fn set_mouse_button_action(&self, button: u32, action: MouseAction) -> Result<(), JsValue> {
+        self.state
+            .borrow_mut()
+            .set_mouse_button_action(button, action)
+    }
+
+    pub fn set_rotate_curve_exponent(&self, exponent: f32) {
+        self.state.borrow_mut().set_rotate_curve_exponent(exponent);
+    }
+
+    pub fn set_suppress_context_menu(&self, suppress: bool) {
+        self.state.borrow_mut().set_suppress_context_menu(suppress);
+    }
+
+    pub fn camera_mode(&self) -> CameraMode {
+        self.state.borrow().camera.mode()
+    }
+
+    // Arcball orientation quaternion as [x, y, z, w]
+    pub fn camera_orientation(&self) -> Vec<f32> {
+        self.state.borrow().camera_orientation().to_vec()
+    }
+
+    pub fn set_camera_orientation(&self, x: f32, y: f32, z: f32, w: f32) {
+        self.state.borrow_mut().set_camera_orientation([x, y, z, w]);
+    }
+
+    pub fn set_render_mode(&self, mode: RenderMode) {
+        self.state.borrow_mut().set_render_mode(mode);
+    }
+
+    pub fn set_active_particles(&self, count: u32) -> u32 {
+        self.state.borrow_mut().set_active_particles(count)
+    }
+
+    pub fn set_inflow_rate(&self, rate: f32) {
+        self.state.borrow_mut().set_inflow_rate(rate);
+    }
+
+    pub fn active_particles(&self) -> u32 {
+        self.state.borrow().active_particles()
+    }
+
+    // Non-physical speed clamp for stable visuals; 0 or Infinity disables it
+    pub fn set_max_speed(&self, max_speed: f32) {
+        self.state.borrow_mut().set_max_speed(max_speed);
+    }
+
+    pub fn set_absorb_radius(&self, radius: f32) {
+        self.state.borrow_mut().set_absorb_radius(radius);
+    }
+
+    pub fn set_softening(&self, softening: f32) {
+        self.state.borrow_mut().set_softening(softening);
+    }
+
+    pub fn set_respawn_radius(&self, radius: f32) {
+        self.state.borrow_mut().set_respawn_radius(radius);
+    }
+
+    // Warped, precessing disk: axis tilt in radians and precession in radians
+    // per second near the center; a rate of 0 keeps the disk flat
+    pub fn set_warp(&self, amplitude: f32, precession_rate: f32) {
+        self.state.borrow_mut().set_warp(amplitude, precession_rate);
+    }
+
+    // Perturbing secondary mass on its own orbit; see `AppState::add_orbiting_mass`
+    pub fn add_orbiting_mass(
+        &self,
+        mass: f32,
+        semi_major: f32,
+        eccentricity: f32,
+    ) -> Result<(), JsValue> {
+        self.state
+            .borrow_mut()
+            .add_orbiting_mass(mass, semi_major, eccentricity)
+    }
+
+    pub fn remove_orbiting_mass(&self) {
+        self.state.borrow_mut().remove_orbiting_mass();
+    }
+
+    // Endless flow: particles wrap around a box of the given half-sizes
+    // instead of bouncing off its walls
+    pub fn set_wrap(&self, enabled: bool, half_x: f32, half_y: f32, half_z: f32) {
+        self.state
+            .borrow_mut()
+            .set_wrap(enabled, half_x, half_y, half_z);
+    }
+
+    // Heating per unit of acceleration and cooling rate of the particle
+    // temperature shown by `ColorMode.Temperature`
+    pub fn set_temperature_rates(&self, heating: f32, cooling: f32) {
+        self.state
+            .borrow_mut()
+            .set_temperature_rates(heating, cooling);
+    }
+
+    // Whether offscreen targets can use half-float HDR color on this device
+    pub fn hdr_supported(&self) -> bool {
+        self.state.borrow().graphics.preferred_hdr_format() == wgpu::TextureFormat::Rgba16Float
+    }
+
+    pub fn input_latency_ms(&self) -> Option<f32> {
+        self.state.borrow().input_latency_ms()
+    }
+
+    pub fn accretion_rate(&self) -> f32 {
+        self.state.borrow().accretion_rate()
+    }
+
+    // Ease a named parameter to `target` over `duration` seconds; see
+    // `AppState::animate_param`
+    pub fn animate_param(&self, name: &str, target: f32, duration: f32) -> Result<(), JsValue> {
+        self.state
+            .borrow_mut()
+            .animate_param(name, target, duration)
+    }
+
+    pub fn cancel_animations(&self) {
+        self.state.borrow_mut().cancel_animations();
+    }
+
+    // Frame counts per duration bucket; see `AppState::frame_time_histogram`
+    pub fn frame_time_histogram(&self) -> Vec<u32> {
+        self.state.borrow().frame_time_histogram().to_vec()
+    }
+
+    pub fn parameter_ranges(&self) -> JsValue {
+        self.state.borrow().parameter_ranges()
+    }
+
+    pub fn orbit_stats(&self, index: u32) -> Result<JsValue, JsValue> {
+        self.state.borrow_mut().orbit_stats(index)
+    }
+
+    pub fn stop_orbit_tracking(&self) {
+        self.state.borrow_mut().stop_orbit_tracking();
+    }
+
+    // Both dimensions set a fixed internal resolution, otherwise it is cleared
+    pub fn set_internal_resolution(
+        &self,
+        width: Option<u32>,
+        height: Option<u32>,
+    ) -> Result<(), JsValue> {
+        self.state
+            .borrow_mut()
+            .set_internal_resolution(width.zip(height))
+    }
+
+    pub fn set_reduced_motion(&self, enabled: bool) {
+        self.state.borrow_mut().set_reduced_motion(enabled);
+    }
+
+    pub fn set_auto_rotate(&self, enabled: bool, speed: f32, idle_seconds: f32) {
+        self.state
+            .borrow_mut()
+            .set_auto_rotate(enabled, speed, idle_seconds);
+    }
+
+    pub fn set_error_callback(&self, callback: Option<js_sys::Function>) {
+        self.state.borrow_mut().set_error_callback(callback);
+    }
+
+    pub fn set_steady_state_callback(&self, callback: Option<js_sys::Function>) {
+        self.state.borrow_mut().set_steady_state_callback(callback);
+    }
+
+    pub fn set_steady_state_tolerances(&self, energy: f32, drift: f32, window: f32) {
+        self.state
+            .borrow_mut()
+            .set_steady_state_tolerances(energy, drift, window);
+    }
+
+    pub fn start_recording(&self, fps: f32, seconds: f32) -> Result<(), JsValue> {
+        self.state.borrow_mut().start_recording(fps, seconds)
+    }
+
+    pub fn stop_recording(&self) -> JsValue {
+        self.state.borrow_mut().stop_recording()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.state.borrow().is_recording()
+    }
+
+    pub fn recording_pending(&self) -> u32 {
+        self.state.borrow().recording_pending()
+    }
+
+    pub fn apply_stability_preset(&self) {
+        self.state.borrow_mut().apply_stability_preset();
+    }
+
+    pub fn set_accretion_flash_enabled(&self, enabled: bool) {
+        self.state.borrow_mut().set_accretion_flash_enabled(enabled);
+    }
+
+    pub fn set_horizon_visible(&self, visible: bool) {
+        self.state.borrow_mut().set_horizon_visible(visible);
+    }
+
+    pub fn set_horizon_ring(&self, width: f32, intensity: f32) {
+        self.state.borrow_mut().set_horizon_ring(width, intensity);
+    }
+
+    pub fn set_clip_plane(&self, nx: f32, ny: f32, nz: f32, distance: f32) -> Result<(), JsValue> {
+        self.state.borrow_mut().set_clip_plane(nx, ny, nz, distance)
+    }
+
+    pub fn set_vignette(&self, strength: f32, radius: f32) {
+        self.state.borrow_mut().set_vignette(strength, radius);
+    }
+
+    pub fn set_display_adjustment(&self, ambient_floor: f32, gamma: f32) {
+        self.state
+            .borrow_mut()
+            .set_display_adjustment(ambient_floor, gamma);
+    }
+
+    pub fn set_dither_strength(&self, strength: f32) {
+        self.state.borrow_mut().set_dither_strength(strength);
+    }
+
+    pub fn set_taa(&self, enabled: bool) {
+        self.state.borrow_mut().set_taa(enabled);
+    }
+
+    pub fn set_attractor_markers(&self, enabled: bool) {
+        self.state.borrow_mut().set_attractor_markers(enabled);
+    }
+
+    pub fn set_reactive_value(&self, value: f32) {
+        self.state.borrow_mut().set_reactive_value(value);
+    }
+
+    pub fn set_reactive_mapping(&self, target: ReactiveTarget, base: f32, range: f32) {
+        self.state
+            .borrow_mut()
+            .set_reactive_mapping(target, base, range);
+    }
+
+    pub fn set_depth_of_field(&self, aperture: f32, focus_distance: Option<f32>) {
+        self.state
+            .borrow_mut()
+            .set_depth_of_field(aperture, focus_distance);
+    }
+
+    pub fn set_velocity_vectors(&self, enabled: bool, scale: f32) {
+        self.state.borrow_mut().set_velocity_vectors(enabled, scale);
+    }
+
+    pub fn set_density_glow_strength(&self, strength: f32) {
+        self.state.borrow_mut().set_density_glow_strength(strength);
+    }
+
+    pub fn set_group_color(&self, group: u32, r: f32, g: f32, b: f32) -> Result<(), JsValue> {
+        self.state.borrow_mut().set_group_color(group, r, g, b)
+    }
+
+    pub fn set_group_layer(&self, group: u32, layer: u32) -> Result<(), JsValue> {
+        self.state.borrow_mut().set_group_layer(group, layer)
+    }
+
+    // Replace the soft-dot sprite with RGBA8 pixels, e.g. from
+    // `getImageData(...).data` of a decoded image
+    pub fn set_particle_texture(
+        &self,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<(), JsValue> {
+        self.state
+            .borrow_mut()
+            .set_particle_texture(width, height, rgba)
+    }
+
+    // Palette for the scalar color modes, e.g. 256 entries of a gradient
+    // drawn into a canvas and read back with `getImageData(...).data`
+    pub fn set_colormap(&self, rgba: &[u8]) -> Result<(), JsValue> {
+        self.state.borrow_mut().set_colormap(rgba)
+    }
+
+    pub fn set_builtin_colormap(&self, name: &str) -> Result<(), JsValue> {
+        self.state.borrow_mut().set_builtin_colormap(name)
+    }
+
+    pub fn clear_colormap(&self) {
+        self.state.borrow_mut().clear_colormap();
+    }
+
+    pub fn set_sprite_size(&self, size: f32) {
+        self.state.borrow_mut().set_sprite_size(size);
+    }
+
+    // Perspective sprite sizes (near particles larger) instead of one screen size
+    pub fn set_size_attenuation(&self, enabled: bool) {
+        self.state.borrow_mut().set_size_attenuation(enabled);
+    }
+
+    pub fn set_min_screen_size(&self, size: f32) {
+        self.state.borrow_mut().set_min_screen_size(size);
+    }
+
+    // Rings per doubling of radius shown by `ColorMode::RadialBands`
+    pub fn set_radial_bands(&self, bands: f32) {
+        self.state.borrow_mut().set_radial_bands(bands);
+    }
+
+    pub fn set_particle_alpha(&self, alpha: f32) {
+        self.state.borrow_mut().set_particle_alpha(alpha);
+    }
+
+    pub fn set_inner_glow(&self, radius: f32, strength: f32, falloff: f32, r: f32, g: f32, b: f32) {
+        self.state
+            .borrow_mut()
+            .set_inner_glow(radius, strength, falloff, r, g, b);
+    }
+
+    pub fn set_fog(&self, density: f32, r: f32, g: f32, b: f32) {
+        self.state.borrow_mut().set_fog(density, r, g, b);
+    }
+
+    pub fn set_background(&self, r: f32, g: f32, b: f32) {
+        self.state.borrow_mut().set_background(r, g, b);
+    }
+
+    pub fn set_transparent_background(&self, transparent: bool) -> bool {
+        self.state
+            .borrow_mut()
+            .set_transparent_background(transparent)
+    }
+
+    pub fn set_clip_enabled(&self, enabled: bool) {
+        self.state.borrow_mut().set_clip_enabled(enabled);
+    }
+
+    pub fn set_integrator(&self, integrator: Integrator) {
+        self.state.borrow_mut().set_integrator(integrator);
+    }
+
+    pub fn enable_ab_compare(&self, integrator_a: Integrator, integrator_b: Integrator) {
+        self.state
+            .borrow_mut()
+            .enable_ab_compare(integrator_a, integrator_b);
+    }
+
+    pub fn disable_ab_compare(&self) {
+        self.state.borrow_mut().disable_ab_compare();
+    }
+
+    pub fn set_cpu_physics(&self, enabled: bool) {
+        self.state.borrow_mut().set_cpu_physics(enabled);
+    }
+
+    pub fn is_cpu_physics(&self) -> bool {
+        self.state.borrow().simulation.is_cpu_physics()
+    }
+
+    pub fn set_replay_length(&self, length: u32) -> u32 {
+        self.state.borrow_mut().set_replay_length(length)
+    }
+
+    pub fn scrub(&self, frame: u32) {
+        self.state.borrow_mut().scrub(frame);
+    }
+
+    pub fn replay_frames(&self) -> u32 {
+        self.state.borrow().replay_frames()
+    }
+}
+
+fn request_animation_frame(callback: &FrameCallback) -> Result<i32, JsValue> {
+    web_sys::window()
+        .ok_or_else(|| JsValue::from_str("No window available for requestAnimationFrame"))?
+        .request_animation_frame(callback.as_ref().unchecked_ref())
 }
@@ -0,0 +1,211 @@
+use crate::simulation::{Particle, FLAG_ABSORBED};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Radius samples averaged to smooth out jitter near the extremes
+const SMOOTHING_WINDOW: usize = 5;
+// An extreme counts once the radius has moved this fraction back away from it,
+// so noise around periapsis cannot register a second passage
+const HYSTERESIS: f32 = 0.01;
+// Passages kept for averaging the period
+const MAX_PASSAGES: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrbitStats {
+    pub periapsis: f32,
+    pub apoapsis: f32,
+    // (periapsis + apoapsis) / 2, from the measured extremes
+    pub semi_major_axis: f32,
+    // Mean time between successive periapsis passages, in simulated seconds
+    pub period: Option<f32>,
+    // Kepler's third law for the measured semi-major axis, 2π sqrt(a³ / GM)
+    pub kepler_period: f32,
+    pub passages: u32,
+    pub absorbed: bool,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Heading {
+    Inward,
+    Outward,
+}
+
+// Follows one particle's orbit: its state is read back every frame a readback
+// is not already in flight, and periapsis passages are found as minima of the
+// smoothed radius
+pub struct OrbitTracker {
+    index: u32,
+    staging_buffer: wgpu::Buffer,
+    copy_pending: Option<f64>,
+    in_flight: Arc<AtomicBool>,
+    // Completed readbacks, with the simulated time of the state they hold
+    samples: Arc<Mutex<Vec<(f64, Particle)>>>,
+    recent: Vec<(f64, f32)>,
+    heading: Heading,
+    // Extreme radius and its time since the heading last changed
+    extreme: (f64, f32),
+    // Time of the first smoothed sample; a minimum there is only where
+    // tracking began, not a passage
+    start_time: Option<f64>,
+    periapsis: Option<f32>,
+    apoapsis: Option<f32>,
+    passages: Vec<f64>,
+    absorbed: bool,
+}
+
+impl OrbitTracker {
+    pub fn new(device: &wgpu::Device, index: u32) -> Self {
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Orbit Staging Buffer"),
+            size: std::mem::size_of::<Particle>() as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            index,
+            staging_buffer,
+            copy_pending: None,
+            in_flight: Arc::new(AtomicBool::new(false)),
+            samples: Arc::new(Mutex::new(Vec::new())),
+            recent: Vec::with_capacity(SMOOTHING_WINDOW),
+            heading: Heading::Inward,
+            extreme: (0.0, f32::MAX),
+            start_time: None,
+            periapsis: None,
+            apoapsis: None,
+            passages: Vec::new(),
+            absorbed: false,
+        }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    // Copy the particle's state at simulated time `time` out of `particles`,
+    // unless the previous copy is still being read
+    pub fn encode(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        particles: &wgpu::Buffer,
+        time: f64,
+    ) {
+        self.collect();
+        if self.absorbed || self.in_flight.load(Ordering::Acquire) {
+            return;
+        }
+        let size = std::mem::size_of::<Particle>() as u64;
+        encoder.copy_buffer_to_buffer(
+            particles,
+            self.index as u64 * size,
+            &self.staging_buffer,
+            0,
+            size,
+        );
+        self.copy_pending = Some(time);
+    }
+
+    // Start mapping the staging buffer; must be called after the encoder passed
+    // to `encode` has been submitted
+    pub fn map_results(&mut self) {
+        let Some(time) = self.copy_pending.take() else {
+            return;
+        };
+        self.in_flight.store(true, Ordering::Release);
+
+        let staging = self.staging_buffer.clone();
+        let in_flight = self.in_flight.clone();
+        let samples = self.samples.clone();
+        self.staging_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    let particle = {
+                        let data = staging.slice(..).get_mapped_range();
+                        *bytemuck::from_bytes::<Particle>(&data)
+                    };
+                    staging.unmap();
+                    if let Ok(mut samples) = samples.lock() {
+                        samples.push((time, particle));
+                    }
+                }
+                in_flight.store(false, Ordering::Release);
+            });
+    }
+
+    fn collect(&mut self) {
+        let samples = match self.samples.lock() {
+            Ok(mut samples) => std::mem::take(&mut *samples),
+            Err(_) => return,
+        };
+        for (time, particle) in samples {
+            if particle.flags & FLAG_ABSORBED != 0 {
+                self.absorbed = true;
+                return;
+            }
+            let [x, y, z] = particle.position;
+            self.add_sample(time, (x * x + y * y + z * z).sqrt());
+        }
+    }
+
+    fn add_sample(&mut self, time: f64, radius: f32) {
+        if self.recent.len() == SMOOTHING_WINDOW {
+            self.recent.remove(0);
+        }
+        self.recent.push((time, radius));
+        if self.recent.len() < SMOOTHING_WINDOW {
+            return;
+        }
+        // Centered moving average, attributed to the middle sample's time
+        let smoothed = self.recent.iter().map(|(_, r)| r).sum::<f32>() / SMOOTHING_WINDOW as f32;
+        let time = self.recent[SMOOTHING_WINDOW / 2].0;
+        let start_time = *self.start_time.get_or_insert(time);
+
+        let (extreme_time, extreme) = self.extreme;
+        match self.heading {
+            Heading::Inward if smoothed < extreme => self.extreme = (time, smoothed),
+            Heading::Inward if smoothed > extreme * (1.0 + HYSTERESIS) => {
+                if extreme_time > start_time {
+                    self.periapsis = Some(extreme);
+                    if self.passages.len() == MAX_PASSAGES {
+                        self.passages.remove(0);
+                    }
+                    self.passages.push(extreme_time);
+                }
+                self.heading = Heading::Outward;
+                self.extreme = (time, smoothed);
+            }
+            Heading::Outward if smoothed > extreme => self.extreme = (time, smoothed),
+            Heading::Outward if smoothed < extreme * (1.0 - HYSTERESIS) => {
+                self.apoapsis = Some(extreme);
+                self.heading = Heading::Inward;
+                self.extreme = (time, smoothed);
+            }
+            _ => {}
+        }
+    }
+
+    // Statistics once both a periapsis and an apoapsis have been seen, with the
+    // central mass's gravitational parameter `gm` for the Kepler comparison
+    pub fn stats(&self, gm: f32) -> Option<OrbitStats> {
+        let (periapsis, apoapsis) = (self.periapsis?, self.apoapsis?);
+        let semi_major_axis = (periapsis + apoapsis) * 0.5;
+        let period = match (self.passages.first(), self.passages.last()) {
+            (Some(first), Some(last)) if self.passages.len() > 1 => {
+                Some(((last - first) / (self.passages.len() - 1) as f64) as f32)
+            }
+            _ => None,
+        };
+        Some(OrbitStats {
+            periapsis,
+            apoapsis,
+            semi_major_axis,
+            period,
+            kepler_period: std::f32::consts::TAU
+                * (semi_major_axis.powi(3) / gm.max(f32::EPSILON)).sqrt(),
+            passages: self.passages.len() as u32,
+            absorbed: self.absorbed,
+        })
+    }
+}
@@ -0,0 +1,232 @@
+// Particle physics on the CPU, mirroring `update_particles` in update.wgsl step
+// for step. The constants both sides use live here and are handed to the
+// shader as pipeline overrides, so the two paths cannot disagree on them.
+
+use crate::simulation::{Particle, SimulationParams, FLAG_ABSORBED, FLAG_WRAPPED};
+use std::collections::HashMap;
+
+// Half-size of the box particles bounce inside
+pub const BOUNDARY: f32 = 600.0;
+// Fraction of the velocity kept, reversed, by a bounce off the box
+pub const BOUNCE_DAMPING: f32 = 0.1;
+// Added to r^2 so a particle at the center does not divide by zero
+pub const GRAVITY_EPSILON: f32 = 1e-6;
+// Radius at which the warp precession has slowed to half its central rate
+pub const WARP_RADIUS: f32 = 150.0;
+// Temperatures are capped here so a close pass cannot saturate forever
+pub const MAX_TEMPERATURE: f32 = 100000.0;
+// Softening length of the orbiting secondary mass's gravity
+pub const SECONDARY_SOFTENING: f32 = 5.0;
+// Smallest halo core radius, keeping the halo's pull finite at the center
+pub const MIN_HALO_CORE_RADIUS: f32 = 1.0;
+
+// Values for the `override` declarations in update.wgsl
+pub fn shader_constants() -> HashMap<String, f64> {
+    HashMap::from([
+        ("BOUNDARY".to_string(), BOUNDARY as f64),
+        ("BOUNCE_DAMPING".to_string(), BOUNCE_DAMPING as f64),
+        ("GRAVITY_EPSILON".to_string(), GRAVITY_EPSILON as f64),
+        ("WARP_RADIUS".to_string(), WARP_RADIUS as f64),
+        ("MAX_TEMPERATURE".to_string(), MAX_TEMPERATURE as f64),
+        (
+            "SECONDARY_SOFTENING".to_string(),
+            SECONDARY_SOFTENING as f64,
+        ),
+    ])
+}
+
+fn length(v: [f32; 3]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+fn add_scaled(a: [f32; 3], b: [f32; 3], scale: f32) -> [f32; 3] {
+    [
+        a[0] + b[0] * scale,
+        a[1] + b[1] * scale,
+        a[2] + b[2] * scale,
+    ]
+}
+
+// `SimulationParams::halo` for a halo with asymptotic circular speed `speed`
+// and core radius `core_radius`: the squares of both
+pub fn halo_params(speed: f32, core_radius: f32) -> [f32; 4] {
+    let speed = if speed.is_finite() { speed.abs() } else { 0.0 };
+    let core_radius = if core_radius.is_finite() {
+        core_radius.max(MIN_HALO_CORE_RADIUS)
+    } else {
+        MIN_HALO_CORE_RADIUS
+    };
+    [speed * speed, core_radius * core_radius, 0.0, 0.0]
+}
+
+// Speed of a circular orbit at radius `r` around the central mass inside `halo`
+pub fn circular_speed(r: f32, gm: f32, halo: [f32; 4]) -> f32 {
+    let r2 = r * r;
+    (gm / r + halo[0] * r2 / (r2 + halo[1])).sqrt()
+}
+
+// Acceleration towards the central mass, -GM / r^3 * position with r softened,
+// plus the halo's -v^2 / (r^2 + core^2) * position and the pull of the orbiting
+// secondary mass if there is one
+fn gravity(position: [f32; 3], params: &SimulationParams) -> [f32; 3] {
+    let d2 = position[0] * position[0] + position[1] * position[1] + position[2] * position[2];
+    let softening2 = params.softening * params.softening;
+    let inv_r = 1.0 / (d2 + softening2 + GRAVITY_EPSILON).sqrt();
+    let scale = -params.gm * inv_r * inv_r * inv_r - params.halo[0] / (d2 + params.halo[1]);
+    let central = position.map(|x| x * scale);
+
+    let [x, y, z, gm] = params.secondary;
+    if gm == 0.0 {
+        return central;
+    }
+    let offset = [position[0] - x, position[1] - y, position[2] - z];
+    let r2 = offset[0] * offset[0]
+        + offset[1] * offset[1]
+        + offset[2] * offset[2]
+        + SECONDARY_SOFTENING * SECONDARY_SOFTENING;
+    let inv_r = 1.0 / r2.sqrt();
+    add_scaled(central, offset, -gm * inv_r * inv_r * inv_r)
+}
+
+// Rotate `v` by `angle` around the unit vector `axis` (Rodrigues)
+fn rotate(v: [f32; 3], axis: [f32; 3], angle: f32) -> [f32; 3] {
+    let (sin, cos) = angle.sin_cos();
+    let cross = [
+        axis[1] * v[2] - axis[2] * v[1],
+        axis[2] * v[0] - axis[0] * v[2],
+        axis[0] * v[1] - axis[1] * v[0],
+    ];
+    let along = (axis[0] * v[0] + axis[1] * v[1] + axis[2] * v[2]) * (1.0 - cos);
+    [0, 1, 2].map(|i| v[i] * cos + cross[i] * sin + axis[i] * along)
+}
+
+// Precession around the tilted warp axis, as `warp` in update.wgsl
+fn warp(particle: &mut Particle, params: &SimulationParams) {
+    if params.precession_rate == 0.0 {
+        return;
+    }
+    let axis = [
+        params.warp_amplitude.sin(),
+        params.warp_amplitude.cos(),
+        0.0,
+    ];
+    let r = length(particle.position) / WARP_RADIUS;
+    let angle = params.precession_rate / (1.0 + r * r) * params.dt;
+    particle.position = rotate(particle.position, axis, angle);
+    particle.velocity = rotate(particle.velocity, axis, angle);
+}
+
+// Heating by gravity and exponential cooling, as `heat` in update.wgsl
+fn heat(particle: &mut Particle, params: &SimulationParams) {
+    let gained = params.heating_rate * length(particle.acceleration) * params.dt;
+    let kept = (-params.cooling_rate * params.dt).exp();
+    particle.temperature = (particle.temperature * kept + gained).min(MAX_TEMPERATURE);
+}
+
+fn clamp_speed(velocity: [f32; 3], max_speed: f32) -> [f32; 3] {
+    let speed = length(velocity);
+    if max_speed > 0.0 && speed > max_speed {
+        velocity.map(|v| v * (max_speed / speed))
+    } else {
+        velocity
+    }
+}
+
+// Integer hash (PCG output permutation), identical in update.wgsl
+fn hash(x: u32) -> u32 {
+    let state = x.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+// Start an absorbed particle again on a circular orbit at `respawn_radius` in
+// the XZ plane, at an angle hashed from its index and the frame
+fn respawn(particle: &mut Particle, index: u32, params: &SimulationParams) {
+    let seed = hash(index ^ params.frame.wrapping_mul(2654435769));
+    let angle = (seed >> 8) as f32 / 16777216.0 * std::f32::consts::TAU;
+    let (sin, cos) = angle.sin_cos();
+    let r = params.respawn_radius;
+    let speed = circular_speed(r, params.gm, params.halo);
+    particle.position = [r * cos, 0.0, r * sin];
+    particle.velocity = [-sin * speed, 0.0, cos * speed];
+    particle.acceleration = [0.0; 3];
+    particle.temperature = 0.0;
+}
+
+// Advance particle `index` by `params.dt`
+pub fn step(particle: &mut Particle, index: u32, params: &SimulationParams) {
+    if particle.flags & FLAG_ABSORBED != 0 {
+        return;
+    }
+    let dt = params.dt;
+    particle.flags &= !FLAG_WRAPPED;
+
+    match params.integrator {
+        // Explicit Euler: position advances with the old velocity
+        1 => {
+            let acceleration = gravity(particle.position, params);
+            particle.position = add_scaled(particle.position, particle.velocity, dt);
+            particle.velocity = clamp_speed(
+                add_scaled(particle.velocity, acceleration, dt),
+                params.max_speed,
+            );
+            particle.acceleration = acceleration;
+        }
+        // Leapfrog (kick-drift-kick)
+        2 => {
+            let half_kick = gravity(particle.position, params);
+            particle.velocity = add_scaled(particle.velocity, half_kick, 0.5 * dt);
+            particle.position = add_scaled(particle.position, particle.velocity, dt);
+            let acceleration = gravity(particle.position, params);
+            particle.velocity = clamp_speed(
+                add_scaled(particle.velocity, acceleration, 0.5 * dt),
+                params.max_speed,
+            );
+            particle.acceleration = acceleration;
+        }
+        // Semi-implicit Euler: velocity first, then position with the new velocity
+        _ => {
+            let acceleration = gravity(particle.position, params);
+            particle.velocity = clamp_speed(
+                add_scaled(particle.velocity, acceleration, dt),
+                params.max_speed,
+            );
+            particle.position = add_scaled(particle.position, particle.velocity, dt);
+            particle.acceleration = acceleration;
+        }
+    }
+
+    warp(particle, params);
+    heat(particle, params);
+
+    // Accretion flashes are GPU-only; absorption itself still happens
+    if length(particle.position) < params.absorb_radius {
+        if params.respawn_radius > 0.0 {
+            respawn(particle, index, params);
+        } else {
+            particle.flags |= FLAG_ABSORBED;
+        }
+        return;
+    }
+
+    if params.wrap_enabled != 0 {
+        for axis in 0..3 {
+            let half = params.wrap_bounds[axis];
+            let size = 2.0 * half;
+            let wrapped =
+                particle.position[axis] - size * ((particle.position[axis] + half) / size).floor();
+            if wrapped != particle.position[axis] {
+                particle.position[axis] = wrapped;
+                particle.flags |= FLAG_WRAPPED;
+            }
+        }
+        return;
+    }
+
+    for axis in 0..3 {
+        if particle.position[axis].abs() > BOUNDARY {
+            particle.position[axis] = BOUNDARY.copysign(particle.position[axis]);
+            particle.velocity[axis] = -particle.velocity[axis] * BOUNCE_DAMPING;
+        }
+    }
+}
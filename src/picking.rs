@@ -0,0 +1,233 @@
+use crate::simulation::dispatch_grid;
+use bytemuck::{Pod, Zeroable};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+const PICK_WORKGROUP_SIZE: u32 = 64;
+// Pick at most once every this many frames
+const PICK_INTERVAL: u32 = 6;
+// How far from the cursor a particle can be and still be picked, in pixels
+const PICK_RADIUS: f32 = 8.0;
+// Must match INDEX_BITS in pick.wgsl
+pub const PICK_INDEX_BITS: u32 = 17;
+const NO_HIT: u32 = u32::MAX;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct PickParams {
+    cursor: [f32; 2],
+    viewport: [f32; 2],
+    radius: f32,
+    particle_count: u32,
+    _padding: [u32; 2],
+}
+
+// Finds the particle under the cursor on the GPU. Like the position reduction,
+// only a single word is read back, asynchronously.
+pub struct ParticlePicker {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    result_buffer: wgpu::Buffer,
+    staging_buffer: wgpu::Buffer,
+    max_workgroups_per_dimension: u32,
+    frames_since_run: u32,
+    copy_pending: bool,
+    in_flight: Arc<AtomicBool>,
+    // Completed pick not yet collected: `Some(None)` means nothing was under the cursor
+    latest: Arc<Mutex<Option<Option<u32>>>>,
+}
+
+impl ParticlePicker {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        particle_buffer: &wgpu::Buffer,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Pick Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/pick.wgsl").into()),
+        });
+
+        let buffer_entry = |binding: u32, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Pick Bind Group Layout"),
+            entries: &[
+                buffer_entry(0, wgpu::BufferBindingType::Uniform),
+                buffer_entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+                buffer_entry(2, wgpu::BufferBindingType::Uniform),
+                buffer_entry(3, wgpu::BufferBindingType::Storage { read_only: false }),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pick Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Pick Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("pick"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Params Buffer"),
+            size: std::mem::size_of::<PickParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let result_size = std::mem::size_of::<u32>() as u64;
+        let result_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Result Buffer"),
+            size: result_size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Staging Buffer"),
+            size: result_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Pick Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: result_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            params_buffer,
+            result_buffer,
+            staging_buffer,
+            max_workgroups_per_dimension: device.limits().max_compute_workgroups_per_dimension,
+            frames_since_run: PICK_INTERVAL,
+            copy_pending: false,
+            in_flight: Arc::new(AtomicBool::new(false)),
+            latest: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // Record a pick at `cursor` (canvas pixels) if enough frames have elapsed and
+    // the previous readback has completed
+    pub fn encode(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        cursor: (f32, f32),
+        viewport: (u32, u32),
+        particle_count: u32,
+    ) {
+        self.frames_since_run = self.frames_since_run.saturating_add(1);
+        if self.frames_since_run < PICK_INTERVAL || self.in_flight.load(Ordering::Acquire) {
+            return;
+        }
+        self.frames_since_run = 0;
+
+        let params = PickParams {
+            cursor: [cursor.0, cursor.1],
+            viewport: [viewport.0 as f32, viewport.1 as f32],
+            radius: PICK_RADIUS,
+            particle_count,
+            _padding: [0; 2],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        queue.write_buffer(&self.result_buffer, 0, bytemuck::cast_slice(&[NO_HIT]));
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Pick Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            let (x, y) = dispatch_grid(
+                particle_count.div_ceil(PICK_WORKGROUP_SIZE),
+                self.max_workgroups_per_dimension,
+            );
+            compute_pass.dispatch_workgroups(x, y, 1);
+        }
+
+        encoder.copy_buffer_to_buffer(
+            &self.result_buffer,
+            0,
+            &self.staging_buffer,
+            0,
+            self.staging_buffer.size(),
+        );
+        self.copy_pending = true;
+    }
+
+    // Start mapping the staging buffer; must be called after the encoder passed to
+    // `encode` has been submitted
+    pub fn map_results(&mut self) {
+        if !self.copy_pending {
+            return;
+        }
+        self.copy_pending = false;
+        self.in_flight.store(true, Ordering::Release);
+
+        let staging = self.staging_buffer.clone();
+        let in_flight = self.in_flight.clone();
+        let latest = self.latest.clone();
+        self.staging_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    let key = {
+                        let data = staging.slice(..).get_mapped_range();
+                        *bytemuck::from_bytes::<u32>(&data)
+                    };
+                    staging.unmap();
+                    if let Ok(mut latest) = latest.lock() {
+                        *latest =
+                            Some((key != NO_HIT).then_some(key & ((1 << PICK_INDEX_BITS) - 1)));
+                    }
+                }
+                in_flight.store(false, Ordering::Release);
+            });
+    }
+
+    // The most recent completed pick, if one arrived since the last call
+    pub fn take(&self) -> Option<Option<u32>> {
+        self.latest.lock().ok().and_then(|mut latest| latest.take())
+    }
+}
@@ -0,0 +1,134 @@
+// Poisson-disk sampling after Bridson ("Fast Poisson Disk Sampling in
+// Arbitrary Dimensions", 2007): points no closer than a minimum spacing,
+// filling a region evenly without the clumps of independent random draws
+
+use crate::utils::console_log;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+// Candidates tried around an active point before it is retired
+const ATTEMPTS: u32 = 30;
+// Background grid cells allowed; finer spacings over larger regions give up
+// rather than allocate without bound
+const MAX_GRID_CELLS: usize = 1 << 21;
+// Random draws spent looking for a first point inside the region
+const SEED_ATTEMPTS: u32 = 1000;
+
+// `count` points of the region `inside`, which lies within the box `min`..`max`,
+// at least `spacing` apart. The region is filled completely and a random subset
+// kept, so the points are spread over all of it; when fewer fit, the rest come
+// from `fallback` without the spacing guarantee.
+pub fn sample<R: Rng>(
+    count: usize,
+    spacing: f32,
+    (min, max): ([f32; 3], [f32; 3]),
+    inside: impl Fn([f32; 3]) -> bool,
+    rng: &mut R,
+    mut fallback: impl FnMut(&mut R) -> [f32; 3],
+) -> Vec<[f32; 3]> {
+    let mut points = fill(spacing, (min, max), &inside, rng).unwrap_or_default();
+    if points.len() > count {
+        points.shuffle(rng);
+        points.truncate(count);
+    } else if points.len() < count {
+        console_log!(
+            "⚠️ Only {} of {} particles fit {} apart; placing the rest at random",
+            points.len(),
+            count,
+            spacing
+        );
+        while points.len() < count {
+            points.push(fallback(rng));
+        }
+    }
+    points
+}
+
+// Every point Bridson's algorithm places in the region, or `None` if the grid
+// would be too large
+fn fill(
+    spacing: f32,
+    (min, max): ([f32; 3], [f32; 3]),
+    inside: &impl Fn([f32; 3]) -> bool,
+    rng: &mut impl Rng,
+) -> Option<Vec<[f32; 3]>> {
+    if spacing.is_nan() || spacing <= 0.0 {
+        return None;
+    }
+    // A cell's diagonal is the spacing, so each holds at most one point
+    let cell = spacing / 3f32.sqrt();
+    let dims = [0, 1, 2].map(|axis| (((max[axis] - min[axis]) / cell).ceil() as usize).max(1));
+    if dims.iter().try_fold(1usize, |n, &d| n.checked_mul(d))? > MAX_GRID_CELLS {
+        return None;
+    }
+    let cell_of = |p: [f32; 3]| {
+        [0, 1, 2].map(|axis| (((p[axis] - min[axis]) / cell) as usize).min(dims[axis] - 1))
+    };
+    let index = |c: [usize; 3]| (c[2] * dims[1] + c[1]) * dims[0] + c[0];
+    let mut grid = vec![u32::MAX; dims[0] * dims[1] * dims[2]];
+    let mut points: Vec<[f32; 3]> = Vec::new();
+
+    let seed = (0..SEED_ATTEMPTS)
+        .map(|_| {
+            [0, 1, 2].map(|axis| {
+                if max[axis] > min[axis] {
+                    rng.gen_range(min[axis]..max[axis])
+                } else {
+                    min[axis]
+                }
+            })
+        })
+        .find(|&p| inside(p))?;
+    grid[index(cell_of(seed))] = 0;
+    points.push(seed);
+    let mut active = vec![0u32];
+
+    while !active.is_empty() {
+        let slot = rng.gen_range(0..active.len());
+        let center = points[active[slot] as usize];
+        let mut placed = false;
+        for _ in 0..ATTEMPTS {
+            // Uniform in the spherical shell between one and two spacings out
+            let r = spacing * rng.gen_range(1.0f32..8.0).cbrt();
+            let z: f32 = rng.gen_range(-1.0..1.0);
+            let theta = rng.gen_range(0.0..std::f32::consts::TAU);
+            let ring = (1.0 - z * z).sqrt();
+            let candidate = [
+                center[0] + r * ring * theta.cos(),
+                center[1] + r * ring * theta.sin(),
+                center[2] + r * z,
+            ];
+            let in_box =
+                (0..3).all(|axis| candidate[axis] >= min[axis] && candidate[axis] <= max[axis]);
+            if !in_box || !inside(candidate) {
+                continue;
+            }
+
+            let c = cell_of(candidate);
+            let near = |axis: usize| c[axis].saturating_sub(2)..(c[axis] + 3).min(dims[axis]);
+            let crowded = near(2).any(|z| {
+                near(1).any(|y| {
+                    near(0).any(|x| {
+                        let other = grid[index([x, y, z])];
+                        other != u32::MAX && {
+                            let p = points[other as usize];
+                            let d = [0, 1, 2].map(|axis| p[axis] - candidate[axis]);
+                            d[0] * d[0] + d[1] * d[1] + d[2] * d[2] < spacing * spacing
+                        }
+                    })
+                })
+            });
+            if !crowded {
+                grid[index(c)] = points.len() as u32;
+                active.push(points.len() as u32);
+                points.push(candidate);
+                placed = true;
+                break;
+            }
+        }
+        if !placed {
+            active.swap_remove(slot);
+        }
+    }
+    Some(points)
+}
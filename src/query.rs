@@ -0,0 +1,104 @@
+// Start-up settings from the page URL, for shareable links to a framing and
+// configuration: `?scale=2&roty=1.5&gm=50000`. Unknown keys and malformed
+// values are ignored; numbers are clamped to the ranges in ranges.rs.
+
+use crate::config::SimulationConfig;
+use crate::ranges::parameter_ranges;
+use crate::utils::console_log;
+
+// Apply the current page's query string to `config`
+pub fn apply_page_query(config: &mut SimulationConfig) {
+    let Some(search) = web_sys::window().and_then(|window| window.location().search().ok()) else {
+        return;
+    };
+    let applied = apply_query(&search, config);
+    if applied > 0 {
+        console_log!("🔗 Applied {} settings from the URL", applied);
+    }
+}
+
+// Apply `key=value` pairs from `query` (with or without the leading '?'),
+// returning how many were used
+pub fn apply_query(query: &str, config: &mut SimulationConfig) -> u32 {
+    let ranges = parameter_ranges();
+    let mut applied = 0;
+    for pair in query.trim_start_matches('?').split('&') {
+        let Some((key, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let Some(value) = value.parse::<f32>().ok().filter(|v| v.is_finite()) else {
+            continue;
+        };
+        if let Some(flag) = flag_field(config, key) {
+            *flag = value != 0.0;
+            applied += 1;
+            continue;
+        }
+
+        // The camera clamps its own zoom and elevation when it resets
+        let (field, value) = match key {
+            "scale" => (&mut config.camera.scale, value),
+            "rotx" => (&mut config.camera.rotation_x, value),
+            "roty" => (&mut config.camera.rotation_y, value),
+            "target_x" => (&mut config.camera.position[0], value),
+            "target_y" => (&mut config.camera.position[1], value),
+            "target_z" => (&mut config.camera.position[2], value),
+            _ => {
+                let Some(field) = simulation_field(config, key) else {
+                    continue;
+                };
+                let value = match ranges.iter().find(|range| range.name == key) {
+                    Some(range) => value.clamp(range.min, range.max),
+                    None => value,
+                };
+                (field, value)
+            }
+        };
+        *field = value;
+        applied += 1;
+    }
+    applied
+}
+
+// Config flag behind a query key, set by any nonzero number
+fn flag_field<'a>(config: &'a mut SimulationConfig, key: &str) -> Option<&'a mut bool> {
+    Some(match key {
+        "streaming_init" => &mut config.streaming_init,
+        _ => return None,
+    })
+}
+
+// Config field behind a query key; every key here has an entry in ranges.rs
+fn simulation_field<'a>(config: &'a mut SimulationConfig, key: &str) -> Option<&'a mut f32> {
+    Some(match key {
+        "gm" => &mut config.gm,
+        "absorb_radius" => &mut config.absorb_radius,
+        "softening" => &mut config.softening,
+        "respawn_radius" => &mut config.respawn_radius,
+        "max_speed" => &mut config.max_speed,
+        "inflow_rate" => &mut config.inflow_rate,
+        "warp_amplitude" => &mut config.warp_amplitude,
+        "precession_rate" => &mut config.precession_rate,
+        "radial_density_exponent" => &mut config.distribution.radial_density_exponent,
+        "poisson_spacing" => &mut config.distribution.poisson_spacing,
+        "disk_flatten" => &mut config.distribution.disk_flatten,
+        "disk_height_spread" => &mut config.distribution.disk_height_spread,
+        "orbital_fraction" => &mut config.distribution.orbital_fraction,
+        "velocity_jitter" => &mut config.distribution.velocity_jitter,
+        "velocity_dispersion_radial" => &mut config.distribution.velocity_dispersion[0],
+        "velocity_dispersion_tangential" => &mut config.distribution.velocity_dispersion[1],
+        "velocity_dispersion_vertical" => &mut config.distribution.velocity_dispersion[2],
+        "disk_orientation_x" => &mut config.distribution.disk_orientation[0],
+        "disk_orientation_y" => &mut config.distribution.disk_orientation[1],
+        "disk_orientation_z" => &mut config.distribution.disk_orientation[2],
+        "halo_speed" => &mut config.distribution.halo_speed,
+        "halo_core_radius" => &mut config.distribution.halo_core_radius,
+        "particle_alpha" => &mut config.particle_alpha,
+        "sprite_size" => &mut config.sprite_size,
+        "fog_density" => &mut config.fog_density,
+        "density_glow_strength" => &mut config.density_glow_strength,
+        "vignette_strength" => &mut config.vignette_strength,
+        "gamma" => &mut config.gamma,
+        _ => return None,
+    })
+}
@@ -0,0 +1,294 @@
+// Valid ranges of the numeric tunables, for host UIs building sliders. Keep in
+// step with the setters: `min`/`max` are where they clamp, or a sensible slider
+// end where a setter has no bound on that side.
+
+use crate::camera::{DEFAULT_AUTO_ROTATE_IDLE, DEFAULT_AUTO_ROTATE_SPEED};
+use crate::composite::{MAX_AMBIENT_FLOOR, MAX_DITHER_STRENGTH, MAX_GAMMA, MIN_GAMMA};
+use crate::config::SimulationConfig;
+use crate::dof::MAX_BLUR_RADIUS;
+use crate::input::{MAX_CURVE_EXPONENT, MIN_CURVE_EXPONENT};
+use crate::physics::{BOUNDARY, MIN_HALO_CORE_RADIUS};
+use crate::simulation::{
+    MAX_POISSON_SPACING, MAX_RADIAL_BANDS, MAX_RADIAL_DENSITY_EXPONENT, MAX_REPLAY_FRAMES,
+    MAX_SUBSTEPS_PER_FRAME, MIN_RADIAL_DENSITY_EXPONENT, NUM_PARTICLES,
+};
+
+pub struct ParameterRange {
+    // Setter argument the range applies to
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+    pub step: f32,
+}
+
+pub fn parameter_ranges() -> Vec<ParameterRange> {
+    let config = SimulationConfig::default();
+    let range = |name, min, max, default, step| ParameterRange {
+        name,
+        min,
+        max,
+        default,
+        step,
+    };
+
+    vec![
+        range("particle_alpha", 0.0, 1.0, config.particle_alpha, 0.01),
+        range("sprite_size", 1.0, 32.0, config.sprite_size, 0.5),
+        range("min_screen_size", 0.0, 4.0, config.min_screen_size, 0.05),
+        range(
+            "radial_bands",
+            0.0,
+            MAX_RADIAL_BANDS,
+            config.radial_bands,
+            0.5,
+        ),
+        range("color_blend", 0.0, 1.0, config.color_blend, 0.01),
+        range("fog_density", 0.0, 0.01, config.fog_density, 0.0001),
+        range(
+            "vignette_strength",
+            0.0,
+            1.0,
+            config.vignette_strength,
+            0.01,
+        ),
+        range("vignette_radius", 0.0, 2.0, config.vignette_radius, 0.01),
+        range(
+            "ambient_floor",
+            0.0,
+            MAX_AMBIENT_FLOOR,
+            config.ambient_floor,
+            0.005,
+        ),
+        range("gamma", MIN_GAMMA, MAX_GAMMA, config.gamma, 0.01),
+        range(
+            "dither_strength",
+            0.0,
+            MAX_DITHER_STRENGTH,
+            config.dither_strength,
+            0.1,
+        ),
+        range(
+            "density_glow_strength",
+            0.0,
+            4.0,
+            config.density_glow_strength,
+            0.05,
+        ),
+        range(
+            "dof_aperture",
+            0.0,
+            MAX_BLUR_RADIUS,
+            config.dof_aperture,
+            0.1,
+        ),
+        range(
+            "inner_glow_radius",
+            0.0,
+            500.0,
+            config.inner_glow_radius,
+            1.0,
+        ),
+        range(
+            "inner_glow_strength",
+            0.0,
+            4.0,
+            config.inner_glow_strength,
+            0.05,
+        ),
+        range(
+            "inner_glow_falloff",
+            0.0,
+            8.0,
+            config.inner_glow_falloff,
+            0.1,
+        ),
+        range(
+            "horizon_ring_width",
+            0.0,
+            10.0,
+            config.horizon.ring_width,
+            0.1,
+        ),
+        range(
+            "horizon_ring_intensity",
+            0.0,
+            5.0,
+            config.horizon.ring_intensity,
+            0.05,
+        ),
+        range("velocity_vector_scale", 0.0, 1.0, 0.0, 0.01),
+        range("gm", 0.0, 200000.0, config.gm, 500.0),
+        range("max_speed", 0.0, 1000.0, config.max_speed, 1.0),
+        range("absorb_radius", 0.0, 100.0, config.absorb_radius, 0.5),
+        range("softening", 0.0, 20.0, config.softening, 0.1),
+        range("respawn_radius", 0.0, BOUNDARY, config.respawn_radius, 1.0),
+        range("inflow_rate", 0.0, 20000.0, config.inflow_rate, 100.0),
+        range(
+            "radial_density_exponent",
+            MIN_RADIAL_DENSITY_EXPONENT,
+            MAX_RADIAL_DENSITY_EXPONENT,
+            config.distribution.radial_density_exponent,
+            0.05,
+        ),
+        range(
+            "poisson_spacing",
+            0.0,
+            MAX_POISSON_SPACING,
+            config.distribution.poisson_spacing,
+            0.1,
+        ),
+        range(
+            "disk_flatten",
+            0.0,
+            1.0,
+            config.distribution.disk_flatten,
+            0.01,
+        ),
+        range(
+            "disk_height_spread",
+            0.0,
+            std::f32::consts::FRAC_PI_2,
+            config.distribution.disk_height_spread,
+            0.01,
+        ),
+        range(
+            "orbital_fraction",
+            0.0,
+            2.0,
+            config.distribution.orbital_fraction,
+            0.01,
+        ),
+        range(
+            "velocity_jitter",
+            0.0,
+            10.0,
+            config.distribution.velocity_jitter,
+            0.1,
+        ),
+        range(
+            "velocity_dispersion_radial",
+            0.0,
+            20.0,
+            config.distribution.velocity_dispersion[0],
+            0.1,
+        ),
+        range(
+            "velocity_dispersion_tangential",
+            0.0,
+            20.0,
+            config.distribution.velocity_dispersion[1],
+            0.1,
+        ),
+        range(
+            "velocity_dispersion_vertical",
+            0.0,
+            20.0,
+            config.distribution.velocity_dispersion[2],
+            0.1,
+        ),
+        range(
+            "disk_orientation_x",
+            -1.0,
+            1.0,
+            config.distribution.disk_orientation[0],
+            0.01,
+        ),
+        range(
+            "disk_orientation_y",
+            -1.0,
+            1.0,
+            config.distribution.disk_orientation[1],
+            0.01,
+        ),
+        range(
+            "disk_orientation_z",
+            -1.0,
+            1.0,
+            config.distribution.disk_orientation[2],
+            0.01,
+        ),
+        range(
+            "halo_speed",
+            0.0,
+            100.0,
+            config.distribution.halo_speed,
+            0.5,
+        ),
+        range(
+            "halo_core_radius",
+            MIN_HALO_CORE_RADIUS,
+            300.0,
+            config.distribution.halo_core_radius,
+            1.0,
+        ),
+        range("heating_rate", 0.0, 500.0, config.heating_rate, 1.0),
+        range("cooling_rate", 0.0, 10.0, config.cooling_rate, 0.05),
+        range("warp_amplitude", 0.0, 0.8, config.warp_amplitude, 0.01),
+        range("precession_rate", -2.0, 2.0, config.precession_rate, 0.01),
+        range(
+            "wrap_bounds",
+            10.0,
+            2.0 * BOUNDARY,
+            config.wrap_bounds[0],
+            10.0,
+        ),
+        range(
+            "steady_state_energy_tolerance",
+            0.0,
+            0.1,
+            config.steady_state_energy_tolerance,
+            0.001,
+        ),
+        range(
+            "steady_state_drift_tolerance",
+            0.0,
+            50.0,
+            config.steady_state_drift_tolerance,
+            0.5,
+        ),
+        range(
+            "steady_state_window",
+            0.0,
+            120.0,
+            config.steady_state_window,
+            1.0,
+        ),
+        range(
+            "substeps",
+            1.0,
+            MAX_SUBSTEPS_PER_FRAME as f32,
+            config.substeps_per_frame as f32,
+            1.0,
+        ),
+        range(
+            "active_particles",
+            0.0,
+            NUM_PARTICLES as f32,
+            NUM_PARTICLES as f32,
+            1024.0,
+        ),
+        range("replay_length", 0.0, MAX_REPLAY_FRAMES as f32, 0.0, 1.0),
+        range(
+            "auto_rotate_speed",
+            -1.0,
+            1.0,
+            DEFAULT_AUTO_ROTATE_SPEED,
+            0.01,
+        ),
+        range(
+            "auto_rotate_idle",
+            0.0,
+            300.0,
+            DEFAULT_AUTO_ROTATE_IDLE,
+            1.0,
+        ),
+        range(
+            "rotate_curve_exponent",
+            MIN_CURVE_EXPONENT,
+            MAX_CURVE_EXPONENT,
+            config.rotate_curve_exponent,
+            0.05,
+        ),
+    ]
+}
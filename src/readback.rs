@@ -0,0 +1,87 @@
+// One-off asynchronous copies of GPU buffers back to the CPU, for exports that
+// need the whole buffer rather than the per-frame results the reduction and
+// picker read back
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use wasm_bindgen::JsValue;
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+// Resolves once `map_async` has called back
+struct MapFuture(Arc<Mutex<MapState>>);
+
+impl Future for MapFuture {
+    type Output = Result<(), wgpu::BufferAsyncError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = match self.0.lock() {
+            Ok(state) => state,
+            Err(_) => return Poll::Ready(Err(wgpu::BufferAsyncError)),
+        };
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// Copy the first `size` bytes of `source` (which needs COPY_SRC) into a staging
+// buffer and wait for them to be mapped
+pub async fn read_buffer(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    source: &wgpu::Buffer,
+    size: u64,
+) -> Result<Vec<u8>, JsValue> {
+    if size == 0 {
+        return Ok(Vec::new());
+    }
+
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Staging Buffer"),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback Encoder"),
+    });
+    encoder.copy_buffer_to_buffer(source, 0, &staging, 0, size);
+    queue.submit(std::iter::once(encoder.finish()));
+
+    map_read(&staging).await?;
+    let data = staging.slice(..).get_mapped_range().to_vec();
+    staging.unmap();
+    Ok(data)
+}
+
+// Map all of `staging` for reading, after the copies into it were submitted
+pub async fn map_read(staging: &wgpu::Buffer) -> Result<(), JsValue> {
+    let state = Arc::new(Mutex::new(MapState::default()));
+    let callback_state = state.clone();
+    staging
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            if let Ok(mut state) = callback_state.lock() {
+                state.result = Some(result);
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            }
+        });
+
+    MapFuture(state)
+        .await
+        .map_err(|e| JsValue::from_str(&format!("Failed to read back buffer: {e}")))
+}
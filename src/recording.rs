@@ -0,0 +1,341 @@
+// Frame sequence capture to IndexedDB. Frames are copied out of the swapchain
+// into a small pool of staging buffers, mapped asynchronously and written to the
+// database as they arrive, so a long recording never has to fit in memory.
+// Assembling the frames into a video is left to JS.
+
+use crate::utils::console_log;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+pub const DATABASE_NAME: &str = "galacto-recordings";
+pub const STORE_NAME: &str = "frames";
+// Frames being read back or waiting to be written; a frame due while all of
+// them are busy is dropped and counted
+const MAX_BUFFERED_FRAMES: usize = 4;
+const MAX_FPS: f32 = 60.0;
+
+pub struct Recorder {
+    // Identifies this recording's frames, which are keyed [session, index]
+    session: f64,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    padded_bytes_per_row: u32,
+    interval_ms: f64,
+    next_capture_ms: Option<f64>,
+    frame_limit: u32,
+    frames_captured: u32,
+    frames_dropped: u32,
+    active: bool,
+    free: Vec<wgpu::Buffer>,
+    buffers_created: usize,
+    // Copy recorded into this frame's encoder, mapped once it is submitted
+    copied: Option<(u32, wgpu::Buffer)>,
+    // Frames being mapped, by index
+    mapping: Vec<(u32, wgpu::Buffer)>,
+    // Frames whose mapping finished, and whether it succeeded; filled in by
+    // the map callbacks
+    completed: Arc<Mutex<Vec<(u32, bool)>>>,
+    database: Rc<RefCell<Option<web_sys::IdbDatabase>>>,
+    open_failed: Rc<Cell<bool>>,
+    pending_writes: Rc<Cell<u32>>,
+}
+
+impl Recorder {
+    // Start recording `seconds` of frames sized `width` x `height` at `fps`.
+    // The texture must be a 4-byte format that allows COPY_SRC.
+    pub fn start(
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        fps: f32,
+        seconds: f32,
+    ) -> Result<Self, JsValue> {
+        if fps.is_nan() || seconds.is_nan() || fps <= 0.0 || seconds <= 0.0 {
+            return Err(JsValue::from_str(&format!(
+                "Recording needs a positive fps and duration, got {fps} fps for {seconds}s"
+            )));
+        }
+        if format.block_copy_size(None) != Some(4) {
+            return Err(JsValue::from_str(&format!(
+                "Cannot record frames in {format:?}"
+            )));
+        }
+        let fps = fps.min(MAX_FPS);
+        let factory = web_sys::window()
+            .ok_or_else(|| JsValue::from_str("No window to record in"))?
+            .indexed_db()?
+            .ok_or_else(|| JsValue::from_str("IndexedDB is not available"))?;
+
+        let database = Rc::new(RefCell::new(None));
+        let open_failed = Rc::new(Cell::new(false));
+        open_database(&factory, database.clone(), open_failed.clone())?;
+
+        let padded_bytes_per_row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let frame_limit = (fps * seconds).ceil().max(1.0) as u32;
+        console_log!(
+            "🎥 Recording {} frames at {}x{} ({} fps)",
+            frame_limit,
+            width,
+            height,
+            fps
+        );
+
+        Ok(Self {
+            session: js_sys::Date::now(),
+            width,
+            height,
+            format,
+            padded_bytes_per_row,
+            interval_ms: 1000.0 / fps as f64,
+            next_capture_ms: None,
+            frame_limit,
+            frames_captured: 0,
+            frames_dropped: 0,
+            active: true,
+            free: Vec::new(),
+            buffers_created: 0,
+            copied: None,
+            mapping: Vec::new(),
+            completed: Arc::new(Mutex::new(Vec::new())),
+            database,
+            open_failed,
+            pending_writes: Rc::new(Cell::new(0)),
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    // Copy `texture` into a staging buffer if a frame is due at `time_ms`
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        time_ms: f64,
+    ) {
+        if !self.active {
+            return;
+        }
+        if texture.width() != self.width || texture.height() != self.height {
+            console_log!("🎥 Canvas resized, recording stopped");
+            self.active = false;
+            return;
+        }
+
+        let due = *self.next_capture_ms.get_or_insert(time_ms);
+        if time_ms < due {
+            return;
+        }
+        // Skip ahead after a stall instead of capturing a burst of frames
+        self.next_capture_ms = Some((due + self.interval_ms).max(time_ms));
+
+        let buffer = match self.free.pop() {
+            Some(buffer) => buffer,
+            None if self.buffers_created < MAX_BUFFERED_FRAMES => {
+                self.buffers_created += 1;
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Recording Staging Buffer"),
+                    size: self.padded_bytes_per_row as u64 * self.height as u64,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            }
+            None => {
+                self.frames_dropped += 1;
+                return;
+            }
+        };
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            texture.size(),
+        );
+        self.copied = Some((self.frames_captured, buffer));
+        self.frames_captured += 1;
+        if self.frames_captured >= self.frame_limit {
+            console_log!("🎥 Recording complete: {} frames", self.frames_captured);
+            self.active = false;
+        }
+    }
+
+    // Start mapping the frame copied this frame; must be called after the
+    // encoder passed to `capture` has been submitted
+    pub fn after_submit(&mut self) {
+        let Some((index, buffer)) = self.copied.take() else {
+            return;
+        };
+        let completed = self.completed.clone();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if let Err(e) = &result {
+                    console_log!("❌ Failed to read back recorded frame {}: {}", index, e);
+                }
+                if let Ok(mut completed) = completed.lock() {
+                    completed.push((index, result.is_ok()));
+                }
+            });
+        self.mapping.push((index, buffer));
+    }
+
+    // Write mapped frames to the database and return their buffers to the pool.
+    // Frames wait, mapped, until the database has opened.
+    pub fn flush(&mut self) {
+        let database = self.database.borrow();
+        if database.is_none() && !self.open_failed.get() {
+            return;
+        }
+        let frames = match self.completed.lock() {
+            Ok(mut completed) => std::mem::take(&mut *completed),
+            Err(_) => return,
+        };
+
+        for (index, mapped) in frames {
+            let Some(position) = self.mapping.iter().position(|(i, _)| *i == index) else {
+                continue;
+            };
+            let (_, buffer) = self.mapping.swap_remove(position);
+            if !mapped {
+                self.free.push(buffer);
+                continue;
+            }
+            if let Some(database) = database.as_ref() {
+                let data = {
+                    let mapped = buffer.slice(..).get_mapped_range();
+                    let row = self.width as usize * 4;
+                    let mut data = Vec::with_capacity(row * self.height as usize);
+                    for padded_row in mapped.chunks(self.padded_bytes_per_row as usize) {
+                        data.extend_from_slice(&padded_row[..row]);
+                    }
+                    data
+                };
+                if let Err(e) = self.write_frame(database, index, &data) {
+                    console_log!("❌ Failed to store recorded frame {}: {:?}", index, e);
+                }
+            }
+            buffer.unmap();
+            self.free.push(buffer);
+        }
+    }
+
+    fn write_frame(
+        &self,
+        database: &web_sys::IdbDatabase,
+        index: u32,
+        data: &[u8],
+    ) -> Result<(), JsValue> {
+        let transaction = database
+            .transaction_with_str_and_mode(STORE_NAME, web_sys::IdbTransactionMode::Readwrite)?;
+        let frame = js_sys::Object::new();
+        js_sys::Reflect::set(&frame, &"width".into(), &self.width.into())?;
+        js_sys::Reflect::set(&frame, &"height".into(), &self.height.into())?;
+        js_sys::Reflect::set(&frame, &"format".into(), &self.format_name().into())?;
+        js_sys::Reflect::set(&frame, &"data".into(), &js_sys::Uint8Array::from(data))?;
+        let key = js_sys::Array::of2(&self.session.into(), &index.into());
+        transaction
+            .object_store(STORE_NAME)?
+            .put_with_key(&frame, &key)?;
+
+        let pending_writes = self.pending_writes.clone();
+        pending_writes.set(pending_writes.get() + 1);
+        let done = Closure::once_into_js(move || pending_writes.set(pending_writes.get() - 1));
+        // A failed write aborts the transaction, so exactly one of these fires
+        transaction.set_oncomplete(Some(done.unchecked_ref()));
+        transaction.set_onabort(Some(done.unchecked_ref()));
+        Ok(())
+    }
+
+    // Frames captured but not yet stored
+    pub fn pending(&self) -> u32 {
+        (self.buffers_created - self.free.len()) as u32 + self.pending_writes.get()
+    }
+
+    // Stop capturing and describe where the frames are. Frames still pending
+    // keep being written; wait for `pending()` to reach 0 before reading them.
+    pub fn stop(&mut self) -> JsValue {
+        if self.active {
+            console_log!("🎥 Recording stopped after {} frames", self.frames_captured);
+        }
+        self.active = false;
+
+        let handle = js_sys::Object::new();
+        let fields: [(&str, JsValue); 8] = [
+            ("database", DATABASE_NAME.into()),
+            ("store", STORE_NAME.into()),
+            ("session", self.session.into()),
+            ("frame_count", self.frames_captured.into()),
+            ("dropped_frames", self.frames_dropped.into()),
+            ("width", self.width.into()),
+            ("height", self.height.into()),
+            ("format", self.format_name().into()),
+        ];
+        for (name, value) in fields {
+            let _ = js_sys::Reflect::set(&handle, &name.into(), &value);
+        }
+        handle.into()
+    }
+
+    // Byte order of each frame's pixels
+    fn format_name(&self) -> &'static str {
+        match self.format {
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => "bgra8",
+            _ => "rgba8",
+        }
+    }
+}
+
+fn open_database(
+    factory: &web_sys::IdbFactory,
+    database: Rc<RefCell<Option<web_sys::IdbDatabase>>>,
+    open_failed: Rc<Cell<bool>>,
+) -> Result<(), JsValue> {
+    let request = factory.open_with_u32(DATABASE_NAME, 1)?;
+
+    let upgrade_request = request.clone();
+    let on_upgrade = Closure::once_into_js(move || {
+        if let Ok(result) = upgrade_request.result() {
+            let db: web_sys::IdbDatabase = result.unchecked_into();
+            if let Err(e) = db.create_object_store(STORE_NAME) {
+                console_log!("❌ Failed to create the recording store: {:?}", e);
+            }
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+    let success_request = request.clone();
+    let success_failed = open_failed.clone();
+    let on_success = Closure::once_into_js(move || match success_request.result() {
+        Ok(result) => *database.borrow_mut() = Some(result.unchecked_into()),
+        Err(e) => {
+            console_log!("❌ Failed to open the recording database: {:?}", e);
+            success_failed.set(true);
+        }
+    });
+    request.set_onsuccess(Some(on_success.unchecked_ref()));
+
+    let error_request = request.clone();
+    let on_error = Closure::once_into_js(move || {
+        console_log!(
+            "❌ Failed to open the recording database: {:?}",
+            error_request.error().ok().flatten()
+        );
+        open_failed.set(true);
+    });
+    request.set_onerror(Some(on_error.unchecked_ref()));
+    Ok(())
+}
@@ -0,0 +1,304 @@
+use crate::simulation::dispatch_grid;
+use bytemuck::{Pod, Zeroable};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+const REDUCE_WORKGROUP_SIZE: u32 = 256;
+// Run the reduction at most once every this many frames
+const REDUCE_INTERVAL: u32 = 30;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ReduceParams {
+    particle_count: u32,
+    partial_count: u32,
+    gm: f32,
+    _padding: u32,
+    halo: [f32; 4],
+}
+
+// Matches `Partial` in reduce.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct Partial {
+    min_position: [f32; 4],
+    max_position: [f32; 4],
+    sum: [f32; 4],
+    energy: [f32; 4],
+}
+
+// Over the particles not yet absorbed, except for `absorbed`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PositionStats {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub centroid: [f32; 3],
+    // Totals per unit mass
+    pub kinetic_energy: f32,
+    pub potential_energy: f32,
+    // Particles absorbed by the black hole since the last restart
+    pub absorbed: u32,
+    // Simulated time of the particle state that was reduced
+    pub time: f64,
+}
+
+impl PositionStats {
+    fn from_partial(partial: &Partial, time: f64) -> Option<Self> {
+        // Nothing to describe once every particle has been absorbed
+        let count = partial.sum[3];
+        if count <= 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            min: [
+                partial.min_position[0],
+                partial.min_position[1],
+                partial.min_position[2],
+            ],
+            max: [
+                partial.max_position[0],
+                partial.max_position[1],
+                partial.max_position[2],
+            ],
+            centroid: [
+                partial.sum[0] / count,
+                partial.sum[1] / count,
+                partial.sum[2] / count,
+            ],
+            kinetic_energy: partial.energy[0],
+            potential_energy: partial.energy[1],
+            absorbed: partial.energy[2] as u32,
+            time,
+        })
+    }
+}
+
+// Two-pass GPU reduction of particle positions and energy. Only the tiny result buffer is
+// read back, asynchronously, and the latest completed result is cached.
+pub struct PositionReduction {
+    particles_pipeline: wgpu::ComputePipeline,
+    partials_pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    result_buffer: wgpu::Buffer,
+    staging_buffer: wgpu::Buffer,
+    max_workgroups_per_dimension: u32,
+    frames_since_run: u32,
+    // A copy into the staging buffer was encoded and still needs mapping, with
+    // the simulated time of the state it holds
+    copy_pending: Option<f64>,
+    // The staging buffer is currently being mapped
+    in_flight: Arc<AtomicBool>,
+    latest: Arc<Mutex<Option<PositionStats>>>,
+}
+
+impl PositionReduction {
+    pub fn new(device: &wgpu::Device, particle_buffer: &wgpu::Buffer, max_particles: u32) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Reduce Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/reduce.wgsl").into()),
+        });
+
+        let storage_entry = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Reduce Bind Group Layout"),
+            entries: &[
+                storage_entry(0, true),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(2, false),
+                storage_entry(3, false),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Reduce Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        };
+        let particles_pipeline = create_pipeline("Reduce Particles Pipeline", "reduce_particles");
+        let partials_pipeline = create_pipeline("Reduce Partials Pipeline", "reduce_partials");
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reduce Params Buffer"),
+            size: std::mem::size_of::<ReduceParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let max_partials = max_particles.div_ceil(REDUCE_WORKGROUP_SIZE).max(1);
+        let partials_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reduce Partials Buffer"),
+            size: (max_partials as usize * std::mem::size_of::<Partial>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let result_size = std::mem::size_of::<Partial>() as u64;
+        let result_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reduce Result Buffer"),
+            size: result_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reduce Staging Buffer"),
+            size: result_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Reduce Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: particle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: partials_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: result_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            particles_pipeline,
+            partials_pipeline,
+            bind_group,
+            params_buffer,
+            result_buffer,
+            staging_buffer,
+            max_workgroups_per_dimension: device.limits().max_compute_workgroups_per_dimension,
+            frames_since_run: REDUCE_INTERVAL,
+            copy_pending: None,
+            in_flight: Arc::new(AtomicBool::new(false)),
+            latest: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // Record the reduction passes if enough frames have elapsed and the previous
+    // readback has completed
+    pub fn encode(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        particle_count: u32,
+        gm: f32,
+        halo: [f32; 4],
+        time: f64,
+    ) {
+        self.frames_since_run = self.frames_since_run.saturating_add(1);
+        if self.frames_since_run < REDUCE_INTERVAL
+            || particle_count == 0
+            || self.in_flight.load(Ordering::Acquire)
+        {
+            return;
+        }
+        self.frames_since_run = 0;
+
+        let partial_count = particle_count.div_ceil(REDUCE_WORKGROUP_SIZE);
+        let params = ReduceParams {
+            particle_count,
+            partial_count,
+            gm,
+            _padding: 0,
+            halo,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Reduce Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            compute_pass.set_pipeline(&self.particles_pipeline);
+            let (x, y) = dispatch_grid(partial_count, self.max_workgroups_per_dimension);
+            compute_pass.dispatch_workgroups(x, y, 1);
+            compute_pass.set_pipeline(&self.partials_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        encoder.copy_buffer_to_buffer(
+            &self.result_buffer,
+            0,
+            &self.staging_buffer,
+            0,
+            self.staging_buffer.size(),
+        );
+        self.copy_pending = Some(time);
+    }
+
+    // Start mapping the staging buffer; must be called after the encoder passed to
+    // `encode` has been submitted
+    pub fn map_results(&mut self) {
+        let Some(time) = self.copy_pending.take() else {
+            return;
+        };
+        self.in_flight.store(true, Ordering::Release);
+
+        let staging = self.staging_buffer.clone();
+        let in_flight = self.in_flight.clone();
+        let latest = self.latest.clone();
+        self.staging_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    let partial = {
+                        let data = staging.slice(..).get_mapped_range();
+                        *bytemuck::from_bytes::<Partial>(&data)
+                    };
+                    staging.unmap();
+                    if let Ok(mut latest) = latest.lock() {
+                        *latest = PositionStats::from_partial(&partial, time);
+                    }
+                }
+                in_flight.store(false, Ordering::Release);
+            });
+    }
+
+    pub fn latest(&self) -> Option<PositionStats> {
+        self.latest.lock().ok().and_then(|latest| *latest)
+    }
+}
@@ -0,0 +1,1143 @@
+use crate::camera::Camera;
+use crate::composite::Composite;
+use crate::config::{RenderMode, SimulationConfig};
+use crate::dof::DepthOfField;
+use crate::horizon::Horizon;
+use crate::simulation::{dispatch_grid, Attractor, Simulation};
+use crate::taa::Taa;
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+// R32Float is not blendable without an optional feature, so counts accumulate in half floats
+const DENSITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+const MAX_WORKGROUP_SIZE: u32 = 64;
+// Capacity of the attractor marker buffer
+const MAX_ATTRACTORS: usize = 16;
+// Half-size in world units of the marker for an attractor of the default mass
+const MARKER_SIZE: f32 = 16.0;
+const MARKER_REFERENCE_MASS: f32 = 40000.0;
+// Velocity vectors are drawn for every this many particles
+const VECTOR_STRIDE: u32 = 32;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct GlowParams {
+    strength: f32,
+    _padding: [u32; 3],
+}
+
+// Matches `Markers` in markers.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct MarkerUniform {
+    view_proj: [[f32; 4]; 4],
+    camera_right: [f32; 4],
+    camera_up: [f32; 4],
+    size: f32,
+    reference_mass: f32,
+    _padding: [u32; 2],
+}
+
+// Matches `Vectors` in vectors.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct VectorUniform {
+    scale: f32,
+    stride: u32,
+    _padding: [u32; 2],
+}
+
+// Matches `Vignette` in vignette.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct VignetteUniform {
+    strength: f32,
+    radius: f32,
+    aspect: f32,
+    _padding: u32,
+}
+
+// Draws the frame: `render` runs the particle pass (or the heatmap) and every
+// post-process in a fixed order, so new effects have one place to slot in.
+// Much of it is built on a density estimate: particles are splatted additively into a density
+// texture and a compute pass finds the peak density. The heatmap maps
+// density / peak through a colormap; the density glow adds it over the particle
+// view instead. Attractor markers and velocity vectors are drawn into the
+// regular pass on request. Depth of field blurs the finished particle view by
+// depth, the vignette darkens the final frame and the composite adjusts it
+// for the viewing conditions. Temporal anti-aliasing accumulates the particle
+// view over frames while the camera is still.
+pub struct Renderer {
+    mode: RenderMode,
+    density_pipeline: wgpu::RenderPipeline,
+    max_pipeline: wgpu::ComputePipeline,
+    colormap_pipeline: wgpu::RenderPipeline,
+    glow_pipeline: wgpu::RenderPipeline,
+    heatmap_bind_group_layout: wgpu::BindGroupLayout,
+    heatmap_bind_group: wgpu::BindGroup,
+    density_view: wgpu::TextureView,
+    max_density_buffer: wgpu::Buffer,
+    glow_params_buffer: wgpu::Buffer,
+    density_glow_strength: f32,
+    marker_pipeline: wgpu::RenderPipeline,
+    marker_bind_group: wgpu::BindGroup,
+    marker_uniform_buffer: wgpu::Buffer,
+    attractor_buffer: wgpu::Buffer,
+    attractor_count: u32,
+    attractor_markers: bool,
+    vector_pipeline: wgpu::RenderPipeline,
+    vector_bind_group: wgpu::BindGroup,
+    vector_buffer: wgpu::Buffer,
+    velocity_vectors: bool,
+    vignette_pipeline: wgpu::RenderPipeline,
+    vignette_bind_group: wgpu::BindGroup,
+    vignette_buffer: wgpu::Buffer,
+    vignette: VignetteUniform,
+    dof: DepthOfField,
+    composite: Composite,
+    taa: Taa,
+    size: (u32, u32),
+    max_workgroups_per_dimension: u32,
+}
+
+impl Renderer {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        size: (u32, u32),
+        depth_view: &wgpu::TextureView,
+        simulation: &Simulation,
+        config: &SimulationConfig,
+    ) -> Self {
+        let density_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Density Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/density.wgsl").into()),
+        });
+        let heatmap_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Heatmap Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/heatmap.wgsl").into()),
+        });
+
+        // Shares the particle bind groups, so replay frames can be shown as a heatmap too
+        let density_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Density Pipeline Layout"),
+                bind_group_layouts: &[simulation.render_bind_group_layout()],
+                push_constant_ranges: &[],
+            });
+
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let density_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Density Pipeline"),
+            layout: Some(&density_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &density_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &density_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: DENSITY_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: wgpu::ColorWrites::RED,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::PointList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            // Every particle counts, including hidden ones
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let heatmap_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Heatmap Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let heatmap_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Heatmap Pipeline Layout"),
+                bind_group_layouts: &[&heatmap_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let max_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Density Max Pipeline"),
+            layout: Some(&heatmap_pipeline_layout),
+            module: &heatmap_shader,
+            entry_point: Some("find_max_density"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let colormap_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Heatmap Colormap Pipeline"),
+            layout: Some(&heatmap_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &heatmap_shader,
+                entry_point: Some("vs_fullscreen"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &heatmap_shader,
+                entry_point: Some("fs_heatmap"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let glow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Density Glow Pipeline"),
+            layout: Some(&heatmap_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &heatmap_shader,
+                entry_point: Some("vs_fullscreen"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &heatmap_shader,
+                entry_point: Some("fs_glow"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: wgpu::ColorWrites::COLOR,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let density_glow_strength = config.density_glow_strength.max(0.0);
+        let glow_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Glow Params Buffer"),
+            contents: bytemuck::cast_slice(&[GlowParams {
+                strength: density_glow_strength,
+                _padding: [0; 3],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let max_density_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Max Density Buffer"),
+            size: std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let (marker_pipeline, marker_bind_group, marker_uniform_buffer, attractor_buffer) =
+            Self::create_markers(device, surface_format);
+
+        let (vector_pipeline, vector_bind_group, vector_buffer) =
+            Self::create_vectors(device, surface_format, simulation);
+
+        let vignette = VignetteUniform {
+            strength: config.vignette_strength.clamp(0.0, 1.0),
+            radius: config.vignette_radius.max(0.0),
+            aspect: size.0 as f32 / size.1.max(1) as f32,
+            _padding: 0,
+        };
+        let (vignette_pipeline, vignette_bind_group, vignette_buffer) =
+            Self::create_vignette(device, surface_format, vignette);
+
+        let density_view = Self::create_density_view(device, size);
+        let heatmap_bind_group = Self::create_heatmap_bind_group(
+            device,
+            &heatmap_bind_group_layout,
+            &density_view,
+            &max_density_buffer,
+            &glow_params_buffer,
+        );
+
+        Self {
+            mode: config.render_mode,
+            density_pipeline,
+            max_pipeline,
+            colormap_pipeline,
+            glow_pipeline,
+            heatmap_bind_group_layout,
+            heatmap_bind_group,
+            density_view,
+            max_density_buffer,
+            glow_params_buffer,
+            density_glow_strength,
+            marker_pipeline,
+            marker_bind_group,
+            marker_uniform_buffer,
+            attractor_buffer,
+            attractor_count: 0,
+            attractor_markers: config.attractor_markers,
+            vector_pipeline,
+            vector_bind_group,
+            vector_buffer,
+            velocity_vectors: false,
+            vignette_pipeline,
+            vignette_bind_group,
+            vignette_buffer,
+            vignette,
+            dof: DepthOfField::new(
+                device,
+                surface_format,
+                size,
+                depth_view,
+                config.dof_aperture,
+                config.dof_focus_distance,
+            ),
+            composite: Composite::new(
+                device,
+                surface_format,
+                size,
+                config.ambient_floor,
+                config.gamma,
+                config.dither_strength,
+            ),
+            taa: Taa::new(device, surface_format, size, config.taa),
+            size,
+            max_workgroups_per_dimension: device.limits().max_compute_workgroups_per_dimension,
+        }
+    }
+
+    fn create_vignette(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        vignette: VignetteUniform,
+    ) -> (wgpu::RenderPipeline, wgpu::BindGroup, wgpu::Buffer) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Vignette Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/vignette.wgsl").into()),
+        });
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vignette Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[vignette]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Vignette Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Vignette Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Vignette Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // dst * src: the shader's output scales what is already in the target
+        let multiply = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::Src,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Vignette Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_fullscreen"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_vignette"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState {
+                        color: multiply,
+                        alpha: wgpu::BlendComponent::REPLACE,
+                    }),
+                    write_mask: wgpu::ColorWrites::COLOR,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        (pipeline, bind_group, buffer)
+    }
+
+    fn create_vectors(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        simulation: &Simulation,
+    ) -> (wgpu::RenderPipeline, wgpu::BindGroup, wgpu::Buffer) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Velocity Vector Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/vectors.wgsl").into()),
+        });
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Velocity Vector Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[VectorUniform {
+                scale: 0.0,
+                stride: VECTOR_STRIDE,
+                _padding: [0; 2],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Velocity Vector Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Velocity Vector Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        // Reads the drawn particle set through the particle bind groups, like the density pass
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Velocity Vector Pipeline Layout"),
+            bind_group_layouts: &[simulation.render_bind_group_layout(), &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Velocity Vector Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        (pipeline, bind_group, buffer)
+    }
+
+    fn create_markers(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+    ) -> (
+        wgpu::RenderPipeline,
+        wgpu::BindGroup,
+        wgpu::Buffer,
+        wgpu::Buffer,
+    ) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Attractor Marker Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/markers.wgsl").into()),
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Attractor Marker Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[MarkerUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let attractor_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Attractor Buffer"),
+            size: (MAX_ATTRACTORS * std::mem::size_of::<[f32; 4]>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Attractor Marker Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Attractor Marker Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: attractor_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Attractor Marker Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Attractor Marker Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState::default(),
+            // Markers are a navigation aid, so they stay visible through everything
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        (pipeline, bind_group, uniform_buffer, attractor_buffer)
+    }
+
+    fn create_density_view(device: &wgpu::Device, size: (u32, u32)) -> wgpu::TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Density Texture"),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: DENSITY_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_heatmap_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        density_view: &wgpu::TextureView,
+        max_density_buffer: &wgpu::Buffer,
+        glow_params_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Heatmap Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(density_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: max_density_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: glow_params_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn mode(&self) -> RenderMode {
+        self.mode
+    }
+
+    // (strength, radius) of the vignette
+    pub fn vignette(&self) -> (f32, f32) {
+        (self.vignette.strength, self.vignette.radius)
+    }
+
+    pub fn density_glow_strength(&self) -> f32 {
+        self.density_glow_strength
+    }
+
+    // (ambient floor, gamma, dither strength) of the composite pass
+    pub fn composite_settings(&self) -> (f32, f32, f32) {
+        self.composite.settings()
+    }
+
+    pub fn set_mode(&mut self, mode: RenderMode) {
+        self.mode = mode;
+    }
+
+    // `strength` in 0..1 (0 disables the pass); `radius` is where darkening
+    // starts, in half-heights of the screen from its center
+    pub fn set_vignette(&mut self, queue: &wgpu::Queue, strength: f32, radius: f32) {
+        self.vignette.strength = strength.clamp(0.0, 1.0);
+        self.vignette.radius = radius.max(0.0);
+        self.write_vignette(queue);
+    }
+
+    fn write_vignette(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.vignette_buffer,
+            0,
+            bytemuck::cast_slice(&[self.vignette]),
+        );
+    }
+
+    pub fn set_attractor_markers(&mut self, enabled: bool) {
+        self.attractor_markers = enabled;
+    }
+
+    // Upload the attractors and camera for `render_markers`
+    pub fn update_markers(
+        &mut self,
+        queue: &wgpu::Queue,
+        camera: &Camera,
+        attractors: &[Attractor],
+    ) {
+        if !self.attractor_markers {
+            return;
+        }
+
+        let data: Vec<[f32; 4]> = attractors
+            .iter()
+            .take(MAX_ATTRACTORS)
+            .map(|a| [a.position[0], a.position[1], a.position[2], a.mass])
+            .collect();
+        self.attractor_count = data.len() as u32;
+        if !data.is_empty() {
+            queue.write_buffer(&self.attractor_buffer, 0, bytemuck::cast_slice(&data));
+        }
+
+        let (right, up) = camera.view_basis();
+        let uniform = MarkerUniform {
+            view_proj: camera.build_view_projection_matrix().into(),
+            camera_right: [right.x, right.y, right.z, 0.0],
+            camera_up: [up.x, up.y, up.z, 0.0],
+            size: MARKER_SIZE,
+            reference_mass: MARKER_REFERENCE_MASS,
+            _padding: [0; 2],
+        };
+        queue.write_buffer(
+            &self.marker_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[uniform]),
+        );
+    }
+
+    // One marker per attractor, drawn into the regular particle pass
+    fn render_markers<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if !self.attractor_markers || self.attractor_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.marker_pipeline);
+        render_pass.set_bind_group(0, &self.marker_bind_group, &[]);
+        render_pass.draw(0..6, 0..self.attractor_count);
+    }
+
+    // Lines of `scale` world units per unit of speed
+    pub fn set_velocity_vectors(&mut self, queue: &wgpu::Queue, enabled: bool, scale: f32) {
+        self.velocity_vectors = enabled;
+        let uniform = VectorUniform {
+            scale,
+            stride: VECTOR_STRIDE,
+            _padding: [0; 2],
+        };
+        queue.write_buffer(&self.vector_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // A velocity line for every VECTOR_STRIDE-th particle of the drawn set,
+    // drawn into the regular particle pass
+    fn render_velocity_vectors<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        simulation: &'a Simulation,
+    ) {
+        if !self.velocity_vectors {
+            return;
+        }
+
+        let (bind_group, particle_count) = simulation.drawn_particles();
+        render_pass.set_pipeline(&self.vector_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, &self.vector_bind_group, &[]);
+        render_pass.draw(0..2, 0..particle_count / VECTOR_STRIDE);
+    }
+
+    // Whether the particle view gets the density glow pass
+    fn density_glow_enabled(&self) -> bool {
+        self.density_glow_strength > 0.0
+    }
+
+    pub fn set_density_glow_strength(&mut self, queue: &wgpu::Queue, strength: f32) {
+        self.density_glow_strength = strength.max(0.0);
+        let params = GlowParams {
+            strength: self.density_glow_strength,
+            _padding: [0; 3],
+        };
+        queue.write_buffer(&self.glow_params_buffer, 0, bytemuck::cast_slice(&[params]));
+    }
+
+    // The density texture, depth of field, TAA and composite targets and
+    // vignette aspect follow the surface size
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        depth_view: &wgpu::TextureView,
+    ) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.dof.resize(device, (width, height), depth_view);
+        if (width, height) == self.size {
+            return;
+        }
+
+        self.size = (width, height);
+        self.composite.resize(device, self.size);
+        self.taa.resize(device, self.size);
+        self.vignette.aspect = width as f32 / height as f32;
+        self.write_vignette(queue);
+        self.density_view = Self::create_density_view(device, self.size);
+        self.heatmap_bind_group = Self::create_heatmap_bind_group(
+            device,
+            &self.heatmap_bind_group_layout,
+            &self.density_view,
+            &self.max_density_buffer,
+            &self.glow_params_buffer,
+        );
+    }
+
+    // Accumulate the particle density and find its peak, for `render_glow`
+    fn render_density(&self, encoder: &mut wgpu::CommandEncoder, simulation: &Simulation) {
+        {
+            let mut density_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Density Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.density_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            let (bind_group, particle_count) = simulation.drawn_particles();
+            density_pass.set_pipeline(&self.density_pipeline);
+            density_pass.set_bind_group(0, bind_group, &[]);
+            density_pass.draw(0..particle_count, 0..1);
+        }
+
+        encoder.clear_buffer(&self.max_density_buffer, 0, None);
+        {
+            let mut max_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Density Max Pass"),
+                timestamp_writes: None,
+            });
+            max_pass.set_pipeline(&self.max_pipeline);
+            max_pass.set_bind_group(0, &self.heatmap_bind_group, &[]);
+            let (x, y) = dispatch_grid(
+                (self.size.0 * self.size.1).div_ceil(MAX_WORKGROUP_SIZE),
+                self.max_workgroups_per_dimension,
+            );
+            max_pass.dispatch_workgroups(x, y, 1);
+        }
+    }
+
+    // Accumulate, normalize and colormap the particle density into `target`
+    fn render_heatmap(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        simulation: &Simulation,
+        target: &wgpu::TextureView,
+    ) {
+        self.render_density(encoder, simulation);
+
+        let mut colormap_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Heatmap Colormap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        colormap_pass.set_pipeline(&self.colormap_pipeline);
+        colormap_pass.set_bind_group(0, &self.heatmap_bind_group, &[]);
+        colormap_pass.draw(0..3, 0..1);
+    }
+
+    // Add the glow from the density accumulated by `render_density` over `target`
+    fn render_glow(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut glow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Density Glow Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        glow_pass.set_pipeline(&self.glow_pipeline);
+        glow_pass.set_bind_group(0, &self.heatmap_bind_group, &[]);
+        glow_pass.draw(0..3, 0..1);
+    }
+
+    // Whether the particle view is drawn into `scene_view` for `render_depth_of_field`
+    fn depth_of_field_enabled(&self) -> bool {
+        self.dof.enabled()
+    }
+
+    fn scene_view(&self) -> &wgpu::TextureView {
+        self.dof.scene_view()
+    }
+
+    pub fn depth_of_field_focus(&self) -> Option<f32> {
+        self.dof.focus_distance()
+    }
+
+    pub fn set_depth_of_field(&mut self, aperture: f32, focus_distance: Option<f32>) {
+        self.dof.set(aperture, focus_distance);
+    }
+
+    pub fn update_depth_of_field(&self, queue: &wgpu::Queue, camera: &Camera) {
+        self.dof.update(queue, camera);
+    }
+
+    // Blur the particle view from `scene_view` into `target`
+    fn render_depth_of_field(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+    ) {
+        self.dof.render(encoder, target);
+    }
+
+    // Darken the edges of the finished frame in `target`
+    fn render_vignette(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if self.vignette.strength <= 0.0 {
+            return;
+        }
+
+        let mut vignette_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Vignette Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        vignette_pass.set_pipeline(&self.vignette_pipeline);
+        vignette_pass.set_bind_group(0, &self.vignette_bind_group, &[]);
+        vignette_pass.draw(0..3, 0..1);
+    }
+
+    // Whether the frame is drawn into `composite_view` for `render_composite`
+    fn composite_enabled(&self) -> bool {
+        self.composite.enabled()
+    }
+
+    fn composite_view(&self) -> &wgpu::TextureView {
+        self.composite.frame_view()
+    }
+
+    // `ambient_floor` in 0..MAX_AMBIENT_FLOOR is the brightness black is lifted
+    // to; `gamma` above 1 brightens the midtones. 0 and 1 leave it as drawn.
+    pub fn set_composite(&mut self, queue: &wgpu::Queue, ambient_floor: f32, gamma: f32) {
+        self.composite.set(queue, ambient_floor, gamma);
+    }
+
+    pub fn set_dither_strength(&mut self, queue: &wgpu::Queue, strength: f32) {
+        self.composite.set_dither_strength(queue, strength);
+    }
+
+    // Adjust the finished frame from `composite_view` into `target`
+    fn render_composite(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        self.composite.render(encoder, target);
+    }
+
+    // Whether the particle view is drawn into `taa_view` for `render_taa`
+    fn taa_enabled(&self) -> bool {
+        self.taa.enabled()
+    }
+
+    fn taa_view(&self) -> &wgpu::TextureView {
+        self.taa.current_view()
+    }
+
+    pub fn set_taa(&mut self, enabled: bool) {
+        self.taa.set_enabled(enabled);
+    }
+
+    // Jitter the camera for this frame, restarting the accumulation if it
+    // moved or the particles (`restarts`) were replaced; call before the
+    // camera uniforms are written
+    pub fn begin_taa(&mut self, queue: &wgpu::Queue, camera: &mut Camera, restarts: u32) {
+        // The heatmap is not drawn through the TAA targets
+        if self.mode == RenderMode::Heatmap {
+            camera.set_jitter([0.0, 0.0]);
+            self.taa.reset();
+            return;
+        }
+        self.taa.begin(queue, camera, restarts);
+    }
+
+    // Blend the frame in `taa_view` into the history and write it to `target`
+    fn render_taa(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        self.taa.render(encoder, target);
+    }
+
+    // Draw the frame into `target` using the given depth view: the particle
+    // view or heatmap, then the post-processing in its fixed order (TAA,
+    // density glow, depth of field, vignette, composite)
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        simulation: &Simulation,
+        horizon: &Horizon,
+        (target, depth_view): (&wgpu::TextureView, &wgpu::TextureView),
+        clear_color: wgpu::Color,
+    ) {
+        // The composite reads the finished frame, so it is drawn offscreen first
+        let composite = self.composite_enabled();
+        let view = if composite {
+            self.composite_view()
+        } else {
+            target
+        };
+
+        if self.mode() == RenderMode::Heatmap {
+            self.render_heatmap(encoder, simulation, view);
+        } else {
+            let glow = self.density_glow_enabled();
+            if glow {
+                self.render_density(encoder, simulation);
+            }
+
+            // Depth of field needs the particle view in a texture it can read
+            let dof = self.depth_of_field_enabled();
+            let scene = if dof { self.scene_view() } else { view };
+            // TAA resolves the particle view into `scene` once it is drawn
+            let taa = self.taa_enabled();
+            let particles = if taa { self.taa_view() } else { scene };
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: particles,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            simulation.render_pass(&mut render_pass);
+            horizon.render_pass(&mut render_pass);
+            self.render_markers(&mut render_pass);
+            self.render_velocity_vectors(&mut render_pass, simulation);
+            drop(render_pass);
+
+            if taa {
+                self.render_taa(encoder, scene);
+            }
+            if glow {
+                self.render_glow(encoder, scene);
+            }
+            if dof {
+                self.render_depth_of_field(encoder, view);
+            }
+        }
+
+        self.render_vignette(encoder, view);
+        if composite {
+            self.render_composite(encoder, target);
+        }
+    }
+}
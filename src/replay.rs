@@ -0,0 +1,79 @@
+// Ring buffer of recent particle states that can be drawn in place of the live buffer
+
+pub struct ReplaySlot {
+    pub buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub particle_count: u32,
+}
+
+pub struct ReplayBuffer {
+    slots: Vec<ReplaySlot>,
+    // Slot that the next recorded frame is written to
+    next: usize,
+    // Number of slots holding a recorded frame
+    recorded: usize,
+    // Selected frame in chronological order (0 is the oldest recorded frame)
+    selected: Option<usize>,
+}
+
+impl ReplayBuffer {
+    pub fn new(slots: Vec<ReplaySlot>) -> Self {
+        Self {
+            slots,
+            next: 0,
+            recorded: 0,
+            selected: None,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn recorded(&self) -> usize {
+        self.recorded
+    }
+
+    // Copy the current particle state into the next slot, overwriting the oldest frame
+    pub fn record(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::Buffer,
+        particle_count: u32,
+    ) {
+        if self.slots.is_empty() {
+            return;
+        }
+
+        let slot = &mut self.slots[self.next];
+        encoder.copy_buffer_to_buffer(source, 0, &slot.buffer, 0, slot.buffer.size());
+        slot.particle_count = particle_count;
+
+        self.next = (self.next + 1) % self.slots.len();
+        self.recorded = (self.recorded + 1).min(self.slots.len());
+        self.selected = None;
+    }
+
+    // Forget every recorded frame, keeping the allocation
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.recorded = 0;
+        self.selected = None;
+    }
+
+    // Select a recorded frame to draw, clamped to the recorded range
+    pub fn select(&mut self, frame: Option<usize>) {
+        self.selected = match frame {
+            Some(_) if self.recorded == 0 => None,
+            Some(frame) => Some(frame.min(self.recorded - 1)),
+            None => None,
+        };
+    }
+
+    pub fn selected_slot(&self) -> Option<&ReplaySlot> {
+        let frame = self.selected?;
+        let capacity = self.slots.len();
+        let index = (self.next + capacity - self.recorded + frame) % capacity;
+        self.slots.get(index)
+    }
+}
@@ -0,0 +1,44 @@
+// A secondary point mass on a fixed Keplerian orbit about the central black
+// hole, in the disk plane. Its position is solved analytically from the
+// simulated time, so it keeps to its orbit however the particles are stepped;
+// the particles feel its gravity but it does not feel theirs.
+
+// Orbits more eccentric than this are rejected: near-parabolic ones leave the
+// scene and Kepler's equation converges slowly
+pub const MAX_ECCENTRICITY: f32 = 0.95;
+// Newton iterations on Kepler's equation; ample at MAX_ECCENTRICITY
+const KEPLER_ITERATIONS: u32 = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrbitingMass {
+    // Gravitational parameter (G * mass), in the units of the central `gm`
+    pub gm: f32,
+    pub semi_major: f32,
+    pub eccentricity: f32,
+}
+
+impl OrbitingMass {
+    // Position `time` simulated seconds after periapsis, which lies on +X, for
+    // a prograde orbit in the XZ plane around a primary of `primary_gm`
+    pub fn position(&self, primary_gm: f32, time: f64) -> [f32; 3] {
+        let a = self.semi_major as f64;
+        let e = self.eccentricity as f64;
+        let mean_motion = ((primary_gm as f64).max(0.0) / (a * a * a)).sqrt();
+        let mean_anomaly = (mean_motion * time).rem_euclid(std::f64::consts::TAU);
+
+        // Solve E - e sin E = M for the eccentric anomaly
+        let mut eccentric = if e > 0.8 {
+            std::f64::consts::PI
+        } else {
+            mean_anomaly
+        };
+        for _ in 0..KEPLER_ITERATIONS {
+            eccentric -=
+                (eccentric - e * eccentric.sin() - mean_anomaly) / (1.0 - e * eccentric.cos());
+        }
+
+        let x = a * (eccentric.cos() - e);
+        let z = a * (1.0 - e * e).sqrt() * eccentric.sin();
+        [x as f32, 0.0, z as f32]
+    }
+}
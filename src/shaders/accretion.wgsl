@@ -0,0 +1,69 @@
+// Fading camera-facing sprites at recent accretion events
+
+struct AccretionEvent {
+    position: vec3<f32>,
+    stamp: u32, // Simulation frame of the event plus one, 0 for an unused slot
+}
+
+struct Flash {
+    view_proj: mat4x4<f32>,
+    camera_right: vec4<f32>,
+    camera_up: vec4<f32>,
+    frame: u32,
+    flash_frames: u32,
+    size: f32,
+    _padding: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) local: vec2<f32>, // Corner offset in [-1, 1]^2
+    @location(1) intensity: f32,
+}
+
+@group(0) @binding(0) var<uniform> flash: Flash;
+@group(0) @binding(1) var<storage, read> events: array<AccretionEvent>;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let event = events[instance_index];
+
+    var out: VertexOutput;
+    out.local = corner;
+
+    // Wrapping subtraction keeps the age correct across frame counter overflow
+    let age = flash.frame - (event.stamp - 1u);
+    if event.stamp == 0u || age >= flash.flash_frames {
+        out.clip_position = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+        out.intensity = 0.0;
+        return out;
+    }
+
+    let fade = 1.0 - f32(age) / f32(flash.flash_frames);
+    // Sprites swell slightly as they fade
+    let size = flash.size * (1.5 - 0.5 * fade);
+    let world = event.position
+        + (flash.camera_right.xyz * corner.x + flash.camera_up.xyz * corner.y) * size;
+    out.clip_position = flash.view_proj * vec4<f32>(world, 1.0);
+    out.intensity = fade * fade;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let d2 = dot(in.local, in.local);
+    let glow = exp(-d2 * 4.0) * in.intensity;
+    return vec4<f32>(vec3<f32>(1.0, 0.85, 0.6) * glow * 3.0, 0.0);
+}
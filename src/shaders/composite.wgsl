@@ -0,0 +1,34 @@
+// Final viewing adjustment: gamma, then a floor that lifts the shadows while
+// leaving white at white, then a dither that breaks up 8-bit banding
+
+struct Composite {
+    ambient_floor: f32, // Brightness that black is lifted to, 0..1
+    gamma: f32,         // Display gamma; above 1 brightens the midtones
+    dither_strength: f32, // Peak-to-peak noise in 8-bit steps
+    _padding0: u32,
+}
+
+@group(0) @binding(0) var frame: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> composite: Composite;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let color = textureLoad(frame, vec2<i32>(position.xy), 0);
+    let graded = pow(max(color.rgb, vec3<f32>(0.0)), vec3<f32>(1.0 / composite.gamma));
+    // Same lift for alpha, so a transparent canvas shows the floor too
+    let lift = composite.ambient_floor;
+    let lifted = mix(vec3<f32>(lift), vec3<f32>(1.0), graded);
+    // Interleaved gradient noise: an ordered pattern without the visible grid
+    // of a Bayer matrix, applied last so nothing re-quantizes after it
+    let noise = fract(52.9829189 * fract(dot(position.xy, vec2<f32>(0.06711056, 0.00583715))));
+    // Scaled by alpha so a transparent canvas stays clear
+    let alpha = mix(lift, 1.0, color.a);
+    let dither = (noise - 0.5) * composite.dither_strength / 255.0 * alpha;
+    return vec4<f32>(lifted + dither, alpha);
+}
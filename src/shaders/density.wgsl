@@ -0,0 +1,34 @@
+// Accumulates particle counts per pixel into a density texture
+
+struct Particle {
+    position: vec3<f32>,
+    group: u32,
+    velocity: vec3<f32>,
+    flags: u32, // Bit 0: absorbed by the black hole
+    acceleration: vec3<f32>, // From the latest physics step
+    temperature: f32, // Heated near the black hole, cooling over time
+}
+
+struct Camera {
+    transform: mat4x4<f32>,
+    position: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var<storage, read> particles: array<Particle>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let particle = particles[vertex_index];
+    // Absorbed particles no longer count
+    if (particle.flags & 1u) != 0u {
+        return vec4<f32>(0.0, 0.0, 2.0, 1.0);
+    }
+    return camera.transform * vec4<f32>(particle.position, 1.0);
+}
+
+// Each particle adds one to its pixel through additive blending
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 0.0);
+}
@@ -0,0 +1,70 @@
+// Gather depth of field: every pixel averages the neighbours whose circle of
+// confusion reaches it, so out-of-focus particles spread over their surroundings
+
+struct DepthOfField {
+    focus_distance: f32, // World units from the eye that stay sharp
+    aperture: f32,       // Circle of confusion in pixels far from the focus
+    near: f32,           // Projection clip planes, for linearizing depth
+    far: f32,
+    max_radius: f32,     // Largest blur radius in pixels
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+@group(0) @binding(0) var scene: texture_2d<f32>;
+@group(0) @binding(1) var depth: texture_depth_2d;
+@group(0) @binding(2) var<uniform> dof: DepthOfField;
+
+const TAPS: u32 = 32u;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Eye distance from the stored depth of the camera's GL-style projection
+fn linear_depth(d: f32) -> f32 {
+    let n = dof.near;
+    let f = dof.far;
+    return 2.0 * f * n / max((f + n) - d * (f - n), 1e-6);
+}
+
+fn circle_of_confusion(distance: f32) -> f32 {
+    let coc = dof.aperture * abs(1.0 - dof.focus_distance / distance);
+    return min(coc, dof.max_radius);
+}
+
+@fragment
+fn fs_dof(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(scene));
+    let center = vec2<i32>(position.xy);
+    let center_distance = linear_depth(textureLoad(depth, center, 0));
+    let center_coc = circle_of_confusion(center_distance);
+
+    var sum = textureLoad(scene, center, 0).rgb;
+    var weight = 1.0;
+    for (var i = 0u; i < TAPS; i++) {
+        // Vogel disk spiral covering the largest blur radius
+        let r = dof.max_radius * sqrt((f32(i) + 0.5) / f32(TAPS));
+        let theta = f32(i) * GOLDEN_ANGLE;
+        let offset = vec2<f32>(cos(theta), sin(theta)) * r;
+        let pixel = clamp(center + vec2<i32>(round(offset)), vec2<i32>(0), size - 1);
+
+        // A tap counts only if its own blur reaches this far. Taps behind this
+        // pixel are limited by its blur, so the background never bleeds over
+        // sharp particles in front of it.
+        let tap_distance = linear_depth(textureLoad(depth, pixel, 0));
+        var coc = circle_of_confusion(tap_distance);
+        if tap_distance > center_distance {
+            coc = min(coc, center_coc);
+        }
+        let w = smoothstep(r - 1.0, r + 1.0, coc);
+        sum += textureLoad(scene, pixel, 0).rgb * w;
+        weight += w;
+    }
+
+    return vec4<f32>(sum / weight, 1.0);
+}
@@ -0,0 +1,96 @@
+// Finds the peak density and maps the density texture through a colormap, or
+// turns it into a soft glow over the regular particle view
+
+@group(0) @binding(0) var density: texture_2d<f32>;
+// Largest density as f32 bits; non-negative floats order the same as their bits
+@group(0) @binding(1) var<storage, read_write> max_density: atomic<u32>;
+
+struct GlowParams {
+    strength: f32, // 0 disables the glow
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+@group(0) @binding(2) var<uniform> glow: GlowParams;
+
+// One invocation per pixel, in row-major order
+@compute @workgroup_size(64)
+fn find_max_density(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let size = textureDimensions(density);
+    let index = gid.x + gid.y * num_workgroups.x * 64u;
+    if index >= size.x * size.y {
+        return;
+    }
+
+    let pixel = vec2<i32>(i32(index % size.x), i32(index / size.x));
+    let value = textureLoad(density, pixel, 0).r;
+    if value > 0.0 {
+        atomicMax(&max_density, bitcast<u32>(value));
+    }
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// Single triangle covering the whole screen
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// Polynomial fit of the inferno colormap
+fn inferno(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.0002189403691192265, 0.001651004631001012, -0.01948089843709184);
+    let c1 = vec3<f32>(0.1065134194856116, 0.5639564367884091, 3.932712388889277);
+    let c2 = vec3<f32>(11.60249308247187, -3.972853965665698, -15.9423941062914);
+    let c3 = vec3<f32>(-41.70399613139459, 17.43639888205313, 44.35414519872813);
+    let c4 = vec3<f32>(77.162935699427, -33.40235894210092, -81.80730925738993);
+    let c5 = vec3<f32>(-71.31942824499214, 32.62606426397723, 73.20951985803202);
+    let c6 = vec3<f32>(25.13112622477341, -12.24266895238567, -23.07032500287172);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+@fragment
+fn fs_heatmap(in: VertexOutput) -> @location(0) vec4<f32> {
+    let value = textureLoad(density, vec2<i32>(in.clip_position.xy), 0).r;
+    let peak = max(bitcast<f32>(atomicLoad(&max_density)), 1.0);
+
+    // Logarithmic scale so sparse regions stay visible next to the dense core
+    let t = clamp(log(1.0 + value) / log(1.0 + peak), 0.0, 1.0);
+    return vec4<f32>(clamp(inferno(t), vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+}
+
+// Spacing of the glow taps, in pixels
+const GLOW_RADIUS: i32 = 3;
+const GLOW_COLOR: vec3<f32> = vec3<f32>(1.0, 0.8, 0.6);
+
+// Added on top of the particles: brightness grows with the local density,
+// gathered over a small neighbourhood so the glow spreads past single pixels
+@fragment
+fn fs_glow(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(density));
+    let center = vec2<i32>(in.clip_position.xy);
+
+    var sum = 0.0;
+    var weights = 0.0;
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let texel = clamp(center + vec2<i32>(x, y) * GLOW_RADIUS, vec2<i32>(0), size - 1);
+            let weight = exp(-f32(x * x + y * y) * 0.5);
+            sum += textureLoad(density, texel, 0).r * weight;
+            weights += weight;
+        }
+    }
+
+    let peak = max(bitcast<f32>(atomicLoad(&max_density)), 1.0);
+    let t = clamp(log(1.0 + sum / weights) / log(1.0 + peak), 0.0, 1.0);
+    return vec4<f32>(GLOW_COLOR * t * glow.strength, 0.0);
+}
@@ -0,0 +1,58 @@
+// Camera-facing billboard drawing the event horizon silhouette and photon ring.
+// Coverage is computed analytically per fragment, so the edge is antialiased
+// without multisampling the whole scene.
+
+struct Horizon {
+    view_proj: mat4x4<f32>,
+    camera_right: vec4<f32>,
+    camera_up: vec4<f32>,
+    ring_color: vec4<f32>,
+    radius: f32,
+    ring_width: f32,
+    ring_intensity: f32,
+    extent: f32, // Half-size of the billboard in world units
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) local: vec2<f32>, // Offset from the center in world units
+}
+
+@group(0) @binding(0) var<uniform> horizon: Horizon;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // Two triangles covering [-1, 1]^2
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let local = corners[vertex_index] * horizon.extent;
+    let world = horizon.camera_right.xyz * local.x + horizon.camera_up.xyz * local.y;
+
+    var out: VertexOutput;
+    out.clip_position = horizon.view_proj * vec4<f32>(world, 1.0);
+    out.local = local;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let d = length(in.local);
+
+    // Smooth coverage across roughly one pixel at the silhouette edge
+    let aa = max(fwidth(d), 1e-4);
+    let coverage = 1.0 - smoothstep(horizon.radius - aa, horizon.radius + aa, d);
+
+    // Photon ring: a soft glow hugging the outside of the horizon
+    let outside = smoothstep(horizon.radius - aa, horizon.radius + aa, d);
+    let ring_offset = (d - horizon.radius) / max(horizon.ring_width, 1e-4);
+    let ring = horizon.ring_intensity * exp(-ring_offset * ring_offset) * outside;
+
+    // Premultiplied: the disk blacks out what is behind it, the ring adds light
+    return vec4<f32>(horizon.ring_color.rgb * ring, coverage);
+}
@@ -0,0 +1,59 @@
+// Crosshair glyphs marking each attractor, sized by its mass
+
+struct Markers {
+    view_proj: mat4x4<f32>,
+    camera_right: vec4<f32>,
+    camera_up: vec4<f32>,
+    size: f32, // Half-size in world units of the marker for the reference mass
+    reference_mass: f32,
+    _padding0: u32,
+    _padding1: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) local: vec2<f32>, // Corner offset in [-1, 1]^2
+}
+
+@group(0) @binding(0) var<uniform> markers: Markers;
+// xyz: position, w: mass (gravitational parameter)
+@group(0) @binding(1) var<storage, read> attractors: array<vec4<f32>>;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let attractor = attractors[instance_index];
+
+    // Area grows with mass
+    let size = markers.size * sqrt(max(attractor.w, 0.0) / markers.reference_mass);
+    let world = attractor.xyz
+        + (markers.camera_right.xyz * corner.x + markers.camera_up.xyz * corner.y) * size;
+
+    var out: VertexOutput;
+    out.clip_position = markers.view_proj * vec4<f32>(world, 1.0);
+    out.local = corner;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let r = length(in.local);
+    let ring = r > 0.85 && r < 1.0;
+    // Cross arms with a gap in the middle, so the mass itself stays visible
+    let arm = min(abs(in.local.x), abs(in.local.y)) < 0.06 && r > 0.35 && r < 1.0;
+    if !(ring || arm) {
+        discard;
+    }
+    return vec4<f32>(0.4, 0.95, 1.0, 0.9);
+}
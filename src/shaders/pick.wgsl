@@ -0,0 +1,70 @@
+// Compute shader finding the particle closest to the cursor on screen
+struct Particle {
+    position: vec3<f32>,
+    group: u32,
+    velocity: vec3<f32>,
+    flags: u32, // Bit 0: absorbed by the black hole
+    acceleration: vec3<f32>, // From the latest physics step
+    temperature: f32, // Heated near the black hole, cooling over time
+}
+
+struct Camera {
+    transform: mat4x4<f32>,
+    position: vec4<f32>,
+}
+
+struct PickParams {
+    cursor: vec2<f32>, // Canvas pixels, origin top-left
+    viewport: vec2<f32>,
+    radius: f32, // Pixels
+    particle_count: u32,
+    _padding0: u32,
+    _padding1: u32,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var<storage, read> particles: array<Particle>;
+@group(0) @binding(2) var<uniform> params: PickParams;
+// Quantized distance in the high bits, particle index in the low bits, so the
+// smallest key is the closest particle; 0xffffffff when nothing is in range
+@group(0) @binding(3) var<storage, read_write> result: atomic<u32>;
+
+const INDEX_BITS: u32 = 17u;
+const DISTANCE_LEVELS: f32 = 32767.0;
+
+@compute @workgroup_size(64)
+fn pick(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let index = gid.x + gid.y * num_workgroups.x * 64u;
+    if index >= params.particle_count {
+        return;
+    }
+
+    let particle = particles[index];
+    if (particle.flags & 1u) != 0u {
+        return;
+    }
+
+    let clip = camera.transform * vec4<f32>(particle.position, 1.0);
+    if clip.w <= 0.0 {
+        return;
+    }
+    let ndc = clip.xyz / clip.w;
+    if ndc.z < 0.0 || ndc.z > 1.0 {
+        return;
+    }
+
+    let screen = vec2<f32>(
+        (ndc.x * 0.5 + 0.5) * params.viewport.x,
+        (0.5 - ndc.y * 0.5) * params.viewport.y,
+    );
+    let d = distance(screen, params.cursor);
+    if d > params.radius {
+        return;
+    }
+
+    let key = (u32(d / params.radius * DISTANCE_LEVELS) << INDEX_BITS) | index;
+    atomicMin(&result, key);
+}
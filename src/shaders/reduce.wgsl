@@ -0,0 +1,121 @@
+// Compute shader reducing particle positions to bounds and a centroid, and the
+// particles' energy to its kinetic and potential totals. Absorbed particles have
+// left the system and only add to the absorbed count.
+struct Particle {
+    position: vec3<f32>,
+    group: u32,
+    velocity: vec3<f32>,
+    flags: u32, // Bit 0: absorbed by the black hole
+    acceleration: vec3<f32>, // From the latest physics step
+    temperature: f32, // Heated near the black hole, cooling over time
+}
+
+struct ReduceParams {
+    particle_count: u32,
+    partial_count: u32,
+    gm: f32,
+    _padding: u32,
+    halo: vec4<f32>, // As in update.wgsl: x: squared halo speed, y: squared core radius
+}
+
+struct Partial {
+    min_position: vec4<f32>,
+    max_position: vec4<f32>,
+    sum: vec4<f32>,    // xyz: summed positions, w: number of particles not absorbed
+    energy: vec4<f32>, // x: kinetic, y: potential around the black hole, z: absorbed count, w: unused
+}
+
+@group(0) @binding(0) var<storage, read> particles: array<Particle>;
+@group(0) @binding(1) var<uniform> params: ReduceParams;
+@group(0) @binding(2) var<storage, read_write> partials: array<Partial>;
+@group(0) @binding(3) var<storage, read_write> result: Partial;
+
+const WORKGROUP_SIZE: u32 = 256u;
+const FLAG_ABSORBED: u32 = 1u;
+// Keeps the potential of particles right at the center finite
+const POTENTIAL_SOFTENING: f32 = 1.0;
+
+var<workgroup> scratch: array<Partial, WORKGROUP_SIZE>;
+
+fn empty_partial() -> Partial {
+    return Partial(vec4<f32>(1e30), vec4<f32>(-1e30), vec4<f32>(0.0), vec4<f32>(0.0));
+}
+
+fn combine(a: Partial, b: Partial) -> Partial {
+    return Partial(
+        min(a.min_position, b.min_position),
+        max(a.max_position, b.max_position),
+        a.sum + b.sum,
+        a.energy + b.energy
+    );
+}
+
+// Tree reduction of the workgroup's scratch values into scratch[0]
+fn reduce_scratch(local_index: u32) {
+    var stride = WORKGROUP_SIZE / 2u;
+    while stride > 0u {
+        if local_index < stride {
+            scratch[local_index] = combine(scratch[local_index], scratch[local_index + stride]);
+        }
+        workgroupBarrier();
+        stride = stride / 2u;
+    }
+}
+
+// Pass 1: each workgroup reduces its slice of particles into one partial
+@compute @workgroup_size(256)
+fn reduce_particles(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_id) lid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let index = gid.x + gid.y * num_workgroups.x * 256u;
+    var value = empty_partial();
+    if index < params.particle_count {
+        let particle = particles[index];
+        let position = particle.position;
+        if (particle.flags & FLAG_ABSORBED) != 0u {
+            value.energy.z = 1.0;
+        } else {
+            let kinetic = 0.5 * dot(particle.velocity, particle.velocity);
+            // The halo's share is zero at the center
+            let r2 = dot(position, position);
+            let potential = -params.gm / max(sqrt(r2), POTENTIAL_SOFTENING)
+                + 0.5 * params.halo.x * log(1.0 + r2 / params.halo.y);
+            value = Partial(
+                vec4<f32>(position, 0.0),
+                vec4<f32>(position, 0.0),
+                vec4<f32>(position, 1.0),
+                vec4<f32>(kinetic, potential, 0.0, 0.0)
+            );
+        }
+    }
+
+    scratch[lid.x] = value;
+    workgroupBarrier();
+    reduce_scratch(lid.x);
+
+    // The last row of the grid can run past the partials buffer
+    let partial_index = wid.x + wid.y * num_workgroups.x;
+    if lid.x == 0u && partial_index < params.partial_count {
+        partials[partial_index] = scratch[0];
+    }
+}
+
+// Pass 2: a single workgroup folds all partials into the final result
+@compute @workgroup_size(256)
+fn reduce_partials(@builtin(local_invocation_id) lid: vec3<u32>) {
+    var value = empty_partial();
+    for (var i = lid.x; i < params.partial_count; i = i + WORKGROUP_SIZE) {
+        value = combine(value, partials[i]);
+    }
+
+    scratch[lid.x] = value;
+    workgroupBarrier();
+    reduce_scratch(lid.x);
+
+    if lid.x == 0u {
+        result = scratch[0];
+    }
+}
@@ -1,49 +1,255 @@
-// Vertex and fragment shaders for rendering particles as points
+// Vertex and fragment shaders for rendering particles as sprite billboards
 
 struct Particle {
     position: vec3<f32>,
+    group: u32,
     velocity: vec3<f32>,
+    flags: u32, // Bit 0: absorbed by the black hole
+    acceleration: vec3<f32>, // From the latest physics step
+    temperature: f32, // Heated near the black hole, cooling over time
 }
 
 struct Camera {
     transform: mat4x4<f32>,
+    position: vec4<f32>, // World-space eye position
+}
+
+struct RenderParams {
+    color_mode: u32, // 0: speed, 1: group tint, 2: acceleration, 3: temperature, 4: radial bands
+    clip_enabled: u32,
+    fog_density: f32,
+    highlight_index: u32, // 0xffffffff for none
+    clip_plane: vec4<f32>, // xyz: normal, w: distance
+    fog_color: vec4<f32>,
+    set_tint: vec4<f32>, // rgb: tint for this particle set, a: how strongly it is applied
+    group_tints: array<vec4<f32>, 2>,
+    render_alpha: f32, // 0: previous physics state, 1: current
+    particle_alpha: f32,
+    inner_glow_radius: f32,
+    inner_glow_strength: f32,
+    inner_glow_color: vec3<f32>,
+    inner_glow_falloff: f32,
+    viewport: vec2<f32>, // Pixels
+    sprite_size: f32, // Pixels
+    size_attenuation: u32, // Nonzero: sprite_size applies at sprite_reference_distance
+    sprite_reference_distance: f32,
+    min_sprite_size: f32, // Pixels
+    color_mode_b: u32, // Mixed into color_mode by color_blend
+    color_blend: f32,
+    min_screen_size: f32, // Pixels; smaller particles are culled (0 disables culling)
+    radial_bands: f32, // Color rings per doubling of radius
+    layer_group_masks: vec2<u32>, // Groups drawn by each layer pass, back to front
+    colormap_enabled: u32, // Nonzero: scalar color modes look their color up in colormap_texture
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
     @location(1) velocity_magnitude: f32,
+    @location(2) view_distance: f32,
+    @location(3) @interpolate(flat) highlighted: u32,
+    @location(4) inner_glow: f32,
+    @location(5) uv: vec2<f32>,
+    @location(6) fade: f32,
 }
 
 @group(0) @binding(0) var<uniform> camera: Camera;
 @group(0) @binding(1) var<storage, read> particles: array<Particle>;
+@group(0) @binding(2) var<uniform> render_params: RenderParams;
+@group(0) @binding(3) var<storage, read> previous_particles: array<Particle>;
+@group(1) @binding(0) var sprite_texture: texture_2d<f32>;
+@group(1) @binding(1) var sprite_sampler: sampler;
+@group(2) @binding(0) var colormap_texture: texture_2d<f32>; // One row of palette entries
+@group(2) @binding(1) var colormap_sampler: sampler;
+
+// Particles fade out over sizes from this multiple of min_screen_size down to
+// it, so culling does not pop
+const CULL_FADE_BAND: f32 = 1.5;
+
+// Two triangles covering the billboard, in -1..1
+const QUAD_CORNERS = array<vec2<f32>, 6>(
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, 1.0),
+);
 
 @vertex
-fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
-    let particle = particles[vertex_index];
+fn vs_main(
+    @builtin(vertex_index) corner_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    // Each layer pass draws the whole set, offset by a multiple of its size
+    let particle_count = arrayLength(&particles);
+    let layer = instance_index / particle_count;
+    let vertex_index = instance_index % particle_count;
+    var particle = particles[vertex_index];
+    // Interpolate between the last two physics steps, except across a wrap of
+    // the box, which would streak the particle through the whole view
+    if (particle.flags & 2u) == 0u {
+        particle.position = mix(
+            previous_particles[vertex_index].position,
+            particle.position,
+            render_params.render_alpha,
+        );
+    }
 
     let world_position = vec4<f32>(particle.position, 1.0);
-    let clip_position = camera.transform * world_position;
+    var clip_position = camera.transform * world_position;
 
-    let velocity_magnitude = length(particle.velocity);
-    let normalized_speed = min(velocity_magnitude / 200.0, 1.0);
-    
-    // Color: blue (slow) -> red (fast)
-    let color = vec3<f32>(
-        normalized_speed * 2.0,
-        0.1,
-        1.0 - normalized_speed
+    // Particles beyond the clip plane are moved outside the view volume
+    let plane = render_params.clip_plane;
+    if render_params.clip_enabled != 0u && dot(plane.xyz, particle.position) > plane.w {
+        clip_position = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+    }
+
+    // Absorbed particles are no longer drawn, nor are groups of other layers
+    let layer_mask = render_params.layer_group_masks[min(layer, 1u)];
+    if (particle.flags & 1u) != 0u || (layer_mask & (1u << min(particle.group, 1u))) == 0u {
+        clip_position = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+    }
+
+    // Expand to a screen-aligned quad, either of a constant size in pixels or
+    // shrinking with the distance from the eye (clip w is the view depth)
+    var sprite_size = render_params.sprite_size;
+    var fade = 1.0;
+    if render_params.size_attenuation != 0u {
+        let projected_size =
+            sprite_size * render_params.sprite_reference_distance / max(clip_position.w, 1e-3);
+        sprite_size = max(projected_size, render_params.min_sprite_size);
+
+        // Sub-pixel particles far away add overdraw without adding detail
+        let threshold = render_params.min_screen_size;
+        if threshold > 0.0 {
+            fade = smoothstep(threshold, threshold * CULL_FADE_BAND, projected_size);
+            if fade <= 0.0 {
+                clip_position = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+            }
+        }
+    }
+    let corner = QUAD_CORNERS[corner_index];
+    clip_position += vec4<f32>(
+        corner * sprite_size / render_params.viewport * clip_position.w,
+        0.0,
+        0.0,
     );
 
+    let velocity_magnitude = length(particle.velocity);
+    var color = mode_color(render_params.color_mode, particle, velocity_magnitude);
+    if render_params.color_blend > 0.0 {
+        color = mix(
+            color,
+            mode_color(render_params.color_mode_b, particle, velocity_magnitude),
+            render_params.color_blend,
+        );
+    }
+    color = mix(color, render_params.set_tint.rgb, render_params.set_tint.a);
+
     var out: VertexOutput;
     out.clip_position = clip_position;
     out.color = color;
     out.velocity_magnitude = velocity_magnitude;
+    out.view_distance = distance(camera.position.xyz, particle.position);
+    out.highlighted = u32(vertex_index == render_params.highlight_index);
+
+    // Hot inner disk: brighter the closer the particle is to the center
+    var inner_glow = 0.0;
+    if render_params.inner_glow_radius > 0.0 {
+        let falloff = max(1.0 - length(particle.position) / render_params.inner_glow_radius, 0.0);
+        inner_glow = render_params.inner_glow_strength * pow(falloff, render_params.inner_glow_falloff);
+    }
+    out.inner_glow = inner_glow;
+    out.uv = vec2<f32>(corner.x, -corner.y) * 0.5 + 0.5;
+    out.fade = fade;
     return out;
 }
 
+// Approximate color of a black body at `kelvin`, after Tanner Helland's fit
+// of the Planckian locus; cold matter fades towards black below ~1500 K
+fn blackbody(kelvin: f32) -> vec3<f32> {
+    let t = clamp(kelvin, 1000.0, 40000.0) / 100.0;
+    var color = vec3<f32>(1.0);
+    if t <= 66.0 {
+        color.g = 0.3900815788 * log(t) - 0.6318414438;
+        color.b = select(0.5432067891 * log(max(t - 10.0, 1e-3)) - 1.1962540891, 0.0, t <= 19.0);
+    } else {
+        color.r = 1.2929361861 * pow(t - 60.0, -0.1332047592);
+        color.g = 1.1298908609 * pow(t - 60.0, -0.0755148492);
+    }
+    let glow = smoothstep(0.0, 1500.0, kelvin);
+    return clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)) * max(glow, 0.05);
+}
+
+// The quantity a scalar color mode shows, normalized to 0..1
+fn mode_scalar(mode: u32, particle: Particle, velocity_magnitude: f32) -> f32 {
+    if mode == 2u {
+        // Gravity falls off as 1/r^2, so map it logarithmically: 0.1 .. 1000
+        let magnitude = length(particle.acceleration);
+        return clamp((log2(max(magnitude, 1e-6)) / log2(10.0) + 1.0) / 4.0, 0.0, 1.0);
+    }
+    if mode == 3u {
+        // 1000 K .. 100000 K on a log scale
+        return clamp(log2(max(particle.temperature, 1.0) / 1000.0) / log2(100.0), 0.0, 1.0);
+    }
+    if mode == 4u {
+        // Concentric rings evenly spaced in log radius
+        return fract(log2(max(length(particle.position), 1e-3)) * render_params.radial_bands);
+    }
+    return min(velocity_magnitude / 200.0, 1.0);
+}
+
+// Entry `t` (0..1) of the host-supplied palette, interpolated between
+// texel centers so both ends land exactly on the first and last entries
+fn colormap(t: f32) -> vec3<f32> {
+    let entries = f32(textureDimensions(colormap_texture).x);
+    let u = (0.5 + t * (entries - 1.0)) / entries;
+    return textureSampleLevel(colormap_texture, colormap_sampler, vec2<f32>(u, 0.5), 0.0).rgb;
+}
+
+// Particle color under one color mode
+fn mode_color(mode: u32, particle: Particle, velocity_magnitude: f32) -> vec3<f32> {
+    if mode == 1u {
+        return render_params.group_tints[min(particle.group, 1u)].rgb;
+    }
+    let t = mode_scalar(mode, particle, velocity_magnitude);
+    if render_params.colormap_enabled != 0u {
+        return colormap(t);
+    }
+    if mode == 2u {
+        // Deep violet through orange to pale yellow
+        let low = mix(vec3<f32>(0.1, 0.0, 0.3), vec3<f32>(1.0, 0.35, 0.05), min(t * 2.0, 1.0));
+        return mix(low, vec3<f32>(1.0, 0.95, 0.6), max(t * 2.0 - 1.0, 0.0));
+    }
+    if mode == 3u {
+        return blackbody(particle.temperature);
+    }
+    if mode == 4u {
+        // The cyclic palette keeps the seam between one ring and the next invisible
+        return 0.5 + 0.5 * cos(6.2831853 * (t + vec3<f32>(0.0, 0.33, 0.67)));
+    }
+    // Blue (slow) -> red (fast)
+    return vec3<f32>(
+        t * 2.0,
+        0.1,
+        1.0 - t
+    );
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sprite = textureSample(sprite_texture, sprite_sampler, in.uv);
+    // Transparent texels must not write depth and hide particles behind
+    if sprite.a <= 0.01 {
+        discard;
+    }
+
+    // The hovered particle stands out at full brightness, unfogged
+    if in.highlighted != 0u {
+        return vec4<f32>(1.0, 1.0, 1.0, sprite.a);
+    }
+
     let normalized_speed = min(in.velocity_magnitude / 200.0, 1.0);
     
     // Brightness increases with speed
@@ -51,7 +257,12 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let final_color = in.color * brightness;
     
     // Add velocity-dependent glow
-    let glow = vec3<f32>(0.3, 0.3, 0.3) + normalized_speed * vec3<f32>(1.5, 0.0, 0.0);
+    let glow = vec3<f32>(0.3, 0.3, 0.3) + normalized_speed * vec3<f32>(1.5, 0.0, 0.0)
+        + render_params.inner_glow_color * in.inner_glow;
+
+    // Exponential distance fog towards the fog color
+    let fog = 1.0 - exp(-render_params.fog_density * in.view_distance);
+    let fogged_color = mix(final_color + glow, render_params.fog_color.rgb, fog);
 
-    return vec4<f32>(final_color + glow, 0.9);
+    return vec4<f32>(fogged_color * sprite.rgb, render_params.particle_alpha * sprite.a * in.fade);
 }
\ No newline at end of file
@@ -0,0 +1,54 @@
+// Temporal anti-aliasing resolve: blends this frame's jittered render into the
+// accumulated history. History is clamped to the current 3x3 neighborhood and
+// weighted down where it disagrees with the current frame, so moving particles
+// do not leave trails.
+
+struct Taa {
+    blend: f32,              // Weight of the current frame, 1 right after a reset
+    motion_sensitivity: f32, // How quickly disagreement with the history discards it
+    _padding0: u32,
+    _padding1: u32,
+}
+
+@group(0) @binding(0) var current: texture_2d<f32>;
+@group(0) @binding(1) var history: texture_2d<f32>;
+@group(0) @binding(2) var<uniform> taa: Taa;
+
+struct Output {
+    @location(0) color: vec4<f32>,
+    @location(1) history: vec4<f32>,
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_taa(@builtin(position) position: vec4<f32>) -> Output {
+    let pixel = vec2<i32>(position.xy);
+    let size = vec2<i32>(textureDimensions(current)) - 1;
+    let color = textureLoad(current, pixel, 0);
+
+    var low = color;
+    var high = color;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbor = textureLoad(current, clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size), 0);
+            low = min(low, neighbor);
+            high = max(high, neighbor);
+        }
+    }
+
+    let previous = textureLoad(history, pixel, 0);
+    let clamped = clamp(previous, low, high);
+    // Motion shows up as history the current frame no longer supports
+    let disagreement = length(previous - clamped) + length(previous.rgb - color.rgb) * 0.5;
+    let weight = max(taa.blend, saturate(disagreement * taa.motion_sensitivity));
+
+    var out: Output;
+    out.color = mix(clamped, color, weight);
+    out.history = out.color;
+    return out;
+}
@@ -1,64 +1,233 @@
 // Compute shader for updating particle positions and velocities
 struct Particle {
     position: vec3<f32>,
+    group: u32,
     velocity: vec3<f32>,
+    flags: u32, // Bit 0: absorbed by the black hole, bit 1: wrapped by the last step
+    acceleration: vec3<f32>, // From the latest physics step
+    temperature: f32, // Heated near the black hole, cooling over time
 }
 
 struct Params {
     dt: f32,
     gm: f32,        // Gravitational parameter (G * central_mass)
     particle_count: u32,
-    _padding: u32,
+    integrator: u32, // 0: semi-implicit Euler, 1: explicit Euler, 2: leapfrog
+    absorb_radius: f32, // Particles closer to the center are absorbed (0 disables absorption)
+    frame: u32,
+    flash_enabled: u32, // Record accretion events for the flash sprites
+    max_speed: f32, // Speed clamp; 0 or infinity disables it
+    warp_amplitude: f32, // Tilt of the precession axis from vertical, radians
+    precession_rate: f32, // Radians per second at the center; 0 disables the warp
+    heating_rate: f32, // Temperature per second per unit of acceleration
+    cooling_rate: f32, // Fraction of temperature lost per second
+    wrap_bounds: vec3<f32>, // Half-size of the box particles wrap around
+    wrap_enabled: u32, // Nonzero: wrap around the box instead of bouncing
+    secondary: vec4<f32>, // xyz: position of the orbiting mass, w: its gm (0 for none)
+    halo: vec4<f32>, // x: squared asymptotic circular speed of the halo (0 for none), y: squared core radius
+    softening: f32, // Softening length of the central mass's gravity (0 for none)
+    respawn_radius: f32, // Absorbed particles restart on a circular orbit this far out (0 removes them)
+    _padding0: u32,
+    _padding1: u32,
+}
+
+struct AccretionEvent {
+    position: vec3<f32>,
+    stamp: u32, // Simulation frame of the event plus one, 0 for an unused slot
 }
 
 @group(0) @binding(0) var<storage, read_write> particles: array<Particle>;
 @group(0) @binding(1) var<uniform> params: Params;
+@group(0) @binding(2) var<storage, read_write> accretion_events: array<AccretionEvent>;
+@group(0) @binding(3) var<storage, read_write> accretion_cursor: atomic<u32>;
+
+const FLAG_ABSORBED: u32 = 1u;
+const FLAG_WRAPPED: u32 = 2u;
+// Supplied from physics.rs, which runs the same step on the CPU
+override BOUNDARY: f32 = 600.0;
+override BOUNCE_DAMPING: f32 = 0.1;
+override GRAVITY_EPSILON: f32 = 1e-6;
+override WARP_RADIUS: f32 = 150.0;
+override MAX_TEMPERATURE: f32 = 100000.0;
+override SECONDARY_SOFTENING: f32 = 5.0;
+const TAU: f32 = 6.283185307;
+
+// Gravitational acceleration towards center: a = -GM/r^3 * position_vector
+fn gravity(position: vec3<f32>) -> vec3<f32> {
+    // Calculate distance from center (0, 0, 0)
+    // Softened; the epsilon avoids division by zero at the center
+    let r2 = dot(position, position) + params.softening * params.softening + GRAVITY_EPSILON;
+    let r = sqrt(r2);
+    let inv_r = 1.0 / r;
+    let inv_r3 = inv_r * inv_r * inv_r;
+    return -params.gm * inv_r3 * position + halo_gravity(position) + secondary_gravity(position);
+}
+
+// Logarithmic halo potential 0.5 * v^2 * ln(r^2 + core^2), whose circular
+// speed levels off at v outside the core
+fn halo_gravity(position: vec3<f32>) -> vec3<f32> {
+    return -params.halo.x / (dot(position, position) + params.halo.y) * position;
+}
+
+// Pull of the orbiting secondary mass, softened so particles passing through
+// it are not flung out of the scene
+fn secondary_gravity(position: vec3<f32>) -> vec3<f32> {
+    if params.secondary.w == 0.0 {
+        return vec3<f32>(0.0);
+    }
+    let offset = position - params.secondary.xyz;
+    let inv_r = inverseSqrt(dot(offset, offset) + SECONDARY_SOFTENING * SECONDARY_SOFTENING);
+    return -params.secondary.w * inv_r * inv_r * inv_r * offset;
+}
+
+// Clamp velocity to maximum speed, keeping its direction
+fn clamp_speed(velocity: vec3<f32>) -> vec3<f32> {
+    let current_speed = length(velocity);
+    if params.max_speed > 0.0 && current_speed > params.max_speed {
+        return velocity * (params.max_speed / current_speed);
+    }
+    return velocity;
+}
+
+// Rotate `v` by `angle` around the unit vector `axis` (Rodrigues)
+fn rotate(v: vec3<f32>, axis: vec3<f32>, angle: f32) -> vec3<f32> {
+    let c = cos(angle);
+    return v * c + cross(axis, v) * sin(angle) + axis * dot(axis, v) * (1.0 - c);
+}
+
+// Precess the orbit around the tilted warp axis, fastest near the center, so
+// neighbouring rings drift apart in orientation
+fn warp(particle: ptr<function, Particle>) {
+    if params.precession_rate == 0.0 {
+        return;
+    }
+    let axis = vec3<f32>(sin(params.warp_amplitude), cos(params.warp_amplitude), 0.0);
+    let r = length((*particle).position) / WARP_RADIUS;
+    let angle = params.precession_rate / (1.0 + r * r) * params.dt;
+    (*particle).position = rotate((*particle).position, axis, angle);
+    (*particle).velocity = rotate((*particle).velocity, axis, angle);
+}
+
+// Strong gravity heats the particle; it cools exponentially in between
+fn heat(particle: ptr<function, Particle>) {
+    let gained = params.heating_rate * length((*particle).acceleration) * params.dt;
+    let kept = exp(-params.cooling_rate * params.dt);
+    (*particle).temperature = min((*particle).temperature * kept + gained, MAX_TEMPERATURE);
+}
+
+// Integer hash (PCG output permutation), identical in physics.rs
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn circular_speed(r: f32) -> f32 {
+    let r2 = r * r;
+    return sqrt(params.gm / r + params.halo.x * r2 / (r2 + params.halo.y));
+}
+
+// Start an absorbed particle again on a circular orbit at `respawn_radius` in
+// the XZ plane, at an angle hashed from its index and the frame
+fn respawn(particle: ptr<function, Particle>, index: u32) {
+    let seed = hash(index ^ (params.frame * 2654435769u));
+    let angle = f32(seed >> 8u) / 16777216.0 * TAU;
+    let r = params.respawn_radius;
+    let speed = circular_speed(r);
+    (*particle).position = vec3<f32>(r * cos(angle), 0.0, r * sin(angle));
+    (*particle).velocity = vec3<f32>(-sin(angle) * speed, 0.0, cos(angle) * speed);
+    (*particle).acceleration = vec3<f32>(0.0);
+    (*particle).temperature = 0.0;
+}
 
 @compute @workgroup_size(64)
-fn update_particles(@builtin(global_invocation_id) gid: vec3<u32>) {
-    let index = gid.x;
+fn update_particles(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    // Large dispatches are split over rows of the grid; see `dispatch_grid`
+    let index = gid.x + gid.y * num_workgroups.x * 64u;
     if index >= params.particle_count {
         return;
     }
 
     var particle = particles[index];
-    
-    // Calculate distance from center (0, 0, 0)
-    let r2 = dot(particle.position, particle.position) + 1e-6; // Add small epsilon to avoid division by zero
-    let r = sqrt(r2);
-    let inv_r = 1.0 / r;
-    let inv_r3 = inv_r * inv_r * inv_r;
-    
-    // Gravitational acceleration towards center: a = -GM/r^3 * position_vector
-    let acceleration = -params.gm * inv_r3 * particle.position;
+    if (particle.flags & FLAG_ABSORBED) != 0u {
+        return;
+    }
+    let dt = params.dt;
+    particle.flags = particle.flags & ~FLAG_WRAPPED;
 
-    let drag = 1.00; // No energy loss to maintain stable orbits
-    
-    // Euler integration
-    particle.velocity = particle.velocity * drag + acceleration * params.dt;
-    
-    // Clamp velocity to maximum speed
-    let max_velocity = 140.0;
-    let current_speed = length(particle.velocity);
-    if current_speed > max_velocity {
-        particle.velocity = normalize(particle.velocity) * max_velocity;
+    switch params.integrator {
+        case 1u: {
+            // Explicit Euler: position advances with the old velocity
+            let acceleration = gravity(particle.position);
+            particle.position = particle.position + particle.velocity * dt;
+            particle.velocity = clamp_speed(particle.velocity + acceleration * dt);
+            particle.acceleration = acceleration;
+        }
+        case 2u: {
+            // Leapfrog (kick-drift-kick)
+            particle.velocity = particle.velocity + gravity(particle.position) * (0.5 * dt);
+            particle.position = particle.position + particle.velocity * dt;
+            let acceleration = gravity(particle.position);
+            particle.velocity = clamp_speed(particle.velocity + acceleration * (0.5 * dt));
+            particle.acceleration = acceleration;
+        }
+        default: {
+            // Semi-implicit Euler: velocity first, then position with the new velocity
+            let acceleration = gravity(particle.position);
+            let drag = 1.00; // No energy loss to maintain stable orbits
+            particle.velocity = clamp_speed(particle.velocity * drag + acceleration * dt);
+            particle.position = particle.position + particle.velocity * dt;
+            particle.acceleration = acceleration;
+        }
     }
 
-    particle.position = particle.position + particle.velocity * params.dt;
+    warp(&particle);
+    heat(&particle);
+
+    // Absorption: the particle is gone or respawned, optionally leaving a flash
+    // where it crossed
+    if length(particle.position) < params.absorb_radius {
+        if params.flash_enabled != 0u {
+            let slot = atomicAdd(&accretion_cursor, 1u) % arrayLength(&accretion_events);
+            accretion_events[slot] = AccretionEvent(particle.position, params.frame + 1u);
+        }
+        if params.respawn_radius > 0.0 {
+            respawn(&particle, index);
+        } else {
+            particle.flags = particle.flags | FLAG_ABSORBED;
+        }
+        particles[index] = particle;
+        return;
+    }
     
+    // Toroidal wrap: leave through one face, come back through the opposite
+    // one at the same velocity
+    if params.wrap_enabled != 0u {
+        let size = 2.0 * params.wrap_bounds;
+        let wrapped = particle.position - size * floor((particle.position + params.wrap_bounds) / size);
+        if any(wrapped != particle.position) {
+            particle.position = wrapped;
+            particle.flags = particle.flags | FLAG_WRAPPED;
+        }
+        particles[index] = particle;
+        return;
+    }
+
     // Boundary conditions - bounce off edges in 3D
-    let boundary = 600.0;
-    if abs(particle.position.x) > boundary {
-        particle.position.x = sign(particle.position.x) * boundary;
-        particle.velocity.x = -particle.velocity.x * 0.1;
+    if abs(particle.position.x) > BOUNDARY {
+        particle.position.x = sign(particle.position.x) * BOUNDARY;
+        particle.velocity.x = -particle.velocity.x * BOUNCE_DAMPING;
     }
-    if abs(particle.position.y) > boundary {
-        particle.position.y = sign(particle.position.y) * boundary;
-        particle.velocity.y = -particle.velocity.y * 0.1;
+    if abs(particle.position.y) > BOUNDARY {
+        particle.position.y = sign(particle.position.y) * BOUNDARY;
+        particle.velocity.y = -particle.velocity.y * BOUNCE_DAMPING;
     }
-    if abs(particle.position.z) > boundary {
-        particle.position.z = sign(particle.position.z) * boundary;
-        particle.velocity.z = -particle.velocity.z * 0.1;
+    if abs(particle.position.z) > BOUNDARY {
+        particle.position.z = sign(particle.position.z) * BOUNDARY;
+        particle.velocity.z = -particle.velocity.z * BOUNCE_DAMPING;
     }
 
     particles[index] = particle;
@@ -0,0 +1,55 @@
+// Velocity vectors: a line from every `stride`-th particle along its velocity
+
+struct Particle {
+    position: vec3<f32>,
+    group: u32,
+    velocity: vec3<f32>,
+    flags: u32, // Bit 0: absorbed by the black hole
+    acceleration: vec3<f32>, // From the latest physics step
+    temperature: f32, // Heated near the black hole, cooling over time
+}
+
+struct Camera {
+    transform: mat4x4<f32>,
+    position: vec4<f32>,
+}
+
+struct Vectors {
+    scale: f32, // World units per unit of speed
+    stride: u32,
+    _padding0: u32,
+    _padding1: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) along: f32, // 0 at the particle, 1 at the tip
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var<storage, read> particles: array<Particle>;
+@group(1) @binding(0) var<uniform> vectors: Vectors;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let particle = particles[instance_index * vectors.stride];
+    let along = f32(vertex_index);
+    let world = particle.position + particle.velocity * vectors.scale * along;
+
+    var out: VertexOutput;
+    out.clip_position = camera.transform * vec4<f32>(world, 1.0);
+    if (particle.flags & 1u) != 0u {
+        out.clip_position = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+    }
+    out.along = along;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Fade towards the tip, so the direction reads at a glance
+    return vec4<f32>(0.6, 1.0, 0.5, mix(0.9, 0.15, in.along));
+}
@@ -0,0 +1,34 @@
+// Darkens the final image towards the screen edges
+
+struct Vignette {
+    strength: f32, // 0 leaves the image untouched, 1 fades the corners to black
+    radius: f32,   // Distance from the center, in half-heights, where darkening starts
+    aspect: f32,   // Width / height of the target
+    _padding: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> vignette: Vignette;
+
+// Single triangle covering the whole screen
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+// Multiplied into the target by the blend state
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let d = length(vec2<f32>(in.ndc.x * vignette.aspect, in.ndc.y));
+    let corner = length(vec2<f32>(vignette.aspect, 1.0));
+    let t = smoothstep(vignette.radius, max(corner, vignette.radius + 1e-3), d);
+    return vec4<f32>(vec3<f32>(1.0 - vignette.strength * t), 1.0);
+}
@@ -1,17 +1,87 @@
+use crate::accretion::AccretionFlashes;
+use crate::colormap::Colormap;
+use crate::config::{ColorMode, DistributionConfig, Integrator, SimulationConfig};
+#[cfg(debug_assertions)]
+use crate::desync::{BufferPair, DesyncCheck};
+use crate::error::{ErrorScopes, GpuError};
+use crate::orbit::{OrbitStats, OrbitTracker};
+use crate::physics;
+use crate::picking::{ParticlePicker, PICK_INDEX_BITS};
+use crate::poisson;
+use crate::reduction::{PositionReduction, PositionStats};
+use crate::replay::{ReplayBuffer, ReplaySlot};
+use crate::secondary::OrbitingMass;
+use crate::spawn;
+use crate::sprite::ParticleSprite;
 use crate::utils::console_log;
 use bytemuck::{Pod, Zeroable};
+use cgmath::{InnerSpace, Quaternion, Vector3, Zero};
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use wgpu::util::DeviceExt;
 
-const NUM_PARTICLES: u32 = 131072;
+pub const NUM_PARTICLES: u32 = 131072;
 const WORKGROUP_SIZE: u32 = 64;
+// Particles uploaded per frame when streaming the initial state
+const STREAM_CHUNK_SIZE: u32 = 16384;
+// Upper bound on recorded replay frames; each one is a full copy of the particle buffer
+pub const MAX_REPLAY_FRAMES: u32 = 120;
+// Tints telling the two particle sets apart in A/B compare mode
+const COMPARE_TINT_A: [f32; 4] = [1.0, 0.45, 0.15, 0.85];
+const COMPARE_TINT_B: [f32; 4] = [0.15, 0.8, 1.0, 0.85];
+// Most fixed steps taken in one frame; time beyond that is dropped so a long
+// stall does not snowball into ever longer frames
+const MAX_STEPS_PER_FRAME: u32 = 4;
+// Eye distance of the default view, where attenuated sprites have their base size
+const SPRITE_REFERENCE_DISTANCE: f32 = 800.0 / 3.0;
+// Attenuated sprites stop shrinking at this many pixels, so distant ones stay visible
+const MIN_SPRITE_SIZE: f32 = 1.0;
+// Upper bound on the fast-forward multiplier, so a frame cannot stall the GPU
+pub const MAX_SUBSTEPS_PER_FRAME: u32 = 32;
+// Radial density exponents accepted by `set_radial_density_exponent`
+pub const MIN_RADIAL_DENSITY_EXPONENT: f32 = -1.0;
+pub const MAX_RADIAL_DENSITY_EXPONENT: f32 = 3.0;
+// Largest spacing accepted by `set_poisson_spacing`
+pub const MAX_POISSON_SPACING: f32 = 20.0;
+// Upper bound on the rings per doubling of radius in radial band color mode
+pub const MAX_RADIAL_BANDS: f32 = 32.0;
 
+// Picking packs particle indices into the low bits of a word
+const _: () = assert!(NUM_PARTICLES <= 1 << PICK_INDEX_BITS);
+
+// Bit set in `Particle::flags` once the black hole has absorbed the particle
+pub const FLAG_ABSORBED: u32 = 1;
+// Bit set in `Particle::flags` by a physics step that wrapped the particle
+// across the box; it is not interpolated from its previous position
+pub const FLAG_WRAPPED: u32 = 2;
+
+// Particle groups produced by the generator
+pub const GROUP_CLOSE_STARS: u32 = 0;
+pub const GROUP_STREAM: u32 = 1;
+pub const NUM_GROUPS: usize = 2;
+
+// Laid out to match the WGSL struct, where each vec3 is 16-byte aligned
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct Particle {
     pub position: [f32; 3],
+    pub group: u32,
     pub velocity: [f32; 3],
+    // Bit 0: absorbed by the black hole
+    pub flags: u32,
+    // Written by every physics step, for the acceleration color mode
+    pub acceleration: [f32; 3],
+    // Raised by strong gravity and decaying over time, for the temperature
+    // color mode; see `set_temperature_rates`
+    pub temperature: f32,
+}
+
+// A point mass the particles are attracted to
+#[derive(Copy, Clone, Debug)]
+pub struct Attractor {
+    pub position: [f32; 3],
+    // Gravitational parameter (G * mass)
+    pub mass: f32,
 }
 
 #[repr(C)]
@@ -20,47 +90,273 @@ pub struct SimulationParams {
     pub dt: f32,
     pub gm: f32, // Gravitational parameter (G * central_mass)
     pub particle_count: u32,
-    pub _padding: u32,
+    pub integrator: u32,
+    // Particles closer to the center than this are absorbed (0 disables absorption)
+    pub absorb_radius: f32,
+    // Simulation frame counter, used to age accretion flashes
+    pub frame: u32,
+    pub flash_enabled: u32,
+    // Speed clamp applied after each velocity update; 0 or infinity disables it
+    pub max_speed: f32,
+    // Tilt of the precession axis from vertical, in radians, and the
+    // precession rate at the center in radians per second (0 disables it);
+    // see `set_warp`
+    pub warp_amplitude: f32,
+    pub precession_rate: f32,
+    // Temperature gained per second per unit of acceleration, and the
+    // fraction lost per second
+    pub heating_rate: f32,
+    pub cooling_rate: f32,
+    // Half-size of the box particles wrap around while `wrap_enabled` is
+    // nonzero, in place of bouncing off the boundary
+    pub wrap_bounds: [f32; 3],
+    pub wrap_enabled: u32,
+    // xyz: position of the orbiting secondary mass this frame, w: its
+    // gravitational parameter (0 for none)
+    pub secondary: [f32; 4],
+    // x: squared asymptotic circular speed of the dark-matter halo (0 for
+    // none), y: its squared core radius; see `physics::halo_params`
+    pub halo: [f32; 4],
+    // Softening length of the central mass's gravity (0 for none)
+    pub softening: f32,
+    // Absorbed particles restart on a circular orbit at this radius (0 removes them)
+    pub respawn_radius: f32,
+    pub _padding: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct CameraUniform {
+    pub view_proj: [[f32; 4]; 4],
+    // World-space eye position (w unused)
+    pub position: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct RenderParams {
+    pub color_mode: u32,
+    pub clip_enabled: u32,
+    // Exponential fog density per world unit of distance from the camera (0 disables fog)
+    pub fog_density: f32,
+    // Index of the particle drawn highlighted, u32::MAX for none
+    pub highlight_index: u32,
+    // xyz: unit normal, w: distance; particles with dot(normal, position) > distance are cut away
+    pub clip_plane: [f32; 4],
+    pub fog_color: [f32; 4],
+    // rgb: tint for this particle set, a: mix amount (0 leaves colors untouched)
+    pub set_tint: [f32; 4],
+    pub group_tints: [[f32; 4]; NUM_GROUPS],
+    // Fraction of the way from the previous physics state to the current one
+    pub render_alpha: f32,
+    // Opacity particles are blended with
+    pub particle_alpha: f32,
+    // Emission boost near the center: radius it reaches to and its brightness
+    pub inner_glow_radius: f32,
+    pub inner_glow_strength: f32,
+    pub inner_glow_color: [f32; 3],
+    // Exponent of the fade towards `inner_glow_radius`
+    pub inner_glow_falloff: f32,
+    // Drawing buffer size in pixels, for sizing the sprite billboards
+    pub viewport: [f32; 2],
+    pub sprite_size: f32,
+    // Nonzero: sprites shrink with distance, `sprite_size` pixels at the
+    // reference distance and never below `min_sprite_size`
+    pub size_attenuation: u32,
+    pub sprite_reference_distance: f32,
+    pub min_sprite_size: f32,
+    // Coloring mixed into `color_mode`, and how much of it (0..1)
+    pub color_mode_b: u32,
+    pub color_blend: f32,
+    // With size attenuation, particles projecting smaller than this many
+    // pixels fade out and are culled (0 draws them all)
+    pub min_screen_size: f32,
+    // Color rings per doubling of radius in radial band color mode
+    pub radial_bands: f32,
+    // Groups drawn by each layer pass, as bit masks from the back layer forward
+    pub layer_group_masks: [u32; NUM_GROUPS],
+    // Nonzero: the scalar color modes take their colors from the colormap
+    // texture instead of their built-in palettes
+    pub colormap_enabled: u32,
+    pub _padding: [u32; 3],
 }
 
 pub struct Simulation {
-    #[allow(dead_code)]
     particle_buffer: wgpu::Buffer,
+    // State before the most recent physics step, for interpolated drawing
+    previous_buffer: wgpu::Buffer,
     pub params_buffer: wgpu::Buffer,
     pub compute_pipeline: wgpu::ComputePipeline,
     pub render_pipeline: wgpu::RenderPipeline,
+    // Draws the layers after the first over everything before them
+    layer_render_pipeline: wgpu::RenderPipeline,
     pub compute_bind_group: wgpu::BindGroup,
+    compute_bind_group_layout: wgpu::BindGroupLayout,
     pub render_bind_group: wgpu::BindGroup,
+    render_bind_group_layout: wgpu::BindGroupLayout,
+    sprite: ParticleSprite,
+    colormap: Colormap,
+    // Particle buffers the live compute and render bind groups were created
+    // with, and the check that they stay in step
+    #[cfg(debug_assertions)]
+    bound_buffers: BoundBuffers,
+    #[cfg(debug_assertions)]
+    desync: DesyncCheck,
     pub camera_buffer: wgpu::Buffer,
+    pub render_params_buffer: wgpu::Buffer,
     params: SimulationParams,
+    render_params: RenderParams,
+    fixed_timestep: Option<f32>,
+    // Simulated time not yet consumed by a fixed step
+    accumulator: f32,
+    // Physics steps to run in the next compute pass
+    pending_steps: u32,
+    // Fast-forward: every frame's physics steps are repeated this many times
+    substeps_per_frame: u32,
+    // Number of particles already uploaded to the GPU; only these are simulated and drawn
+    fill_cursor: u32,
+    // Upper bound on simulated and drawn particles, for trading detail for speed
+    active_limit: u32,
+    // Particles activated per simulated second while inflow is on, and the
+    // fraction of a particle owed from earlier frames
+    inflow_rate: f32,
+    inflow_budget: f32,
+    // Particles still waiting to be streamed in (empty once the upload is complete)
+    pending_particles: Vec<Particle>,
+    reduction: PositionReduction,
+    picker: ParticlePicker,
+    replay: ReplayBuffer,
+    distribution: DistributionConfig,
+    // Caller-supplied initial conditions, used instead of the generator until
+    // the next distribution change
+    loaded_particles: Option<Vec<Particle>>,
+    // Simulated seconds since the particles were (re)started
+    elapsed: f64,
+    // Simulated seconds since the clock was last reset; unlike `elapsed` it
+    // keeps running across restarts
+    sim_time: f64,
+    // Times the particle state has been replaced, so views can tell
+    restarts: u32,
+    // Second mass perturbing the disk from its own orbit
+    orbiting_mass: Option<OrbitingMass>,
+    // Draw layer of each particle group, drawn from the lowest up
+    group_layers: [u32; NUM_GROUPS],
+    // Particle whose orbit is being measured
+    orbit: Option<OrbitTracker>,
+    // Second particle set advanced with another integrator, for A/B comparison
+    compare: Option<CompareSet>,
+    // Particle state stepped on the CPU and uploaded every frame, instead of
+    // the compute pass; see `set_cpu_physics`
+    cpu_particles: Option<Vec<Particle>>,
+    accretion: AccretionFlashes,
+    // Device limit on workgroups along one dispatch dimension
+    max_workgroups_per_dimension: u32,
+}
+
+// Compute and render pipelines built from one pair of shader sources
+pub struct ParticlePipelines {
+    compute: wgpu::ComputePipeline,
+    render: wgpu::RenderPipeline,
+    layer_render: wgpu::RenderPipeline,
+}
+
+// Resources for the B side of A/B compare mode, mirroring the live set
+struct CompareSet {
+    params_buffer: wgpu::Buffer,
+    params: SimulationParams,
+    render_params_buffer: wgpu::Buffer,
+    compute_bind_group: wgpu::BindGroup,
+    render_bind_group: wgpu::BindGroup,
+    #[cfg(debug_assertions)]
+    bound_buffers: BoundBuffers,
+}
+
+// The particle buffers a compute and a render bind group were created with,
+// recorded alongside them for the desync check: binding 0 of the compute group
+// and bindings 1 (current) and 3 (previous) of the render group
+#[cfg(debug_assertions)]
+struct BoundBuffers {
+    compute: wgpu::Buffer,
+    render: wgpu::Buffer,
+    render_previous: wgpu::Buffer,
 }
 
 impl Simulation {
     pub fn new(
         device: &wgpu::Device,
-        _queue: &wgpu::Queue,
+        queue: &wgpu::Queue,
         surface_format: wgpu::TextureFormat,
+        config: &SimulationConfig,
+        scopes: &mut ErrorScopes,
     ) -> Result<Self, wasm_bindgen::JsValue> {
         console_log!("Creating simulation...");
+        check_particle_buffer_size(device)?;
 
         // Generate initial particle data
-        let particles = Self::generate_initial_particles();
+        let particles = Self::generate_initial_particles(&config.distribution, config.gm);
 
-        // Create particle buffer
-        let particle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Particle Buffer"),
-            contents: bytemuck::cast_slice(&particles),
+        // Create particle buffer, either filled in one go or left empty to be streamed into
+        let particle_usage = wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::VERTEX
+            | wgpu::BufferUsages::COPY_DST
+            | wgpu::BufferUsages::COPY_SRC;
+        // State before the latest physics step, filled alongside the particle buffer
+        let previous_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Previous Particle Buffer"),
+            size: std::mem::size_of_val(particles.as_slice()) as u64,
+            // Copied from by the desync check in debug builds
             usage: wgpu::BufferUsages::STORAGE
-                | wgpu::BufferUsages::VERTEX
-                | wgpu::BufferUsages::COPY_DST,
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
         });
+        if !config.streaming_init {
+            queue.write_buffer(&previous_buffer, 0, bytemuck::cast_slice(&particles));
+        }
+        let (particle_buffer, fill_cursor, pending_particles) = if config.streaming_init {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Particle Buffer"),
+                size: std::mem::size_of_val(particles.as_slice()) as u64,
+                usage: particle_usage,
+                mapped_at_creation: false,
+            });
+            console_log!("🌊 Streaming particles in chunks of {}", STREAM_CHUNK_SIZE);
+            (buffer, 0, particles)
+        } else {
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Particle Buffer"),
+                contents: bytemuck::cast_slice(&particles),
+                usage: particle_usage,
+            });
+            (buffer, NUM_PARTICLES, Vec::new())
+        };
+
+        let inflow_rate = sanitize_inflow_rate(config.inflow_rate);
 
         // Create simulation parameters
         let params = SimulationParams {
-            dt: 0.016,   // ~60fps
-            gm: 40000.0, // Reduced gravitational parameter for more stable orbits
-            particle_count: NUM_PARTICLES,
-            _padding: 0,
+            dt: 0.016, // ~60fps
+            gm: config.gm,
+            particle_count: fill_cursor,
+            integrator: config.integrator as u32,
+            absorb_radius: config.absorb_radius.max(0.0),
+            frame: 0,
+            flash_enabled: config.accretion_flash_enabled as u32,
+            max_speed: sanitize_max_speed(config.max_speed),
+            warp_amplitude: sanitize_finite(config.warp_amplitude),
+            precession_rate: sanitize_finite(config.precession_rate),
+            heating_rate: config.heating_rate.max(0.0),
+            cooling_rate: config.cooling_rate.max(0.0),
+            wrap_bounds: sanitize_wrap_bounds(config.wrap_bounds),
+            wrap_enabled: config.wrap_enabled as u32,
+            secondary: [0.0; 4],
+            halo: physics::halo_params(
+                config.distribution.halo_speed,
+                config.distribution.halo_core_radius,
+            ),
+            softening: sanitize_softening(config.softening),
+            respawn_radius: sanitize_respawn_radius(config.respawn_radius),
+            _padding: [0; 2],
         };
 
         let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -72,21 +368,48 @@ impl Simulation {
         // Create camera buffer
         let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Camera Buffer"),
-            size: 64, // 4x4 matrix = 16 * 4 bytes
+            size: std::mem::size_of::<CameraUniform>() as u64,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
-        // Load and create compute shader
-        let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Compute Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/update.wgsl").into()),
-        });
+        // Create render parameters buffer
+        let render_params = RenderParams {
+            color_mode: config.color_mode as u32,
+            clip_enabled: 0,
+            fog_density: config.fog_density,
+            highlight_index: u32::MAX,
+            clip_plane: [0.0, 1.0, 0.0, 0.0],
+            fog_color: {
+                let [r, g, b] = config.fog_color.unwrap_or(config.background);
+                [r, g, b, 1.0]
+            },
+            set_tint: [0.0; 4],
+            group_tints: config.group_tints.map(|[r, g, b]| [r, g, b, 1.0]),
+            render_alpha: 1.0,
+            particle_alpha: sanitize_particle_alpha(config.particle_alpha),
+            inner_glow_radius: config.inner_glow_radius.max(0.0),
+            inner_glow_strength: config.inner_glow_strength.max(0.0),
+            inner_glow_color: config.inner_glow_color,
+            inner_glow_falloff: config.inner_glow_falloff.max(0.0),
+            viewport: [1.0, 1.0],
+            sprite_size: config.sprite_size.max(1.0),
+            size_attenuation: config.size_attenuation as u32,
+            sprite_reference_distance: SPRITE_REFERENCE_DISTANCE,
+            min_sprite_size: MIN_SPRITE_SIZE,
+            color_mode_b: config.color_mode_b as u32,
+            color_blend: config.color_blend.clamp(0.0, 1.0),
+            min_screen_size: config.min_screen_size.max(0.0),
+            radial_bands: sanitize_radial_bands(config.radial_bands),
+            layer_group_masks: layer_group_masks(config.group_layers),
+            colormap_enabled: 0,
+            _padding: [0; 3],
+        };
 
-        // Load and create render shader
-        let render_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Render Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/render.wgsl").into()),
+        let render_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Render Params Buffer"),
+            contents: bytemuck::cast_slice(&[render_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
         // Create compute bind group layout
@@ -114,6 +437,27 @@ impl Simulation {
                         },
                         count: None,
                     },
+                    // Accretion event ring buffer and its write cursor
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
@@ -142,82 +486,305 @@ impl Simulation {
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Particle state before the last physics step
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
+        let sprite = scopes.capture(device, "Particle Sprite", || {
+            ParticleSprite::new(device, queue)
+        });
+        let colormap = scopes.capture(device, "Colormap", || Colormap::new(device, queue));
+
+        let pipelines = Self::create_pipelines(
+            device,
+            &compute_bind_group_layout,
+            &[
+                &render_bind_group_layout,
+                sprite.bind_group_layout(),
+                colormap.bind_group_layout(),
+            ],
+            surface_format,
+            include_str!("shaders/update.wgsl"),
+            include_str!("shaders/render.wgsl"),
+            scopes,
+        );
+
+        let accretion = scopes.capture(device, "Accretion Flashes", || {
+            AccretionFlashes::new(device, surface_format, config.accretion_flash_enabled)
+        });
+
+        // Create bind groups
+        let compute_bind_group = Self::create_compute_bind_group(
+            device,
+            &compute_bind_group_layout,
+            &particle_buffer,
+            &params_buffer,
+            &accretion,
+        );
+
+        let render_bind_group = Self::create_render_bind_group(
+            device,
+            &render_bind_group_layout,
+            &camera_buffer,
+            &particle_buffer,
+            &previous_buffer,
+            &render_params_buffer,
+        );
+        #[cfg(debug_assertions)]
+        let bound_buffers = BoundBuffers {
+            compute: particle_buffer.clone(),
+            render: particle_buffer.clone(),
+            render_previous: previous_buffer.clone(),
+        };
+
+        let reduction = scopes.capture(device, "Position Reduction", || {
+            PositionReduction::new(device, &particle_buffer, NUM_PARTICLES)
+        });
+        let picker = scopes.capture(device, "Particle Picker", || {
+            ParticlePicker::new(device, &camera_buffer, &particle_buffer)
+        });
+
+        console_log!("⚫ Black Hole Simulation initialized!");
+        console_log!(
+            "📊 Particle count: {} ({}K)",
+            NUM_PARTICLES,
+            NUM_PARTICLES / 1000
+        );
+        console_log!(
+            "⚡ Workgroups: {} ({} particles per workgroup)",
+            NUM_PARTICLES.div_ceil(WORKGROUP_SIZE),
+            WORKGROUP_SIZE
+        );
+        console_log!("🎯 Ready to simulate gravitational dynamics!");
+
+        Ok(Self {
+            particle_buffer,
+            previous_buffer,
+            params_buffer,
+            compute_pipeline: pipelines.compute,
+            render_pipeline: pipelines.render,
+            layer_render_pipeline: pipelines.layer_render,
+            compute_bind_group,
+            compute_bind_group_layout,
+            render_bind_group,
+            render_bind_group_layout,
+            sprite,
+            colormap,
+            #[cfg(debug_assertions)]
+            bound_buffers,
+            #[cfg(debug_assertions)]
+            desync: DesyncCheck::new(device),
+            camera_buffer,
+            render_params_buffer,
+            params,
+            render_params,
+            fixed_timestep: config.fixed_timestep.filter(|h| h.is_finite() && *h > 0.0),
+            accumulator: 0.0,
+            pending_steps: 0,
+            substeps_per_frame: config.substeps_per_frame.clamp(1, MAX_SUBSTEPS_PER_FRAME),
+            fill_cursor,
+            active_limit: if inflow_rate > 0.0 { 0 } else { NUM_PARTICLES },
+            inflow_rate,
+            inflow_budget: 0.0,
+            pending_particles,
+            reduction,
+            picker,
+            replay: ReplayBuffer::new(Vec::new()),
+            distribution: config.distribution.clone(),
+            loaded_particles: None,
+            elapsed: 0.0,
+            sim_time: 0.0,
+            restarts: 0,
+            orbiting_mass: None,
+            group_layers: config.group_layers,
+            orbit: None,
+            compare: None,
+            cpu_particles: None,
+            accretion,
+            max_workgroups_per_dimension: device.limits().max_compute_workgroups_per_dimension,
+        })
+    }
+
+    fn create_pipelines(
+        device: &wgpu::Device,
+        compute_bind_group_layout: &wgpu::BindGroupLayout,
+        render_bind_group_layouts: &[&wgpu::BindGroupLayout],
+        surface_format: wgpu::TextureFormat,
+        compute_src: &str,
+        render_src: &str,
+        scopes: &mut ErrorScopes,
+    ) -> ParticlePipelines {
+        // Load and create compute shader
+        let compute_shader = scopes.capture(device, "Compute Shader", || {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Compute Shader"),
+                source: wgpu::ShaderSource::Wgsl(compute_src.into()),
+            })
+        });
+
+        // Load and create render shader
+        let render_shader = scopes.capture(device, "Render Shader", || {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Render Shader"),
+                source: wgpu::ShaderSource::Wgsl(render_src.into()),
+            })
+        });
+
         // Create compute pipeline
         let compute_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Compute Pipeline Layout"),
-                bind_group_layouts: &[&compute_bind_group_layout],
+                bind_group_layouts: &[compute_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
-        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &compute_shader,
-            entry_point: Some("update_particles"),
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-            cache: None,
+        let compute_pipeline = scopes.capture(device, "Compute Pipeline", || {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Compute Pipeline"),
+                layout: Some(&compute_pipeline_layout),
+                module: &compute_shader,
+                entry_point: Some("update_particles"),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &physics::shader_constants(),
+                    ..Default::default()
+                },
+                cache: None,
+            })
         });
 
         // Create render pipeline
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&render_bind_group_layout],
+                bind_group_layouts: render_bind_group_layouts,
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &render_shader,
-                entry_point: Some("vs_main"),
-                buffers: &[],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &render_shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            cache: None,
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::PointList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
+        let mut create_render_pipeline = |label, depth_compare| {
+            scopes.capture(device, label, || {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&render_pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &render_shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &render_shader,
+                        entry_point: Some("fs_main"),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: surface_format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    }),
+                    cache: None,
+                    primitive: wgpu::PrimitiveState {
+                        // One billboard quad per particle instance
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: None,
+                        unclipped_depth: false,
+                        polygon_mode: wgpu::PolygonMode::Fill,
+                        conservative: false,
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: wgpu::TextureFormat::Depth32Float,
+                        depth_write_enabled: true,
+                        depth_compare,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: 1,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                })
+            })
+        };
+        let render_pipeline =
+            create_render_pipeline("Render Pipeline", wgpu::CompareFunction::Less);
+        // Front layers ignore the depth of the ones behind them, but still
+        // write theirs for the horizon and depth of field
+        let layer_render_pipeline =
+            create_render_pipeline("Layer Render Pipeline", wgpu::CompareFunction::Always);
 
-        // Create bind groups
-        let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        ParticlePipelines {
+            compute: compute_pipeline,
+            render: render_pipeline,
+            layer_render: layer_render_pipeline,
+        }
+    }
+
+    // Build pipelines from replacement shader sources, for swapping in with
+    // `set_pipelines`. Errors surface through the device's error scopes.
+    #[cfg(debug_assertions)]
+    pub fn build_pipelines(
+        &self,
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        compute_src: &str,
+        render_src: &str,
+        scopes: &mut ErrorScopes,
+    ) -> ParticlePipelines {
+        Self::create_pipelines(
+            device,
+            &self.compute_bind_group_layout,
+            &[
+                &self.render_bind_group_layout,
+                self.sprite.bind_group_layout(),
+                self.colormap.bind_group_layout(),
+            ],
+            surface_format,
+            compute_src,
+            render_src,
+            scopes,
+        )
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn set_pipelines(&mut self, pipelines: ParticlePipelines) {
+        self.compute_pipeline = pipelines.compute;
+        self.render_pipeline = pipelines.render;
+        self.layer_render_pipeline = pipelines.layer_render;
+    }
+
+    fn create_compute_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        particle_buffer: &wgpu::Buffer,
+        params_buffer: &wgpu::Buffer,
+        accretion: &AccretionFlashes,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Compute Bind Group"),
-            layout: &compute_bind_group_layout,
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -227,12 +794,29 @@ impl Simulation {
                     binding: 1,
                     resource: params_buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: accretion.events_buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: accretion.cursor_buffer().as_entire_binding(),
+                },
             ],
-        });
+        })
+    }
 
-        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+    fn create_render_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        camera_buffer: &wgpu::Buffer,
+        particle_buffer: &wgpu::Buffer,
+        previous_buffer: &wgpu::Buffer,
+        render_params_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Render Bind Group"),
-            layout: &render_bind_group_layout,
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -242,58 +826,166 @@ impl Simulation {
                     binding: 1,
                     resource: particle_buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: render_params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: previous_buffer.as_entire_binding(),
+                },
             ],
-        });
+        })
+    }
 
-        console_log!("⚫ Black Hole Simulation initialized!");
-        console_log!(
-            "📊 Particle count: {} ({}K)",
+    fn generate_initial_particles(distribution: &DistributionConfig, gm: f32) -> Vec<Particle> {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut particles = match spawn::generate_scene(
+            &distribution.regions,
             NUM_PARTICLES,
-            NUM_PARTICLES / 1000
-        );
-        console_log!(
-            "⚡ Workgroups: {} ({} particles per workgroup)",
-            NUM_PARTICLES.div_ceil(WORKGROUP_SIZE),
-            WORKGROUP_SIZE
-        );
-        console_log!("🎯 Ready to simulate gravitational dynamics!");
+            gm,
+            physics::halo_params(distribution.halo_speed, distribution.halo_core_radius),
+            distribution.poisson_spacing,
+            42,
+        ) {
+            Some(particles) => {
+                console_log!(
+                    "✅ Generated {} particles from {} spawn regions",
+                    NUM_PARTICLES,
+                    distribution.regions.len()
+                );
+                particles
+            }
+            None => Self::generate_default_particles(distribution, gm, &mut rng),
+        };
 
-        Ok(Self {
-            particle_buffer,
-            params_buffer,
-            compute_pipeline,
-            render_pipeline,
-            compute_bind_group,
-            render_bind_group,
-            camera_buffer,
-            params,
-        })
+        let dispersion = distribution.velocity_dispersion.map(|sigma| sigma.max(0.0));
+        if dispersion.iter().any(|&sigma| sigma > 0.0) {
+            for particle in &mut particles {
+                Self::disperse(particle, dispersion, &mut rng);
+            }
+        }
+
+        Self::orient(&mut particles, distribution.disk_orientation);
+
+        // Randomize velocities after placement, so zero jitter leaves the RNG
+        // sequence and thus the initial state unchanged
+        let jitter = distribution.velocity_jitter;
+        if jitter > 0.0 {
+            for particle in &mut particles {
+                for component in &mut particle.velocity {
+                    *component += rng.gen_range(-jitter..jitter);
+                }
+            }
+        }
+
+        particles
     }
 
-    fn generate_initial_particles() -> Vec<Particle> {
-        let mut rng = StdRng::seed_from_u64(42);
+    // Add a Gaussian velocity offset with standard deviations `dispersion`
+    // along the radial, tangential and vertical directions of the particle's
+    // position about the Y axis. Particles on the axis use +X as radial.
+    fn disperse(particle: &mut Particle, dispersion: [f32; 3], rng: &mut StdRng) {
+        let [x, _, z] = particle.position;
+        let r = (x * x + z * z).sqrt();
+        let (cos, sin) = if r > 0.0 { (x / r, z / r) } else { (1.0, 0.0) };
+        let [radial, tangential, vertical] = dispersion.map(|sigma| sigma * gaussian(rng));
+        particle.velocity[0] += radial * cos - tangential * sin;
+        particle.velocity[1] += vertical;
+        particle.velocity[2] += radial * sin + tangential * cos;
+    }
+
+    // Rotate positions and velocities together so the XZ plane ends up facing
+    // `normal`; orbits stay in the tilted plane
+    fn orient(particles: &mut [Particle], normal: [f32; 3]) {
+        let normal = Vector3::from(normal);
+        let length = normal.magnitude();
+        if !length.is_finite() || length == 0.0 {
+            return;
+        }
+
+        let rotation = Quaternion::from_arc(Vector3::unit_y(), normal / length, None);
+        if rotation == Quaternion::from_sv(1.0, Vector3::zero()) {
+            return;
+        }
+        for particle in particles {
+            particle.position = (rotation * Vector3::from(particle.position)).into();
+            particle.velocity = (rotation * Vector3::from(particle.velocity)).into();
+        }
+    }
+
+    // The black hole disk: close orbiting stars plus the inflow stream
+    fn generate_default_particles(
+        distribution: &DistributionConfig,
+        gm: f32,
+        rng: &mut StdRng,
+    ) -> Vec<Particle> {
         let mut particles = Vec::with_capacity(NUM_PARTICLES as usize);
+        let halo = physics::halo_params(distribution.halo_speed, distribution.halo_core_radius);
 
         // Add scattered stars close to the black hole (first 500 particles)
         let num_close_stars = 500u32;
-        for _ in 0..num_close_stars {
+        let (inner, outer) = (20.0, 80.0);
+        let spread = distribution.disk_height_spread;
+        let flatten = distribution.disk_flatten;
+        let random_position = |rng: &mut StdRng| {
             // Random position in a sphere near the black hole
-            let radius = rng.gen_range(20.0..80.0);
+            let radius = sample_radius(rng, inner, outer, distribution.radial_density_exponent);
             let theta = rng.gen_range(0.0..std::f32::consts::TAU); // Angle around Y axis
-            let phi: f32 = rng.gen_range(-0.5..0.5); // Elevation angle (flatten to disk-ish)
+            let phi: f32 = if spread > 0.0 {
+                rng.gen_range(-spread..spread) // Elevation angle (flatten to disk-ish)
+            } else {
+                0.0
+            };
 
             let x = radius * theta.cos() * phi.cos();
-            let y = radius * phi.sin() * 0.3; // Flatten vertically
+            let y = radius * phi.sin() * flatten; // Flatten vertically
             let z = radius * theta.sin() * phi.cos();
+            ([x, y, z], radius, theta)
+        };
+        // Each star's position with the radius and angle its orbit is set up for
+        let stars: Vec<([f32; 3], f32, f32)> = if distribution.poisson_spacing > 0.0 {
+            // The same shell, filled evenly instead of following the radial density
+            let max_elevation = spread.min(std::f32::consts::FRAC_PI_2).sin();
+            let height = outer * max_elevation * flatten;
+            let unflatten = |y: f32| if flatten > 0.0 { y / flatten } else { y };
+            let inside = |[x, y, z]: [f32; 3]| {
+                let y = unflatten(y);
+                let radius = (x * x + y * y + z * z).sqrt();
+                (inner..outer).contains(&radius) && (y / radius).abs() <= max_elevation
+            };
+            poisson::sample(
+                num_close_stars as usize,
+                distribution.poisson_spacing,
+                ([-outer, -height, -outer], [outer, height, outer]),
+                inside,
+                rng,
+                |rng| random_position(rng).0,
+            )
+            .into_iter()
+            .map(|[x, y, z]| {
+                let lifted = unflatten(y);
+                let radius = (x * x + lifted * lifted + z * z).sqrt().max(1e-3);
+                ([x, y, z], radius, z.atan2(x))
+            })
+            .collect()
+        } else {
+            (0..num_close_stars).map(|_| random_position(rng)).collect()
+        };
 
+        for ([x, y, z], radius, theta) in stars {
             // Calculate orbital velocity (perpendicular to radius, for roughly circular orbit)
-            let speed = (40000.0 / radius).sqrt() * 0.8; // Slightly slower than orbital
+            let speed = physics::circular_speed(radius, gm, halo) * distribution.orbital_fraction;
             let vx = -theta.sin() * speed;
             let vz = theta.cos() * speed;
 
             particles.push(Particle {
                 position: [x, y, z],
+                group: GROUP_CLOSE_STARS,
                 velocity: [vx, 0.0, vz],
+                flags: 0,
+                acceleration: [0.0; 3],
+                temperature: 0.0,
             });
         }
 
@@ -308,7 +1000,11 @@ impl Simulation {
 
             particles.push(Particle {
                 position: [x, y, z],
+                group: GROUP_STREAM,
                 velocity: [vx, 0.0, 0.0],
+                flags: 0,
+                acceleration: [0.0; 3],
+                temperature: 0.0,
             });
         }
 
@@ -316,32 +1012,1080 @@ impl Simulation {
         particles
     }
 
+    // Upload the next chunk of pending particles, if any are left to stream in
+    pub fn stream_particles(&mut self, queue: &wgpu::Queue) {
+        let total = self.pending_particles.len();
+        if self.fill_cursor as usize >= total {
+            return;
+        }
+
+        let start = self.fill_cursor as usize;
+        let end = (start + STREAM_CHUNK_SIZE as usize).min(total);
+        let offset = (start * std::mem::size_of::<Particle>()) as u64;
+        for buffer in [&self.particle_buffer, &self.previous_buffer] {
+            queue.write_buffer(
+                buffer,
+                offset,
+                bytemuck::cast_slice(&self.pending_particles[start..end]),
+            );
+        }
+
+        self.fill_cursor = end as u32;
+        self.params.particle_count = self.active_particle_count();
+
+        if end >= total {
+            self.pending_particles = Vec::new();
+            console_log!("✅ Finished streaming {} particles", total);
+        }
+    }
+
     pub fn update(&mut self, queue: &wgpu::Queue, dt: f32) {
-        self.params.dt = dt.min(0.033); // Cap at ~30fps for stability
+        match self.fixed_timestep {
+            Some(step) => {
+                self.accumulator += dt;
+                let steps = (self.accumulator / step).floor() as u32;
+                self.pending_steps = steps.min(MAX_STEPS_PER_FRAME);
+                self.accumulator -= steps as f32 * step;
+                self.params.dt = step;
+                self.render_params.render_alpha = (self.accumulator / step).clamp(0.0, 1.0);
+            }
+            None => {
+                self.pending_steps = 1;
+                self.params.dt = dt.min(0.033); // Cap at ~30fps for stability
+                self.render_params.render_alpha = 1.0;
+            }
+        }
+        self.pending_steps *= self.substeps_per_frame;
+        // The secondary holds still for the frame's steps, at their midpoint
+        let midpoint = self.elapsed + self.pending_steps as f64 * self.params.dt as f64 / 2.0;
+        self.params.secondary = match self.orbiting_mass {
+            Some(mass) => {
+                let [x, y, z] = mass.position(self.params.gm, midpoint);
+                [x, y, z, mass.gm]
+            }
+            None => [0.0; 4],
+        };
+        self.inject_inflow();
+        self.write_render_params(queue);
+
+        self.params.frame = self.params.frame.wrapping_add(1);
         queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[self.params]));
+
+        if let Some(compare) = &mut self.compare {
+            compare.params = SimulationParams {
+                integrator: compare.params.integrator,
+                ..self.params
+            };
+            queue.write_buffer(
+                &compare.params_buffer,
+                0,
+                bytemuck::cast_slice(&[compare.params]),
+            );
+        }
+    }
+
+    // Activate the particles inflow owes for the time simulated this frame.
+    // Inactive slots keep their initial state, so the default distribution's
+    // stream particles enter at the stream source.
+    fn inject_inflow(&mut self) {
+        if self.inflow_rate <= 0.0 || self.active_limit >= NUM_PARTICLES {
+            return;
+        }
+        self.inflow_budget += self.inflow_rate * self.params.dt * self.pending_steps as f32;
+        let injected = self.inflow_budget.floor();
+        self.inflow_budget -= injected;
+        self.active_limit = (self.active_limit as f32 + injected).min(NUM_PARTICLES as f32) as u32;
+        self.params.particle_count = self.active_particle_count();
+    }
+
+    // Inject particles continuously at `rate` per simulated second instead of
+    // having them all present. Turning inflow on restarts from the initial
+    // conditions with no particles; turning it off keeps those injected so far.
+    pub fn set_inflow_rate(&mut self, queue: &wgpu::Queue, rate: f32) {
+        let was_off = self.inflow_rate <= 0.0;
+        self.inflow_rate = sanitize_inflow_rate(rate);
+        if was_off && self.inflow_rate > 0.0 {
+            let particles = self.initial_particles();
+            self.restart_with(queue, &particles);
+        }
+    }
+
+    // Run the physics `substeps` times as often per frame, clamped to
+    // 1..=MAX_SUBSTEPS_PER_FRAME; returns the value applied
+    pub fn set_substeps(&mut self, substeps: u32) -> u32 {
+        self.substeps_per_frame = substeps.clamp(1, MAX_SUBSTEPS_PER_FRAME);
+        self.substeps_per_frame
+    }
+
+    // Switch between fixed physics steps of `step` seconds and one step per frame (`None`)
+    pub fn set_fixed_timestep(&mut self, queue: &wgpu::Queue, step: Option<f32>) {
+        self.fixed_timestep = step.filter(|h| h.is_finite() && *h > 0.0);
+        // A full step is due straight away, so the previous state is refreshed
+        // before anything is interpolated against it
+        self.accumulator = self.fixed_timestep.unwrap_or(0.0);
+        self.render_params.render_alpha = 1.0;
+        self.write_render_params(queue);
+    }
+
+    // Restart from a new initial distribution
+    pub fn set_distribution(&mut self, queue: &wgpu::Queue, distribution: DistributionConfig) {
+        self.params.halo =
+            physics::halo_params(distribution.halo_speed, distribution.halo_core_radius);
+        self.distribution = distribution;
+        self.loaded_particles = None;
+        let particles = Self::generate_initial_particles(&self.distribution, self.params.gm);
+        self.restart_with(queue, &particles);
+    }
+
+    // Restart the current distribution with the close-star surface density
+    // falling off as r^-exponent, clamped to the MIN/MAX_RADIAL_DENSITY_EXPONENT
+    pub fn set_radial_density_exponent(&mut self, queue: &wgpu::Queue, exponent: f32) {
+        let mut distribution = self.distribution.clone();
+        distribution.radial_density_exponent = if exponent.is_nan() {
+            1.0
+        } else {
+            exponent.clamp(MIN_RADIAL_DENSITY_EXPONENT, MAX_RADIAL_DENSITY_EXPONENT)
+        };
+        self.set_distribution(queue, distribution);
+    }
+
+    // Restart the current distribution with its particles at least `spacing`
+    // apart, up to MAX_POISSON_SPACING; 0 places them independently at random
+    pub fn set_poisson_spacing(&mut self, queue: &wgpu::Queue, spacing: f32) {
+        let mut distribution = self.distribution.clone();
+        distribution.poisson_spacing = if spacing.is_nan() {
+            0.0
+        } else {
+            spacing.clamp(0.0, MAX_POISSON_SPACING)
+        };
+        self.set_distribution(queue, distribution);
+    }
+
+    // Restart from caller-supplied particles, e.g. an N-body snapshot computed
+    // elsewhere, instead of generated ones. At most NUM_PARTICLES fit; the
+    // particle count becomes the number supplied. Returns that count.
+    pub fn load_particles(
+        &mut self,
+        queue: &wgpu::Queue,
+        particles: Vec<Particle>,
+    ) -> Result<u32, wasm_bindgen::JsValue> {
+        if particles.is_empty() || particles.len() > NUM_PARTICLES as usize {
+            return Err(wasm_bindgen::JsValue::from_str(&format!(
+                "Expected between 1 and {NUM_PARTICLES} particles, got {}",
+                particles.len()
+            )));
+        }
+        self.restart_with(queue, &particles);
+        self.loaded_particles = Some(particles);
+        Ok(self.fill_cursor)
+    }
+
+    // Current initial conditions: the loaded particles if any, else generated
+    fn initial_particles(&self) -> Vec<Particle> {
+        match &self.loaded_particles {
+            Some(particles) => particles.clone(),
+            None => Self::generate_initial_particles(&self.distribution, self.params.gm),
+        }
+    }
+
+    // Replace the particle state with `particles`. Recorded frames and the A/B
+    // compare set belong to the old state, so both are dropped.
+    fn restart_with(&mut self, queue: &wgpu::Queue, particles: &[Particle]) {
+        queue.write_buffer(&self.particle_buffer, 0, bytemuck::cast_slice(particles));
+        queue.write_buffer(&self.previous_buffer, 0, bytemuck::cast_slice(particles));
+        self.fill_cursor = particles.len() as u32;
+        self.pending_particles = Vec::new();
+        // With inflow on, every restart builds up from nothing again
+        if self.inflow_rate > 0.0 {
+            self.active_limit = 0;
+            self.inflow_budget = 0.0;
+        }
+        self.params.particle_count = self.active_particle_count();
+        self.accumulator = 0.0;
+        self.elapsed = 0.0;
+        self.restarts = self.restarts.wrapping_add(1);
+        self.orbit = None;
+        if let Some(cpu_particles) = &mut self.cpu_particles {
+            *cpu_particles = particles.to_vec();
+        }
+
+        self.replay.clear();
+        self.disable_ab_compare(queue);
     }
 
-    pub fn compute_pass(&self, encoder: &mut wgpu::CommandEncoder) {
-        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-            label: Some("Compute Pass"),
-            timestamp_writes: None,
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.params.integrator = integrator as u32;
+    }
+
+    // Restart from the initial conditions with a second particle set, advancing set A
+    // with one integrator and set B with another and overlaying both in distinct tints
+    pub fn enable_ab_compare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        integrator_a: Integrator,
+        integrator_b: Integrator,
+    ) {
+        if self.cpu_particles.is_some() {
+            console_log!("⚠️ A/B compare needs GPU physics");
+            return;
+        }
+        let particles = self.initial_particles();
+        queue.write_buffer(&self.particle_buffer, 0, bytemuck::cast_slice(&particles));
+        queue.write_buffer(&self.previous_buffer, 0, bytemuck::cast_slice(&particles));
+        self.fill_cursor = particles.len() as u32;
+        self.pending_particles = Vec::new();
+        self.params.particle_count = self.active_particle_count();
+        self.params.integrator = integrator_a as u32;
+
+        let particle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Compare Particle Buffer"),
+            contents: bytemuck::cast_slice(&particles),
+            // Copied from by the desync check in debug builds
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
         });
 
-        compute_pass.set_pipeline(&self.compute_pipeline);
-        compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
-        let workgroups = NUM_PARTICLES.div_ceil(WORKGROUP_SIZE);
-        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        let params = SimulationParams {
+            integrator: integrator_b as u32,
+            ..self.params
+        };
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Compare Params Buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let render_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Compare Render Params Buffer"),
+            contents: bytemuck::cast_slice(&[self.render_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let compute_bind_group = Self::create_compute_bind_group(
+            device,
+            &self.compute_bind_group_layout,
+            &particle_buffer,
+            &params_buffer,
+            &self.accretion,
+        );
+        let render_bind_group = Self::create_render_bind_group(
+            device,
+            &self.render_bind_group_layout,
+            &self.camera_buffer,
+            &particle_buffer,
+            // The B set is drawn without interpolation
+            &particle_buffer,
+            &render_params_buffer,
+        );
+        #[cfg(debug_assertions)]
+        let bound_buffers = BoundBuffers {
+            compute: particle_buffer.clone(),
+            render: particle_buffer.clone(),
+            render_previous: particle_buffer.clone(),
+        };
+
+        self.compare = Some(CompareSet {
+            params_buffer,
+            params,
+            render_params_buffer,
+            compute_bind_group,
+            render_bind_group,
+            #[cfg(debug_assertions)]
+            bound_buffers,
+        });
+        self.render_params.set_tint = COMPARE_TINT_A;
+        self.write_render_params(queue);
+
+        console_log!(
+            "🆚 A/B compare: {:?} (orange) vs {:?} (cyan)",
+            integrator_a,
+            integrator_b
+        );
+    }
+
+    pub fn disable_ab_compare(&mut self, queue: &wgpu::Queue) {
+        if self.compare.take().is_some() {
+            self.render_params.set_tint = [0.0; 4];
+            self.write_render_params(queue);
+        }
+    }
+
+    // Step the particles on the CPU instead of in the compute pass, for
+    // adapters without compute shaders. Slower, but deterministic. Turning it
+    // on restarts from the initial conditions, as the live state is on the GPU;
+    // turning it off carries on from the CPU state.
+    pub fn set_cpu_physics(&mut self, queue: &wgpu::Queue, enabled: bool) {
+        if enabled == self.cpu_particles.is_some() {
+            return;
+        }
+        if enabled {
+            self.cpu_particles = Some(Vec::new());
+            let particles = self.initial_particles();
+            self.restart_with(queue, &particles);
+        } else {
+            self.cpu_particles = None;
+        }
+        console_log!("🖥️ Physics on the {}", if enabled { "CPU" } else { "GPU" });
+    }
+
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    pub fn params(&self) -> &SimulationParams {
+        &self.params
+    }
+
+    pub fn render_params(&self) -> &RenderParams {
+        &self.render_params
+    }
+
+    pub fn distribution(&self) -> &DistributionConfig {
+        &self.distribution
+    }
+
+    pub fn fixed_timestep(&self) -> Option<f32> {
+        self.fixed_timestep
+    }
+
+    pub fn substeps_per_frame(&self) -> u32 {
+        self.substeps_per_frame
+    }
+
+    pub fn inflow_rate(&self) -> f32 {
+        self.inflow_rate
+    }
+
+    // Simulated seconds stepped since `reset_sim_time`; with a fixed timestep
+    // this is an exact count of steps times the step length
+    pub fn sim_time(&self) -> f64 {
+        self.sim_time
+    }
+
+    pub fn reset_sim_time(&mut self) {
+        self.sim_time = 0.0;
+    }
+
+    pub fn is_cpu_physics(&self) -> bool {
+        self.cpu_particles.is_some()
+    }
+
+    // Run the physics steps scheduled by the last `update`
+    pub fn compute_pass(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        let active_count = self.active_particle_count();
+        let (groups_x, groups_y) = dispatch_grid(
+            active_count.div_ceil(WORKGROUP_SIZE),
+            self.max_workgroups_per_dimension,
+        );
+        let steps = std::mem::take(&mut self.pending_steps);
+        self.elapsed += steps as f64 * self.params.dt as f64;
+        self.sim_time += steps as f64 * self.params.dt as f64;
+
+        if let Some(particles) = &mut self.cpu_particles {
+            let count = (active_count as usize).min(particles.len());
+            let active = &mut particles[..count];
+            for step in 0..steps {
+                // Uploads land before the frame's commands, so only the state
+                // before the last step reaches the previous buffer
+                if step + 1 == steps && self.fixed_timestep.is_some() {
+                    queue.write_buffer(&self.previous_buffer, 0, bytemuck::cast_slice(active));
+                }
+                for (index, particle) in active.iter_mut().enumerate() {
+                    physics::step(particle, index as u32, &self.params);
+                }
+            }
+            if steps > 0 {
+                queue.write_buffer(&self.particle_buffer, 0, bytemuck::cast_slice(active));
+            }
+            return;
+        }
+        for _ in 0..steps {
+            if self.fixed_timestep.is_some() {
+                encoder.copy_buffer_to_buffer(
+                    &self.particle_buffer,
+                    0,
+                    &self.previous_buffer,
+                    0,
+                    self.particle_buffer.size(),
+                );
+            }
+
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Compute Pass"),
+                timestamp_writes: None,
+            });
+
+            compute_pass.set_pipeline(&self.compute_pipeline);
+            compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
+            compute_pass.dispatch_workgroups(groups_x, groups_y, 1);
+
+            if let Some(compare) = &self.compare {
+                compute_pass.set_bind_group(0, &compare.compute_bind_group, &[]);
+                compute_pass.dispatch_workgroups(groups_x, groups_y, 1);
+            }
+        }
+    }
+
+    // Periodically reduce the particle positions on the GPU; see `reduce_positions`
+    pub fn reduction_pass(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        self.reduction.encode(
+            queue,
+            encoder,
+            self.active_particle_count(),
+            self.params.gm,
+            self.params.halo,
+            self.elapsed,
+        );
+    }
+
+    // Read back the tracked particle after this frame's physics; see `orbit_stats`
+    pub fn orbit_pass(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(orbit) = &mut self.orbit {
+            orbit.encode(encoder, &self.particle_buffer, self.elapsed);
+        }
+    }
+
+    // Development only: compare a few particles between the buffers bound for
+    // computing and drawing after this frame's physics; see `DesyncCheck`
+    #[cfg(debug_assertions)]
+    pub fn desync_pass(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        // Scrubbing draws a recorded frame on purpose
+        if self.replay.selected_slot().is_some() {
+            return;
+        }
+        // The CPU fallback uploads straight into the particle buffer instead
+        // of going through the compute bind group
+        let live = &self.bound_buffers;
+        let written = if self.cpu_particles.is_some() {
+            &self.particle_buffer
+        } else {
+            &live.compute
+        };
+        let mut pairs = vec![
+            BufferPair {
+                name: "particles",
+                written,
+                drawn: &live.render,
+            },
+            // Filled by copy or upload before each step, not by a bind group
+            BufferPair {
+                name: "previous particles",
+                written: &self.previous_buffer,
+                drawn: &live.render_previous,
+            },
+        ];
+        if let Some(compare) = &self.compare {
+            let bound = &compare.bound_buffers;
+            pairs.push(BufferPair {
+                name: "A/B compare particles",
+                written: &bound.compute,
+                drawn: &bound.render,
+            });
+            // The B set is drawn without interpolation, from its own state
+            pairs.push(BufferPair {
+                name: "A/B compare previous particles",
+                written: &bound.compute,
+                drawn: &bound.render_previous,
+            });
+        }
+        self.desync
+            .encode(encoder, &pairs, self.active_particle_count());
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn set_desync_check(&mut self, enabled: bool) {
+        self.desync.set_enabled(enabled);
+    }
+
+    // Must be called once the frame's command buffer has been submitted
+    pub fn after_submit(&mut self) {
+        self.reduction.map_results();
+        self.picker.map_results();
+        #[cfg(debug_assertions)]
+        self.desync.map_results();
+        if let Some(orbit) = &mut self.orbit {
+            orbit.map_results();
+        }
+    }
+
+    // Start measuring the orbit of particle `index`, or stop with `None`
+    pub fn track_orbit(&mut self, device: &wgpu::Device, index: Option<u32>) {
+        self.orbit = index.map(|index| OrbitTracker::new(device, index));
+    }
+
+    pub fn tracked_orbit(&self) -> Option<&OrbitTracker> {
+        self.orbit.as_ref()
+    }
+
+    // Measured orbit of the tracked particle, once it has passed both extremes
+    pub fn orbit_stats(&self) -> Option<OrbitStats> {
+        self.orbit.as_ref()?.stats(self.params.gm)
+    }
+
+    // Periodically pick the particle under `cursor` (canvas pixels); see `take_pick`
+    pub fn pick_pass(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        cursor: (f32, f32),
+        viewport: (u32, u32),
+    ) {
+        // Picks run against the live state, which is not what is shown while scrubbing
+        if self.replay.selected_slot().is_some() {
+            return;
+        }
+        let count = self.active_particle_count();
+        self.picker.encode(queue, encoder, cursor, viewport, count);
+    }
+
+    // Result of the latest completed pick, once: `Some(None)` if nothing was under the cursor
+    pub fn take_pick(&self) -> Option<Option<u32>> {
+        self.picker.take()
+    }
+
+    pub fn set_highlight(&mut self, queue: &wgpu::Queue, index: Option<u32>) {
+        self.render_params.highlight_index =
+            index.filter(|&i| i < NUM_PARTICLES).unwrap_or(u32::MAX);
+        self.write_render_params(queue);
+    }
+
+    // Bounds, centroid and energy of the particles from the most recent
+    // completed reduction
+    pub fn reduce_positions(&self) -> Option<PositionStats> {
+        self.reduction.latest()
+    }
+
+    // Bind group and particle count to draw: the selected replay frame while
+    // scrubbing, otherwise the live state
+    pub fn drawn_particles(&self) -> (&wgpu::BindGroup, u32) {
+        match self.replay.selected_slot() {
+            Some(slot) => (&slot.bind_group, slot.particle_count),
+            None => (&self.render_bind_group, self.active_particle_count()),
+        }
+    }
+
+    // The masses acting on the particles: the central black hole and the
+    // orbiting secondary, if any
+    pub fn attractors(&self) -> Vec<Attractor> {
+        let mut attractors = vec![Attractor {
+            position: [0.0; 3],
+            mass: self.params.gm,
+        }];
+        if let Some(mass) = self.orbiting_mass {
+            attractors.push(Attractor {
+                position: mass.position(self.params.gm, self.elapsed),
+                mass: mass.gm,
+            });
+        }
+        attractors
+    }
+
+    // Put a secondary mass on its own Keplerian orbit around the black hole,
+    // replacing any previous one; `None` removes it
+    pub fn set_orbiting_mass(&mut self, mass: Option<OrbitingMass>) {
+        self.orbiting_mass = mass;
+    }
+
+    // Live particle buffer and the number of particles in use
+    pub fn live_particles(&self) -> (&wgpu::Buffer, u32) {
+        (&self.particle_buffer, self.active_particle_count())
+    }
+
+    // Layout of the bind groups returned by `drawn_particles`
+    pub fn render_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.render_bind_group_layout
     }
 
     pub fn render_pass<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_bind_group(0, &self.render_bind_group, &[]);
-        render_pass.draw(0..NUM_PARTICLES, 0..1);
+        let (bind_group, particle_count) = self.drawn_particles();
+
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, self.sprite.bind_group(), &[]);
+        render_pass.set_bind_group(2, self.colormap.bind_group(), &[]);
+        self.draw_layers(render_pass, particle_count);
+
+        // The B set has no history, so only overlay it on the live state
+        if let (Some(compare), None) = (&self.compare, self.replay.selected_slot()) {
+            render_pass.set_bind_group(0, &compare.render_bind_group, &[]);
+            self.draw_layers(render_pass, self.active_particle_count());
+        }
+
+        self.accretion.render_pass(render_pass);
+    }
+
+    // One draw per layer, back to front. Layer `i` draws instances from
+    // i * NUM_PARTICLES, which the shader maps back to particles and its mask.
+    fn draw_layers<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, particle_count: u32) {
+        let masks = self.render_params.layer_group_masks;
+        for (layer, _) in (0u32..).zip(masks).filter(|&(_, mask)| mask != 0) {
+            render_pass.set_pipeline(if layer == 0 {
+                &self.render_pipeline
+            } else {
+                &self.layer_render_pipeline
+            });
+            let first = layer * NUM_PARTICLES;
+            render_pass.draw(0..6, first..first + particle_count);
+        }
+    }
+
+    // Reallocate the replay ring buffer to hold the last `length` frames (0 disables it)
+    pub fn set_replay_length(&mut self, device: &wgpu::Device, length: u32) -> u32 {
+        let length = length.min(MAX_REPLAY_FRAMES);
+        let slots = (0..length)
+            .map(|_| {
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Replay Particle Buffer"),
+                    size: self.particle_buffer.size(),
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let bind_group = Self::create_render_bind_group(
+                    device,
+                    &self.render_bind_group_layout,
+                    &self.camera_buffer,
+                    &buffer,
+                    // Recorded frames are drawn as they were, without interpolation
+                    &buffer,
+                    &self.render_params_buffer,
+                );
+                ReplaySlot {
+                    buffer,
+                    bind_group,
+                    particle_count: 0,
+                }
+            })
+            .collect();
+        self.replay = ReplayBuffer::new(slots);
+
+        console_log!(
+            "⏪ Replay buffer: {} frames ({} MB)",
+            length,
+            length as u64 * self.particle_buffer.size() / (1024 * 1024)
+        );
+        length
+    }
+
+    // Copy the current particle state into the replay ring buffer
+    pub fn record_replay(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.replay.capacity() > 0 {
+            self.replay
+                .record(encoder, &self.particle_buffer, self.active_particle_count());
+        }
+    }
+
+    pub fn replay_frames(&self) -> u32 {
+        self.replay.recorded() as u32
+    }
+
+    // Draw a recorded frame (0 is the oldest) instead of the live state, or `None` for live
+    pub fn scrub(&mut self, frame: Option<u32>) {
+        self.replay.select(frame.map(|frame| frame as usize));
+    }
+
+    // Particles currently simulated and drawn
+    pub fn active_particle_count(&self) -> u32 {
+        self.fill_cursor.min(self.active_limit)
+    }
+
+    // Only the first `count` particles are simulated and drawn; the rest keep their
+    // state and resume where they left off when the count is raised again.
+    // Returns the count actually used, clamped to the allocation.
+    pub fn set_active_particles(&mut self, count: u32) -> u32 {
+        self.active_limit = count.min(NUM_PARTICLES);
+        self.params.particle_count = self.active_particle_count();
+        self.active_limit
+    }
+
+    // Cap particle speeds, 0 or infinity for no cap. Not physical: clamped
+    // particles lose energy, but slingshots near the center stay on screen.
+    pub fn set_max_speed(&mut self, max_speed: f32) {
+        self.params.max_speed = sanitize_max_speed(max_speed);
+    }
+
+    // Non-physical warp: every orbit precesses around an axis tilted
+    // `amplitude` radians from vertical, at `rate` radians per second near the
+    // center and more slowly further out, so the flat disk twists into a
+    // warped, precessing one. A rate of 0 turns it off.
+    pub fn set_warp(&mut self, amplitude: f32, rate: f32) {
+        self.params.warp_amplitude = sanitize_finite(amplitude);
+        self.params.precession_rate = sanitize_finite(rate);
+    }
+
+    // Toroidal flow: particles leaving the box of half-size `bounds` come back
+    // in through the opposite face with their velocity unchanged. Replaces the
+    // bounce off the boundary while enabled.
+    pub fn set_wrap(&mut self, enabled: bool, bounds: [f32; 3]) {
+        self.params.wrap_enabled = enabled as u32;
+        self.params.wrap_bounds = sanitize_wrap_bounds(bounds);
+    }
+
+    // Particles heat up by `heating` per second per unit of gravitational
+    // acceleration and cool exponentially at `cooling` per second, so they
+    // glow as they fall in and fade on the way out
+    pub fn set_temperature_rates(&mut self, heating: f32, cooling: f32) {
+        self.params.heating_rate = heating.max(0.0);
+        self.params.cooling_rate = cooling.max(0.0);
+    }
+
+    // Strength of the central mass, for subsequent steps; existing orbits are kept
+    pub fn set_gm(&mut self, gm: f32) {
+        self.params.gm = gm.max(0.0);
+    }
+
+    pub fn set_absorb_radius(&mut self, radius: f32) {
+        self.params.absorb_radius = radius.max(0.0);
+    }
+
+    pub fn set_softening(&mut self, softening: f32) {
+        self.params.softening = sanitize_softening(softening);
+    }
+
+    pub fn set_respawn_radius(&mut self, radius: f32) {
+        self.params.respawn_radius = sanitize_respawn_radius(radius);
+    }
+
+    pub fn set_accretion_flash_enabled(&mut self, enabled: bool) {
+        self.accretion.set_enabled(enabled);
+        self.params.flash_enabled = self.accretion.enabled() as u32;
+    }
+
+    pub fn set_color_mode(&mut self, queue: &wgpu::Queue, mode: ColorMode) {
+        self.render_params.color_mode = mode as u32;
+        self.write_render_params(queue);
+    }
+
+    // Color with `blend` (0..1) of the way from `mode_a` to `mode_b`
+    pub fn set_color_blend(
+        &mut self,
+        queue: &wgpu::Queue,
+        mode_a: ColorMode,
+        mode_b: ColorMode,
+        blend: f32,
+    ) {
+        self.render_params.color_mode = mode_a as u32;
+        self.render_params.color_mode_b = mode_b as u32;
+        if blend.is_finite() {
+            self.render_params.color_blend = blend.clamp(0.0, 1.0);
+        }
+        self.write_render_params(queue);
+    }
+
+    pub fn set_clip_plane(&mut self, queue: &wgpu::Queue, normal: [f32; 3], distance: f32) {
+        self.render_params.clip_plane = [normal[0], normal[1], normal[2], distance];
+        self.write_render_params(queue);
+    }
+
+    pub fn set_fog(&mut self, queue: &wgpu::Queue, density: f32, color: [f32; 3]) {
+        self.render_params.fog_density = density;
+        self.render_params.fog_color = [color[0], color[1], color[2], 1.0];
+        self.write_render_params(queue);
+    }
+
+    // Base color of one particle group in group color mode
+    pub fn set_group_color(&mut self, queue: &wgpu::Queue, group: usize, color: [f32; 3]) {
+        if let Some(tint) = self.render_params.group_tints.get_mut(group) {
+            *tint = [color[0], color[1], color[2], 1.0];
+            self.write_render_params(queue);
+        }
+    }
+
+    // Groups in a higher layer are drawn over those in lower ones regardless
+    // of depth; groups sharing a layer are depth sorted together as usual
+    pub fn set_group_layer(&mut self, queue: &wgpu::Queue, group: usize, layer: u32) {
+        if let Some(slot) = self.group_layers.get_mut(group) {
+            *slot = layer;
+            self.render_params.layer_group_masks = layer_group_masks(self.group_layers);
+            self.write_render_params(queue);
+        }
+    }
+
+    pub fn set_inner_glow(
+        &mut self,
+        queue: &wgpu::Queue,
+        radius: f32,
+        strength: f32,
+        falloff: f32,
+        color: [f32; 3],
+    ) {
+        self.render_params.inner_glow_radius = radius.max(0.0);
+        self.render_params.inner_glow_strength = strength.max(0.0);
+        self.render_params.inner_glow_falloff = falloff.max(0.0);
+        self.render_params.inner_glow_color = color;
+        self.write_render_params(queue);
+    }
+
+    // Draw particles with a `width` x `height` RGBA8 sprite instead of the soft dot
+    pub fn set_particle_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        self.sprite.set_texture(device, queue, width, height, rgba)
+    }
+
+    // Color the scalar color modes (all but group tint) from RGBA8 palette
+    // entries, low to high, in place of their built-in palettes
+    pub fn set_colormap(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &[u8],
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        self.colormap.set_entries(device, queue, rgba)?;
+        self.render_params.colormap_enabled = 1;
+        self.write_render_params(queue);
+        Ok(())
+    }
+
+    // As `set_colormap`, with one of the palettes in `colormap::COLORMAPS`
+    pub fn set_named_colormap(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: &str,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        self.colormap.set_named(device, queue, name)?;
+        self.render_params.colormap_enabled = 1;
+        self.write_render_params(queue);
+        Ok(())
+    }
+
+    // Back to each color mode's built-in palette
+    pub fn clear_colormap(&mut self, queue: &wgpu::Queue) {
+        self.render_params.colormap_enabled = 0;
+        self.write_render_params(queue);
+    }
+
+    pub fn set_sprite_size(&mut self, queue: &wgpu::Queue, size: f32) {
+        self.render_params.sprite_size = size.max(1.0);
+        self.write_render_params(queue);
+    }
+
+    // Cull particles projecting smaller than `size` pixels while size
+    // attenuation is on, fading them out just above it; 0 draws them all
+    pub fn set_min_screen_size(&mut self, queue: &wgpu::Queue, size: f32) {
+        self.render_params.min_screen_size = if size.is_nan() { 0.0 } else { size.max(0.0) };
+        self.write_render_params(queue);
+    }
+
+    // Rings per doubling of radius drawn by the radial band color mode
+    pub fn set_radial_bands(&mut self, queue: &wgpu::Queue, bands: f32) {
+        self.render_params.radial_bands = sanitize_radial_bands(bands);
+        self.write_render_params(queue);
+    }
+
+    // Perspective-correct sprites that shrink with distance, or a constant
+    // screen size when off
+    pub fn set_size_attenuation(&mut self, queue: &wgpu::Queue, enabled: bool) {
+        self.render_params.size_attenuation = enabled as u32;
+        self.write_render_params(queue);
+    }
+
+    pub fn set_viewport(&mut self, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.render_params.viewport = [width.max(1) as f32, height.max(1) as f32];
+        self.write_render_params(queue);
+    }
+
+    pub fn set_inner_glow_strength(&mut self, queue: &wgpu::Queue, strength: f32) {
+        self.render_params.inner_glow_strength = strength.max(0.0);
+        self.write_render_params(queue);
+    }
+
+    pub fn set_particle_alpha(&mut self, queue: &wgpu::Queue, alpha: f32) {
+        self.render_params.particle_alpha = sanitize_particle_alpha(alpha);
+        self.write_render_params(queue);
+    }
+
+    pub fn set_clip_enabled(&mut self, queue: &wgpu::Queue, enabled: bool) {
+        self.render_params.clip_enabled = enabled as u32;
+        self.write_render_params(queue);
+    }
+
+    fn write_render_params(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.render_params_buffer,
+            0,
+            bytemuck::cast_slice(&[self.render_params]),
+        );
+
+        if let Some(compare) = &self.compare {
+            let params = RenderParams {
+                set_tint: COMPARE_TINT_B,
+                highlight_index: u32::MAX,
+                ..self.render_params
+            };
+            queue.write_buffer(
+                &compare.render_params_buffer,
+                0,
+                bytemuck::cast_slice(&[params]),
+            );
+        }
     }
 
     pub fn update_camera(&self, queue: &wgpu::Queue, camera: &crate::camera::Camera) {
-        let matrix = camera.build_view_projection_matrix();
-        let matrix_array: &[f32; 16] = matrix.as_ref();
-        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(matrix_array));
+        let eye = camera.eye_position();
+        let uniform = CameraUniform {
+            view_proj: camera.build_view_projection_matrix().into(),
+            position: [eye.x, eye.y, eye.z, 1.0],
+        };
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.accretion.update(queue, camera, self.params.frame);
+    }
+}
+
+// The particle buffer is bound whole as storage, so it has to fit in one
+// binding; fails with the device's limit and the particle count it allows
+fn check_particle_buffer_size(device: &wgpu::Device) -> Result<(), GpuError> {
+    let limits = device.limits();
+    let limit = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+    let particle_size = std::mem::size_of::<Particle>() as u64;
+    let required = NUM_PARTICLES as u64 * particle_size;
+    if required <= limit {
+        return Ok(());
+    }
+    let error = GpuError::limit_exceeded(
+        "Particle Buffer",
+        format!(
+            "{NUM_PARTICLES} particles need {required} bytes, but this device binds at most {limit} bytes of storage ({} particles)",
+            limit / particle_size
+        ),
+    );
+    console_log!("❌ {}", error);
+    Err(error)
+}
+
+// Bit masks of the groups in each distinct layer, back to front, with unused
+// entries left 0. Equal layers give a single mask of every group.
+fn layer_group_masks(layers: [u32; NUM_GROUPS]) -> [u32; NUM_GROUPS] {
+    let mut order = layers;
+    order.sort_unstable();
+    let mut masks = [0; NUM_GROUPS];
+    let mut count = 0;
+    for (i, &layer) in order.iter().enumerate() {
+        if i > 0 && order[i - 1] == layer {
+            continue;
+        }
+        for (group, &group_layer) in layers.iter().enumerate() {
+            if group_layer == layer {
+                masks[count] |= 1 << group;
+            }
+        }
+        count += 1;
+    }
+    masks
+}
+
+// Split `workgroups` over the X and Y dimensions so that neither exceeds
+// `max_per_dimension`. The shader flattens the grid back into one index, and the
+// few extra invocations of the last row fail its particle count check.
+pub fn dispatch_grid(workgroups: u32, max_per_dimension: u32) -> (u32, u32) {
+    let max_per_dimension = max_per_dimension.max(1);
+    if workgroups <= max_per_dimension {
+        return (workgroups, 1);
+    }
+    let rows = workgroups.div_ceil(max_per_dimension);
+    (workgroups.div_ceil(rows), rows)
+}
+
+// Standard normal sample, by the Box-Muller transform
+fn gaussian(rng: &mut StdRng) -> f32 {
+    let u = 1.0 - rng.gen::<f32>();
+    let v = rng.gen::<f32>();
+    (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos()
+}
+
+// Radius in inner..outer for a disk whose surface density goes as r^-exponent,
+// by inverse transform of the radial density r^(1 - exponent)
+fn sample_radius(rng: &mut StdRng, inner: f32, outer: f32, exponent: f32) -> f32 {
+    // Uniform in radius, drawn as before so the default disk is unchanged
+    if exponent == 1.0 {
+        return rng.gen_range(inner..outer);
+    }
+    let u: f32 = rng.gen();
+    let k = 2.0 - exponent;
+    if k.abs() < 1e-3 {
+        // r^-1 radial density: uniform in log r
+        inner * (outer / inner).powf(u)
+    } else {
+        (inner.powf(k) + u * (outer.powf(k) - inner.powf(k))).powf(1.0 / k)
+    }
+}
+
+// Negative and NaN speed limits disable the clamp like 0 does
+fn sanitize_max_speed(max_speed: f32) -> f32 {
+    if max_speed > 0.0 {
+        max_speed
+    } else {
+        0.0
+    }
+}
+
+// NaN, infinite and negative softening lengths turn softening off
+fn sanitize_softening(softening: f32) -> f32 {
+    if softening.is_finite() {
+        softening.max(0.0)
+    } else {
+        0.0
+    }
+}
+
+// Respawning happens inside the bounce boundary; NaN turns it off
+fn sanitize_respawn_radius(radius: f32) -> f32 {
+    if radius.is_nan() {
+        0.0
+    } else {
+        radius.clamp(0.0, physics::BOUNDARY)
+    }
+}
+
+// Wrap box half-sizes must be positive and finite; others fall back to the
+// bounce boundary
+fn sanitize_wrap_bounds(bounds: [f32; 3]) -> [f32; 3] {
+    bounds.map(|half| {
+        if half.is_finite() && half > 0.0 {
+            half
+        } else {
+            physics::BOUNDARY
+        }
+    })
+}
+
+// NaN draws particles fully opaque rather than blanking them all
+fn sanitize_particle_alpha(alpha: f32) -> f32 {
+    if alpha.is_nan() {
+        1.0
+    } else {
+        alpha.clamp(0.0, 1.0)
+    }
+}
+
+fn sanitize_radial_bands(bands: f32) -> f32 {
+    if bands.is_nan() {
+        0.0
+    } else {
+        bands.clamp(0.0, MAX_RADIAL_BANDS)
+    }
+}
+
+// NaN and infinite values become 0
+fn sanitize_finite(value: f32) -> f32 {
+    if value.is_finite() {
+        value
+    } else {
+        0.0
+    }
+}
+
+// Negative, NaN and infinite rates turn inflow off
+fn sanitize_inflow_rate(rate: f32) -> f32 {
+    if rate.is_finite() && rate > 0.0 {
+        rate
+    } else {
+        0.0
     }
 }
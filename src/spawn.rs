@@ -0,0 +1,584 @@
+// Spawn-region primitives for building initial conditions. Every region samples
+// uniformly over its volume or area, so particle density is even throughout.
+
+use crate::physics;
+use crate::poisson;
+use crate::simulation::{Particle, NUM_GROUPS};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::{FRAC_PI_2, TAU};
+use wasm_bindgen::JsValue;
+
+// Rejection draws for a ring point before the last candidate is kept anyway.
+// A valid ring accepts at least half of them on average, so this is never hit
+// in practice.
+const MAX_RING_ATTEMPTS: u32 = 32;
+
+#[derive(Clone, Debug)]
+pub enum SpawnRegion {
+    // Axis-aligned box
+    Box {
+        center: [f32; 3],
+        half_extents: [f32; 3],
+    },
+    // Flat annulus in the XZ plane with some vertical thickness
+    Annulus {
+        center: [f32; 3],
+        inner_radius: f32,
+        outer_radius: f32,
+        thickness: f32,
+    },
+    // Torus around the Y axis; `tube_radius` must not exceed `radius`
+    Ring {
+        center: [f32; 3],
+        radius: f32,
+        tube_radius: f32,
+    },
+    // Solid cone opening from `apex` along `axis`
+    Cone {
+        apex: [f32; 3],
+        axis: [f32; 3],
+        height: f32,
+        radius: f32,
+    },
+    // Square patch facing `normal`
+    Plane {
+        center: [f32; 3],
+        normal: [f32; 3],
+        half_size: f32,
+    },
+    // Disk in the XZ plane whose density follows a logarithmic-spiral wave,
+    // 1 + arm_contrast * cos(arms * (theta - ln(r / inner_radius) / tan(pitch_angle))).
+    // `pitch_angle` is in radians; `arm_contrast` in 0..1 sets how empty the gaps are.
+    SpiralGalaxy {
+        center: [f32; 3],
+        arms: u32,
+        pitch_angle: f32,
+        arm_contrast: f32,
+        inner_radius: f32,
+        outer_radius: f32,
+        thickness: f32,
+    },
+}
+
+impl SpawnRegion {
+    pub fn sample(&self, rng: &mut impl Rng) -> [f32; 3] {
+        match *self {
+            SpawnRegion::Box {
+                center,
+                half_extents,
+            } => [
+                center[0] + rng.gen_range(-1.0..=1.0) * half_extents[0],
+                center[1] + rng.gen_range(-1.0..=1.0) * half_extents[1],
+                center[2] + rng.gen_range(-1.0..=1.0) * half_extents[2],
+            ],
+            SpawnRegion::Annulus {
+                center,
+                inner_radius,
+                outer_radius,
+                thickness,
+            } => {
+                // Inverse transform of the area element r dr
+                let (r0, r1) = (inner_radius * inner_radius, outer_radius * outer_radius);
+                let r = (r0 + rng.gen::<f32>() * (r1 - r0)).sqrt();
+                let theta = rng.gen_range(0.0..TAU);
+                [
+                    center[0] + r * theta.cos(),
+                    center[1] + (rng.gen::<f32>() - 0.5) * thickness,
+                    center[2] + r * theta.sin(),
+                ]
+            }
+            SpawnRegion::Ring {
+                center,
+                radius,
+                tube_radius,
+            } => {
+                // Uniform point in the tube's cross-section; the outer side of the
+                // torus has more volume, so keep points in proportion to their
+                // distance from the axis (acceptance is at least (R - a) / (R + a))
+                let mut attempt = 0;
+                let (offset, y) = loop {
+                    let rho = tube_radius * rng.gen::<f32>().sqrt();
+                    let phi = rng.gen_range(0.0..TAU);
+                    let offset = rho * phi.cos();
+                    let weight = (radius + offset) / (radius + tube_radius).max(f32::EPSILON);
+                    attempt += 1;
+                    if rng.gen::<f32>() <= weight || attempt >= MAX_RING_ATTEMPTS {
+                        break (offset, rho * phi.sin());
+                    }
+                };
+                let theta = rng.gen_range(0.0..TAU);
+                let r = radius + offset;
+                [
+                    center[0] + r * theta.cos(),
+                    center[1] + y,
+                    center[2] + r * theta.sin(),
+                ]
+            }
+            SpawnRegion::Cone {
+                apex,
+                axis,
+                height,
+                radius,
+            } => {
+                // Cross-sections grow with t^2, so t follows the cube root
+                let t = rng.gen::<f32>().cbrt();
+                let r = radius * t * rng.gen::<f32>().sqrt();
+                let theta = rng.gen_range(0.0..TAU);
+                let axis = normalize(axis);
+                let (tangent, bitangent) = orthonormal_basis(axis);
+                let along = height * t;
+                let (a, b) = (r * theta.cos(), r * theta.sin());
+                [
+                    apex[0] + axis[0] * along + tangent[0] * a + bitangent[0] * b,
+                    apex[1] + axis[1] * along + tangent[1] * a + bitangent[1] * b,
+                    apex[2] + axis[2] * along + tangent[2] * a + bitangent[2] * b,
+                ]
+            }
+            SpawnRegion::Plane {
+                center,
+                normal,
+                half_size,
+            } => {
+                let (tangent, bitangent) = orthonormal_basis(normal);
+                let a = rng.gen_range(-1.0..=1.0) * half_size;
+                let b = rng.gen_range(-1.0..=1.0) * half_size;
+                [
+                    center[0] + tangent[0] * a + bitangent[0] * b,
+                    center[1] + tangent[1] * a + bitangent[1] * b,
+                    center[2] + tangent[2] * a + bitangent[2] * b,
+                ]
+            }
+            SpawnRegion::SpiralGalaxy {
+                center,
+                arms,
+                pitch_angle,
+                arm_contrast,
+                inner_radius,
+                outer_radius,
+                thickness,
+            } => {
+                // Uniform over the disk area, then thinned by the density wave
+                let inner = inner_radius.max(f32::EPSILON);
+                let (r0, r1) = (inner * inner, outer_radius * outer_radius);
+                let contrast = arm_contrast.clamp(0.0, 1.0);
+                let winding = 1.0 / pitch_angle.tan().abs().max(1e-3);
+                let (r, theta) = loop {
+                    let r = (r0 + rng.gen::<f32>() * (r1 - r0)).sqrt();
+                    let theta = rng.gen_range(0.0..TAU);
+                    let phase = arms as f32 * (theta - (r / inner).ln() * winding);
+                    let density = (1.0 + contrast * phase.cos()) / (1.0 + contrast);
+                    if rng.gen::<f32>() <= density {
+                        break (r, theta);
+                    }
+                };
+                [
+                    center[0] + r * theta.cos(),
+                    center[1] + (rng.gen::<f32>() - 0.5) * thickness,
+                    center[2] + r * theta.sin(),
+                ]
+            }
+        }
+    }
+
+    // Box around the volume `sample` draws from, for Poisson-disk sampling with
+    // `contains`; `None` for the flat plane and the density-weighted spiral,
+    // which have no evenly filled volume to pack
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        let around = |center: [f32; 3], half: [f32; 3]| {
+            Some((
+                [0, 1, 2].map(|axis| center[axis] - half[axis].abs()),
+                [0, 1, 2].map(|axis| center[axis] + half[axis].abs()),
+            ))
+        };
+        match *self {
+            SpawnRegion::Box {
+                center,
+                half_extents,
+            } => around(center, half_extents),
+            SpawnRegion::Annulus {
+                center,
+                outer_radius,
+                thickness,
+                ..
+            } => around(center, [outer_radius, thickness * 0.5, outer_radius]),
+            SpawnRegion::Ring {
+                center,
+                radius,
+                tube_radius,
+            } => {
+                let reach = radius.abs() + tube_radius.abs();
+                around(center, [reach, tube_radius, reach])
+            }
+            SpawnRegion::Cone {
+                apex,
+                height,
+                radius,
+                ..
+            } => {
+                let reach = height.abs() + radius.abs();
+                around(apex, [reach; 3])
+            }
+            SpawnRegion::Plane { .. } | SpawnRegion::SpiralGalaxy { .. } => None,
+        }
+    }
+
+    // Whether `p` lies in the volume given by `bounds`
+    pub fn contains(&self, p: [f32; 3]) -> bool {
+        match *self {
+            SpawnRegion::Box {
+                center,
+                half_extents,
+            } => (0..3).all(|axis| (p[axis] - center[axis]).abs() <= half_extents[axis].abs()),
+            SpawnRegion::Annulus {
+                center,
+                inner_radius,
+                outer_radius,
+                thickness,
+            } => {
+                let (x, z) = (p[0] - center[0], p[2] - center[2]);
+                let r = (x * x + z * z).sqrt();
+                r >= inner_radius
+                    && r <= outer_radius
+                    && (p[1] - center[1]).abs() <= thickness * 0.5
+            }
+            SpawnRegion::Ring {
+                center,
+                radius,
+                tube_radius,
+            } => {
+                let (x, y, z) = (p[0] - center[0], p[1] - center[1], p[2] - center[2]);
+                let offset = (x * x + z * z).sqrt() - radius;
+                offset * offset + y * y <= tube_radius * tube_radius
+            }
+            SpawnRegion::Cone {
+                apex,
+                axis,
+                height,
+                radius,
+            } => {
+                let length = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt();
+                if length == 0.0 || height <= 0.0 {
+                    return false;
+                }
+                let d = [0, 1, 2].map(|i| p[i] - apex[i]);
+                let along = (d[0] * axis[0] + d[1] * axis[1] + d[2] * axis[2]) / length;
+                let across2 = d[0] * d[0] + d[1] * d[1] + d[2] * d[2] - along * along;
+                let reach = radius * along / height;
+                (0.0..=height).contains(&along) && across2 <= reach * reach
+            }
+            SpawnRegion::Plane { .. } | SpawnRegion::SpiralGalaxy { .. } => false,
+        }
+    }
+
+    // Rejects shapes `sample` can't draw from evenly, or at all
+    pub fn validate(&self) -> Result<(), String> {
+        let finite = |values: &[f32]| values.iter().all(|v| v.is_finite());
+        let nonzero = |v: [f32; 3]| v.iter().any(|&c| c != 0.0);
+        let ok = match *self {
+            SpawnRegion::Box {
+                center,
+                half_extents,
+            } => finite(&center) && finite(&half_extents) && half_extents.iter().all(|&h| h >= 0.0),
+            SpawnRegion::Annulus {
+                center,
+                inner_radius,
+                outer_radius,
+                thickness,
+            } => {
+                finite(&center)
+                    && finite(&[inner_radius, outer_radius, thickness])
+                    && 0.0 <= inner_radius
+                    && inner_radius <= outer_radius
+                    && thickness >= 0.0
+            }
+            SpawnRegion::Ring {
+                center,
+                radius,
+                tube_radius,
+            } => {
+                finite(&center)
+                    && finite(&[radius, tube_radius])
+                    && radius > 0.0
+                    && (0.0..=radius).contains(&tube_radius)
+            }
+            SpawnRegion::Cone {
+                apex,
+                axis,
+                height,
+                radius,
+            } => {
+                finite(&apex)
+                    && finite(&axis)
+                    && nonzero(axis)
+                    && finite(&[height, radius])
+                    && height > 0.0
+                    && radius >= 0.0
+            }
+            SpawnRegion::Plane {
+                center,
+                normal,
+                half_size,
+            } => {
+                finite(&center)
+                    && finite(&normal)
+                    && nonzero(normal)
+                    && half_size.is_finite()
+                    && half_size >= 0.0
+            }
+            SpawnRegion::SpiralGalaxy {
+                center,
+                arms,
+                pitch_angle,
+                arm_contrast,
+                inner_radius,
+                outer_radius,
+                thickness,
+            } => {
+                finite(&center)
+                    && finite(&[
+                        pitch_angle,
+                        arm_contrast,
+                        inner_radius,
+                        outer_radius,
+                        thickness,
+                    ])
+                    && arms > 0
+                    && pitch_angle > 0.0
+                    && pitch_angle < FRAC_PI_2
+                    && (0.0..=1.0).contains(&arm_contrast)
+                    && 0.0 < inner_radius
+                    && inner_radius <= outer_radius
+                    && thickness >= 0.0
+            }
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("Invalid spawn region {self:?}"))
+        }
+    }
+}
+
+// One part of a scene: where its particles start and how they move
+#[derive(Clone, Debug)]
+pub struct SpawnComponent {
+    pub region: SpawnRegion,
+    // Share of the particle budget, relative to the other components
+    pub weight: f32,
+    pub group: u32,
+    // Bulk velocity added to every particle
+    pub velocity: [f32; 3],
+    // Multiple of the circular orbital speed around the black hole (0 for none)
+    pub orbital_fraction: f32,
+}
+
+impl SpawnComponent {
+    // Parse one scripted component, a plain object such as
+    // `{ shape: "ring", center: [0, 0, 0], radius: 70, tube_radius: 10, weight: 1,
+    // group: 0, velocity: [0, 0, 0], orbital_fraction: 0.3 }`. The shape's own
+    // fields use the names of the `SpawnRegion` variant; `center` defaults to the
+    // origin, `weight` to 1 and the velocity model to at rest.
+    pub fn from_js(value: &JsValue) -> Result<Self, String> {
+        let field = |key: &str| {
+            js_sys::Reflect::get(value, &key.into())
+                .ok()
+                .filter(|v| !v.is_undefined() && !v.is_null())
+        };
+        let number = |key: &str, default: Option<f32>| match field(key) {
+            Some(v) => v
+                .as_f64()
+                .map(|v| v as f32)
+                .ok_or_else(|| format!("Spawn component '{key}' must be a number")),
+            None => default.ok_or_else(|| format!("Spawn component is missing '{key}'")),
+        };
+        let vector = |key: &str, default: Option<[f32; 3]>| match field(key) {
+            Some(v) => {
+                let values: Vec<f64> = js_sys::Array::from(&v)
+                    .iter()
+                    .filter_map(|c| c.as_f64())
+                    .collect();
+                match values[..] {
+                    [x, y, z] if js_sys::Array::is_array(&v) => Ok([x as f32, y as f32, z as f32]),
+                    _ => Err(format!(
+                        "Spawn component '{key}' must be an [x, y, z] array"
+                    )),
+                }
+            }
+            None => default.ok_or_else(|| format!("Spawn component is missing '{key}'")),
+        };
+
+        let shape = field("shape")
+            .and_then(|v| v.as_string())
+            .ok_or("Spawn component is missing its 'shape' name")?;
+        let center = || vector("center", Some([0.0; 3]));
+        let region = match shape.as_str() {
+            "box" => SpawnRegion::Box {
+                center: center()?,
+                half_extents: vector("half_extents", None)?,
+            },
+            "annulus" => SpawnRegion::Annulus {
+                center: center()?,
+                inner_radius: number("inner_radius", Some(0.0))?,
+                outer_radius: number("outer_radius", None)?,
+                thickness: number("thickness", Some(0.0))?,
+            },
+            "ring" => SpawnRegion::Ring {
+                center: center()?,
+                radius: number("radius", None)?,
+                tube_radius: number("tube_radius", None)?,
+            },
+            "cone" => SpawnRegion::Cone {
+                apex: vector("apex", Some([0.0; 3]))?,
+                axis: vector("axis", Some([0.0, 1.0, 0.0]))?,
+                height: number("height", None)?,
+                radius: number("radius", None)?,
+            },
+            "plane" => SpawnRegion::Plane {
+                center: center()?,
+                normal: vector("normal", Some([0.0, 1.0, 0.0]))?,
+                half_size: number("half_size", None)?,
+            },
+            "spiral_galaxy" => SpawnRegion::SpiralGalaxy {
+                center: center()?,
+                arms: number("arms", Some(2.0))? as u32,
+                pitch_angle: number("pitch_angle", Some(0.3))?,
+                arm_contrast: number("arm_contrast", Some(0.85))?,
+                inner_radius: number("inner_radius", None)?,
+                outer_radius: number("outer_radius", None)?,
+                thickness: number("thickness", Some(0.0))?,
+            },
+            other => {
+                return Err(format!(
+                    "Unknown spawn shape '{other}', expected one of: \
+                     box, annulus, ring, cone, plane, spiral_galaxy"
+                ))
+            }
+        };
+        region.validate()?;
+
+        let weight = number("weight", Some(1.0))?;
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(format!(
+                "Spawn component weight must be at least 0, got {weight}"
+            ));
+        }
+        let group = number("group", Some(0.0))?;
+        if group.fract() != 0.0 || !(0.0..NUM_GROUPS as f32).contains(&group) {
+            return Err(format!(
+                "Spawn component group must be an integer below {NUM_GROUPS}, got {group}"
+            ));
+        }
+        let velocity = vector("velocity", Some([0.0; 3]))?;
+        let orbital_fraction = number("orbital_fraction", Some(0.0))?;
+        if !velocity.iter().all(|v| v.is_finite()) || !orbital_fraction.is_finite() {
+            return Err("Spawn component velocity must be finite".to_string());
+        }
+
+        Ok(Self {
+            region,
+            weight,
+            group: group as u32,
+            velocity,
+            orbital_fraction,
+        })
+    }
+}
+
+// Fill `particle_count` particles from the scene's components, split by weight,
+// with orbital velocities for a central mass of gravitational parameter `gm`
+// inside `halo`, as given by `physics::halo_params`.
+// A positive `poisson_spacing` places each component's particles at least that
+// far apart where its region allows. Returns `None` if no component has a
+// positive weight.
+pub fn generate_scene(
+    components: &[SpawnComponent],
+    particle_count: u32,
+    gm: f32,
+    halo: [f32; 4],
+    poisson_spacing: f32,
+    seed: u64,
+) -> Option<Vec<Particle>> {
+    let total_weight: f32 = components.iter().map(|c| c.weight.max(0.0)).sum();
+    if total_weight <= 0.0 {
+        return None;
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut particles = Vec::with_capacity(particle_count as usize);
+    let last = components.iter().rposition(|c| c.weight > 0.0)?;
+
+    for (i, component) in components.iter().enumerate() {
+        if component.weight <= 0.0 {
+            continue;
+        }
+
+        // The last component takes the rounding remainder
+        let count = if i == last {
+            particle_count as usize - particles.len()
+        } else {
+            ((component.weight / total_weight) * particle_count as f32) as usize
+        };
+
+        let region = &component.region;
+        let positions: Vec<[f32; 3]> = match region.bounds() {
+            Some(bounds) if poisson_spacing > 0.0 => poisson::sample(
+                count,
+                poisson_spacing,
+                bounds,
+                |p| region.contains(p),
+                &mut rng,
+                |rng| region.sample(rng),
+            ),
+            _ => (0..count).map(|_| region.sample(&mut rng)).collect(),
+        };
+
+        for position in positions {
+            let mut velocity = component.velocity;
+            if component.orbital_fraction != 0.0 {
+                // Tangential around the Y axis, as in the default disk
+                let r = (position[0] * position[0] + position[2] * position[2]).sqrt();
+                if r > 1e-3 {
+                    let speed = physics::circular_speed(r, gm, halo) * component.orbital_fraction;
+                    velocity[0] += -position[2] / r * speed;
+                    velocity[2] += position[0] / r * speed;
+                }
+            }
+
+            particles.push(Particle {
+                position,
+                group: component.group,
+                velocity,
+                flags: 0,
+                acceleration: [0.0; 3],
+                temperature: 0.0,
+            });
+        }
+    }
+
+    Some(particles)
+}
+
+// `v` scaled to unit length, or +Y for the zero vector
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if length > 0.0 {
+        [v[0] / length, v[1] / length, v[2] / length]
+    } else {
+        [0.0, 1.0, 0.0]
+    }
+}
+
+// Two unit vectors perpendicular to `n` and to each other
+fn orthonormal_basis(n: [f32; 3]) -> ([f32; 3], [f32; 3]) {
+    let n = normalize(n);
+
+    // Branchless construction from Duff et al., "Building an Orthonormal Basis, Revisited"
+    let sign = 1.0f32.copysign(n[2]);
+    let a = -1.0 / (sign + n[2]);
+    let b = n[0] * n[1] * a;
+    (
+        [1.0 + sign * n[0] * n[0] * a, sign * b, -sign * n[0]],
+        [b, sign + n[1] * n[1] * a, -n[1]],
+    )
+}
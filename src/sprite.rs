@@ -0,0 +1,179 @@
+use wasm_bindgen::JsValue;
+
+// Size of the built-in soft dot, in texels per side
+const DEFAULT_SPRITE_SIZE: u32 = 32;
+// Larger textures are rejected rather than uploaded
+const MAX_SPRITE_SIZE: u32 = 1024;
+
+// Texture drawn on every particle billboard, bound as its own group so a new
+// sprite can be swapped in without rebuilding the particle bind groups
+pub struct ParticleSprite {
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+}
+
+impl ParticleSprite {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Sprite Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let texels = soft_dot(DEFAULT_SPRITE_SIZE);
+        let bind_group = Self::create_bind_group(
+            device,
+            queue,
+            &bind_group_layout,
+            &sampler,
+            (DEFAULT_SPRITE_SIZE, DEFAULT_SPRITE_SIZE),
+            &texels,
+        );
+
+        Self {
+            bind_group_layout,
+            bind_group,
+            sampler,
+        }
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    // Replace the sprite with `width` x `height` RGBA8 texels, row by row from
+    // the top. Color multiplies the particle color and alpha its opacity.
+    pub fn set_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<(), JsValue> {
+        if width == 0 || height == 0 || width > MAX_SPRITE_SIZE || height > MAX_SPRITE_SIZE {
+            return Err(JsValue::from_str(&format!(
+                "Sprite must be between 1x1 and {MAX_SPRITE_SIZE}x{MAX_SPRITE_SIZE}, got {width}x{height}"
+            )));
+        }
+        let expected = width as usize * height as usize * 4;
+        if rgba.len() != expected {
+            return Err(JsValue::from_str(&format!(
+                "A {width}x{height} RGBA sprite needs {expected} bytes, got {}",
+                rgba.len()
+            )));
+        }
+
+        self.bind_group = Self::create_bind_group(
+            device,
+            queue,
+            &self.bind_group_layout,
+            &self.sampler,
+            (width, height),
+            rgba,
+        );
+        Ok(())
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        (width, height): (u32, u32),
+        rgba: &[u8],
+    ) -> wgpu::BindGroup {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Sprite Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+}
+
+// White dot, opaque in the middle and fading to nothing at the edge
+fn soft_dot(size: u32) -> Vec<u8> {
+    let mut texels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let r = (u * u + v * v).sqrt();
+            let t = ((r - 0.25) / 0.75).clamp(0.0, 1.0);
+            let alpha = 1.0 - t * t * (3.0 - 2.0 * t);
+            texels.extend_from_slice(&[255, 255, 255, (alpha * 255.0).round() as u8]);
+        }
+    }
+    texels
+}
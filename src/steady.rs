@@ -0,0 +1,92 @@
+// Detects when the simulation has settled, from the periodic GPU reduction:
+// over a trailing window of simulated time the total energy must vary by less
+// than a fraction of itself and the centroid must stay within a distance.
+
+use crate::reduction::PositionStats;
+
+pub struct SteadyStateDetector {
+    // Largest (max - min) / |mean| of the total energy over the window
+    energy_tolerance: f32,
+    // Largest spread of the centroid over the window, in world units
+    drift_tolerance: f32,
+    // Simulated seconds the system must stay within both tolerances
+    window: f32,
+    // (time, total energy, centroid) of the reductions within the window
+    samples: Vec<(f64, f32, [f32; 3])>,
+    // Time of the first sample since tracking (re)started
+    start_time: Option<f64>,
+    reached: bool,
+}
+
+impl SteadyStateDetector {
+    pub fn new(energy_tolerance: f32, drift_tolerance: f32, window: f32) -> Self {
+        let mut detector = Self {
+            energy_tolerance: 0.0,
+            drift_tolerance: 0.0,
+            window: 0.0,
+            samples: Vec::new(),
+            start_time: None,
+            reached: false,
+        };
+        detector.set_tolerances(energy_tolerance, drift_tolerance, window);
+        detector
+    }
+
+    pub fn set_tolerances(&mut self, energy_tolerance: f32, drift_tolerance: f32, window: f32) {
+        let positive = |value: f32| if value.is_nan() { 0.0 } else { value.max(0.0) };
+        self.energy_tolerance = positive(energy_tolerance);
+        self.drift_tolerance = positive(drift_tolerance);
+        self.window = positive(window);
+        self.reset();
+    }
+
+    // Start over, so the steady state is reported again once reached
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.start_time = None;
+        self.reached = false;
+    }
+
+    // Feed the latest reduction; returns true on the frame the steady state is
+    // first reached
+    pub fn observe(&mut self, stats: &PositionStats) -> bool {
+        let time = stats.time;
+        match self.samples.last() {
+            Some(&(last, _, _)) if time == last => return false,
+            // Time went backwards: the simulation was restarted
+            Some(&(last, _, _)) if time < last => self.reset(),
+            _ => {}
+        }
+        let start_time = *self.start_time.get_or_insert(time);
+
+        let energy = stats.kinetic_energy + stats.potential_energy;
+        self.samples.push((time, energy, stats.centroid));
+        let window = self.window as f64;
+        self.samples.retain(|&(t, _, _)| t >= time - window);
+
+        if self.reached || time - start_time < window || self.samples.len() < 2 {
+            return false;
+        }
+
+        let (mut low, mut high, mut sum) = (f32::MAX, f32::MIN, 0.0);
+        let mut bounds = [[f32::MAX; 3], [f32::MIN; 3]];
+        for &(_, energy, centroid) in &self.samples {
+            low = low.min(energy);
+            high = high.max(energy);
+            sum += energy;
+            for axis in 0..3 {
+                bounds[0][axis] = bounds[0][axis].min(centroid[axis]);
+                bounds[1][axis] = bounds[1][axis].max(centroid[axis]);
+            }
+        }
+        let mean = (sum / self.samples.len() as f32).abs().max(f32::EPSILON);
+        let spread = (0..3)
+            .map(|axis| (bounds[1][axis] - bounds[0][axis]).powi(2))
+            .sum::<f32>()
+            .sqrt();
+
+        self.reached =
+            (high - low) / mean <= self.energy_tolerance && spread <= self.drift_tolerance;
+        self.reached
+    }
+}
@@ -0,0 +1,307 @@
+use crate::camera::Camera;
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+// Subpixel offsets cycle through this many points of the Halton (2, 3) sequence
+const JITTER_SAMPLES: u32 = 8;
+// Weight of the current frame once the history is full; lower converges to a
+// cleaner image but responds more slowly
+const MIN_BLEND: f32 = 0.1;
+// Scale on the history/current disagreement that discards history
+const MOTION_SENSITIVITY: f32 = 4.0;
+
+// Matches `Taa` in taa.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct TaaUniform {
+    blend: f32,
+    motion_sensitivity: f32,
+    _padding: [u32; 2],
+}
+
+// Temporal anti-aliasing. The projection is jittered by a subpixel offset
+// every frame, the scene is drawn into `current_view`, and the resolve blends
+// it into a history that ping-pongs between two textures. Accumulation starts
+// over whenever the camera moves or the particles restart; particles that move
+// on their own are handled in the shader by weighting the history down.
+pub struct Taa {
+    enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    // Bind group `i` reads history `i` and is drawn while writing the other
+    bind_groups: [wgpu::BindGroup; 2],
+    current_view: wgpu::TextureView,
+    history_views: [wgpu::TextureView; 2],
+    uniform_buffer: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    size: (u32, u32),
+    // History written this frame
+    write: usize,
+    // Frames accumulated since the last reset
+    frames: u32,
+    // Unjittered view-projection and simulation restart count of the last frame
+    last_view_proj: Option<[[f32; 4]; 4]>,
+    last_restarts: u32,
+}
+
+impl Taa {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+        enabled: bool,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("TAA Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/taa.wgsl").into()),
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("TAA Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[TaaUniform {
+                blend: 1.0,
+                motion_sensitivity: MOTION_SENSITIVITY,
+                _padding: [0; 2],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("TAA Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("TAA Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let target = Some(wgpu::ColorTargetState {
+            format,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("TAA Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_fullscreen"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_taa"),
+                targets: &[target.clone(), target],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let (current_view, history_views, bind_groups) =
+            Self::create_targets(device, &bind_group_layout, &uniform_buffer, format, size);
+
+        Self {
+            enabled,
+            pipeline,
+            bind_group_layout,
+            bind_groups,
+            current_view,
+            history_views,
+            uniform_buffer,
+            format,
+            size,
+            write: 0,
+            frames: 0,
+            last_view_proj: None,
+            last_restarts: 0,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+    ) -> (
+        wgpu::TextureView,
+        [wgpu::TextureView; 2],
+        [wgpu::BindGroup; 2],
+    ) {
+        let target = |label| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: size.0,
+                        height: size.1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let current_view = target("TAA Current Texture");
+        let history_views = [target("TAA History Texture"), target("TAA History Texture")];
+        let bind_groups = [0, 1].map(|read| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("TAA Bind Group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&current_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&history_views[read]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+        (current_view, history_views, bind_groups)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.reset();
+    }
+
+    // Drop the accumulated history; the next frame is drawn as is
+    pub fn reset(&mut self) {
+        self.frames = 0;
+    }
+
+    // Texture the scene should be drawn into while enabled
+    pub fn current_view(&self) -> &wgpu::TextureView {
+        &self.current_view
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        self.size = size;
+        (self.current_view, self.history_views, self.bind_groups) = Self::create_targets(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            self.format,
+            size,
+        );
+        self.reset();
+    }
+
+    // Start a frame: reset on camera motion or a particle restart (`restarts`
+    // being the simulation's restart count) and jitter the camera for it.
+    // Called before the camera uniforms are written; clears the jitter while
+    // disabled.
+    pub fn begin(&mut self, queue: &wgpu::Queue, camera: &mut Camera, restarts: u32) {
+        camera.set_jitter([0.0, 0.0]);
+        if !self.enabled {
+            return;
+        }
+
+        let view_proj: [[f32; 4]; 4] = camera.build_view_projection_matrix().into();
+        if self.last_view_proj != Some(view_proj) || self.last_restarts != restarts {
+            self.reset();
+        }
+        self.last_view_proj = Some(view_proj);
+        self.last_restarts = restarts;
+
+        let sample = self.frames % JITTER_SAMPLES + 1;
+        camera.set_jitter([
+            (halton(sample, 2) - 0.5) * 2.0 / self.size.0 as f32,
+            (halton(sample, 3) - 0.5) * 2.0 / self.size.1 as f32,
+        ]);
+
+        let uniform = TaaUniform {
+            blend: (1.0 / (self.frames + 1) as f32).max(MIN_BLEND),
+            motion_sensitivity: MOTION_SENSITIVITY,
+            _padding: [0; 2],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.write = 1 - self.write;
+        self.frames = self.frames.saturating_add(1);
+    }
+
+    // Resolve `current_view` against the history into `target`
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let attachment = |view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        };
+        let mut taa_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("TAA Pass"),
+            color_attachments: &[
+                attachment(target),
+                attachment(&self.history_views[self.write]),
+            ],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        taa_pass.set_pipeline(&self.pipeline);
+        taa_pass.set_bind_group(0, &self.bind_groups[1 - self.write], &[]);
+        taa_pass.draw(0..3, 0..1);
+    }
+}
+
+// Element `index` (from 1) of the Halton sequence in `base`, in 0..1
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
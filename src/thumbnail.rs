@@ -0,0 +1,141 @@
+// Offscreen render target for preview images: its own color and depth textures
+// at an arbitrary size, independent of the canvas, read back as RGBA bytes
+
+use crate::readback;
+use wasm_bindgen::JsValue;
+
+// Larger previews are rejected; thumbnails are meant to be cheap
+pub const MAX_THUMBNAIL_SIZE: u32 = 1024;
+
+pub struct ThumbnailTarget {
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    color_texture: wgpu::Texture,
+    color_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    padded_bytes_per_row: u32,
+    staging_buffer: wgpu::Buffer,
+}
+
+impl ThumbnailTarget {
+    // `format` must match the pipelines drawn into it and be a 4-byte format
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+    ) -> Result<Self, JsValue> {
+        let max = MAX_THUMBNAIL_SIZE.min(device.limits().max_texture_dimension_2d);
+        if width == 0 || height == 0 || width > max || height > max {
+            return Err(JsValue::from_str(&format!(
+                "Thumbnail must be between 1x1 and {max}x{max}, got {width}x{height}"
+            )));
+        }
+        if format.block_copy_size(None) != Some(4) {
+            return Err(JsValue::from_str(&format!(
+                "Cannot read back thumbnails in {format:?}"
+            )));
+        }
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = |label, format, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let color_texture = texture(
+            "Thumbnail Color Texture",
+            format,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let depth_view = texture(
+            "Thumbnail Depth Texture",
+            wgpu::TextureFormat::Depth32Float,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default());
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let padded_bytes_per_row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Thumbnail Staging Buffer"),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Ok(Self {
+            size: (width, height),
+            format,
+            color_texture,
+            color_view,
+            depth_view,
+            padded_bytes_per_row,
+            staging_buffer,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn color_view(&self) -> &wgpu::TextureView {
+        &self.color_view
+    }
+
+    pub fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth_view
+    }
+
+    // Copy the rendered image into the staging buffer; record after drawing
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_texture_to_buffer(
+            self.color_texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.staging_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: Some(self.size.1),
+                },
+            },
+            self.color_texture.size(),
+        );
+    }
+
+    // Tightly packed RGBA rows from the top, once the encoder passed to `copy`
+    // has been submitted
+    pub async fn read(self) -> Result<Vec<u8>, JsValue> {
+        readback::map_read(&self.staging_buffer).await?;
+        let row = self.size.0 as usize * 4;
+        let mut data = Vec::with_capacity(row * self.size.1 as usize);
+        {
+            let mapped = self.staging_buffer.slice(..).get_mapped_range();
+            for padded_row in mapped.chunks(self.padded_bytes_per_row as usize) {
+                data.extend_from_slice(&padded_row[..row]);
+            }
+        }
+        self.staging_buffer.unmap();
+
+        if matches!(
+            self.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in data.as_chunks_mut::<4>().0 {
+                pixel.swap(0, 2);
+            }
+        }
+        Ok(data)
+    }
+}
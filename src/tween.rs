@@ -0,0 +1,60 @@
+// Smooth transitions of named parameters: each tween eases from the value at
+// its start to a target over a duration in seconds. The caller reads and
+// writes the parameters themselves; this only produces the values.
+
+struct Tween {
+    name: &'static str,
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
+#[derive(Default)]
+pub struct Tweens {
+    active: Vec<Tween>,
+}
+
+impl Tweens {
+    // Start easing `name` from `from` to `to`, replacing any tween of the same
+    // parameter still running
+    pub fn start(&mut self, name: &'static str, from: f32, to: f32, duration: f32) {
+        self.active.retain(|tween| tween.name != name);
+        self.active.push(Tween {
+            name,
+            from,
+            to,
+            duration: duration.max(0.0),
+            elapsed: 0.0,
+        });
+    }
+
+    pub fn cancel(&mut self) {
+        self.active.clear();
+    }
+
+    // Advance every tween by `dt` seconds, returning each parameter's new
+    // value; finished tweens end exactly on their target and are dropped
+    pub fn advance(&mut self, dt: f32) -> Vec<(&'static str, f32)> {
+        let values = self
+            .active
+            .iter_mut()
+            .map(|tween| {
+                tween.elapsed += dt.max(0.0);
+                let t = if tween.duration > 0.0 {
+                    (tween.elapsed / tween.duration).min(1.0)
+                } else {
+                    1.0
+                };
+                if t >= 1.0 {
+                    return (tween.name, tween.to);
+                }
+                // Smoothstep, so the change starts and ends at rest
+                let eased = t * t * (3.0 - 2.0 * t);
+                (tween.name, tween.from + (tween.to - tween.from) * eased)
+            })
+            .collect();
+        self.active.retain(|tween| tween.elapsed < tween.duration);
+        values
+    }
+}
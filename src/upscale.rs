@@ -0,0 +1,217 @@
+// Fixed internal resolution: the scene is drawn into small color and depth
+// targets and blown up onto the surface with nearest-neighbor filtering, at
+// the largest integer scale that fits, centered between bars
+
+// Smallest internal size accepted on either axis; the upper bound is the
+// device's texture limit
+const MIN_INTERNAL_SIZE: u32 = 16;
+
+pub struct InternalTarget {
+    size: (u32, u32),
+    color_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl InternalTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+    ) -> Result<Self, wasm_bindgen::JsValue> {
+        let max = device.limits().max_texture_dimension_2d;
+        if width < MIN_INTERNAL_SIZE || height < MIN_INTERNAL_SIZE || width > max || height > max {
+            return Err(wasm_bindgen::JsValue::from_str(&format!(
+                "Internal resolution must be between {MIN_INTERNAL_SIZE} and {max} on each side, got {width}x{height}"
+            )));
+        }
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let target = |label, format, usage| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let sampled = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let color_view = target("Internal Color Texture", format, sampled);
+        // Sampled by the depth of field pass, like the full-size depth texture
+        let depth_view = target(
+            "Internal Depth Texture",
+            wgpu::TextureFormat::Depth32Float,
+            sampled,
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Blit Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/blit.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Blit Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Blit Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blit Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_fullscreen"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_blit"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Ok(Self {
+            size: (width, height),
+            color_view,
+            depth_view,
+            pipeline,
+            bind_group,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn color_view(&self) -> &wgpu::TextureView {
+        &self.color_view
+    }
+
+    pub fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth_view
+    }
+
+    // Where the image lands on a surface of `surface` pixels, as (x, y, scale).
+    // Scales are whole numbers unless the surface is smaller than the target.
+    fn placement(&self, surface: (u32, u32)) -> (f32, f32, f32) {
+        let fit =
+            (surface.0 as f32 / self.size.0 as f32).min(surface.1 as f32 / self.size.1 as f32);
+        let scale = if fit >= 1.0 { fit.floor() } else { fit };
+        let x = ((surface.0 as f32 - self.size.0 as f32 * scale) * 0.5).floor();
+        let y = ((surface.1 as f32 - self.size.1 as f32 * scale) * 0.5).floor();
+        (x, y, scale)
+    }
+
+    // Surface pixel -> internal pixel, `None` over the bars
+    pub fn to_internal(&self, surface: (u32, u32), position: (f32, f32)) -> Option<(f32, f32)> {
+        let (x, y, scale) = self.placement(surface);
+        let internal = ((position.0 - x) / scale, (position.1 - y) / scale);
+        let inside = (0.0..self.size.0 as f32).contains(&internal.0)
+            && (0.0..self.size.1 as f32).contains(&internal.1);
+        inside.then_some(internal)
+    }
+
+    // Draw the target onto `surface_view`, clearing the bars to `bars`
+    pub fn blit(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        surface_view: &wgpu::TextureView,
+        surface: (u32, u32),
+        bars: wgpu::Color,
+    ) {
+        let (x, y, scale) = self.placement(surface);
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blit Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(bars),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_viewport(
+            x,
+            y,
+            self.size.0 as f32 * scale,
+            self.size.1 as f32 * scale,
+            0.0,
+            1.0,
+        );
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -5,6 +5,11 @@ pub fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
+// Clamp color components from JS to [0, 1], treating NaN as 0
+pub fn clamp_color(color: [f32; 3]) -> [f32; 3] {
+    color.map(|c| if c.is_nan() { 0.0 } else { c.clamp(0.0, 1.0) })
+}
+
 #[allow(unused_macros)]
 macro_rules! console_log {
     ( $( $t:tt )* ) => {